@@ -0,0 +1,47 @@
+// The shortest distance from every node to every other node
+#[derive(Debug, Clone)]
+pub struct DistanceTable {
+    n_nodes: usize,
+    // None if there is no route
+    distances: Box<[Option<u32>]>,
+}
+
+impl DistanceTable {
+    // Builds a table from a function that returns the distance between
+    // two nodes, or None if there is no route
+    pub fn from_fn<F>(n_nodes: usize, func: F) -> DistanceTable
+        where F: Fn(usize, usize) -> Option<u32>
+    {
+        let distances = (0..n_nodes * n_nodes).map(|i| {
+            func(i / n_nodes, i % n_nodes)
+        }).collect();
+
+        DistanceTable { n_nodes, distances }
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.n_nodes
+    }
+
+    // Returns None if there is no route
+    pub fn get(&self, from: usize, to: usize) -> Option<u32> {
+        self.distances[from * self.n_nodes + to]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_fn() {
+        let distances = DistanceTable::from_fn(3, |from, to| {
+            (to != 2).then_some((from * 10 + to) as u32)
+        });
+
+        assert_eq!(distances.n_nodes(), 3);
+        assert_eq!(distances.get(0, 1), Some(1));
+        assert_eq!(distances.get(2, 0), Some(20));
+        assert_eq!(distances.get(1, 2), None);
+    }
+}
@@ -0,0 +1,87 @@
+mod distance_table;
+// The solver can also find round trips but Santa doesn’t need to get home
+#[allow(dead_code)]
+mod tsp;
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+use distance_table::DistanceTable;
+use tsp::{Route, Goal};
+
+struct Map {
+    names: Vec<String>,
+    distances: HashMap<(usize, usize), u32>,
+}
+
+impl Map {
+    fn city_num(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(city_num) => city_num,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            },
+        }
+    }
+
+    fn distance_table(&self) -> DistanceTable {
+        DistanceTable::from_fn(self.names.len(), |a, b| {
+            self.distances.get(&(a, b)).copied()
+        })
+    }
+}
+
+fn read_map<I>(lines: &mut I) -> Result<Map, String>
+    where I: Iterator<Item = Result<String, std::io::Error>>
+{
+    let re = regex::Regex::new(r"^(\w+) to (\w+) = (\d+)$").unwrap();
+    let mut map = Map { names: Vec::new(), distances: HashMap::new() };
+
+    for (line_num, result) in lines.enumerate() {
+        let line = result.map_err(|e| e.to_string())?;
+
+        let captures = re.captures(&line).ok_or_else(|| {
+            format!("line {}: invalid syntax", line_num + 1)
+        })?;
+
+        let distance = captures[3].parse::<u32>().map_err(|e| {
+            format!("line {}: {}", line_num + 1, e)
+        })?;
+
+        let a = map.city_num(&captures[1]);
+        let b = map.city_num(&captures[2]);
+
+        map.distances.insert((a, b), distance);
+        map.distances.insert((b, a), distance);
+    }
+
+    Ok(map)
+}
+
+fn main() -> ExitCode {
+    let map = match read_map(&mut std::io::stdin().lines()) {
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+        Ok(map) => map,
+    };
+
+    let distances = map.distance_table();
+
+    for (part, goal) in [(1, Goal::Minimise), (2, Goal::Maximise)] {
+        let Some(tour) = tsp::solve(&distances, None, Route::Open, goal)
+        else {
+            eprintln!("no route visits every city");
+            return ExitCode::FAILURE;
+        };
+
+        let route = tour.order.iter()
+            .map(|&city_num| map.names[city_num].as_str())
+            .collect::<Vec<_>>();
+
+        println!("part {}: {} ({})", part, tour.cost, route.join(" -> "));
+    }
+
+    ExitCode::SUCCESS
+}
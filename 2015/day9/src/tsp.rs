@@ -0,0 +1,295 @@
+use crate::distance_table::DistanceTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    // The route can finish at any node
+    Open,
+    // The route has to get back to where it started
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    Minimise,
+    Maximise,
+}
+
+impl Goal {
+    fn is_better(self, cost: u32, other: u32) -> bool {
+        match self {
+            Goal::Minimise => cost < other,
+            Goal::Maximise => cost > other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tour {
+    pub cost: u32,
+    // Every node in the order that they are visited. For a closed
+    // route the return to the first node isn’t included.
+    pub order: Vec<usize>,
+}
+
+// Finds the best route that visits every node in the table once using
+// the Held–Karp algorithm. This keeps the best cost for every set of
+// visited nodes and last node so it takes O(2ⁿ·n²) time instead of
+// trying all n! orders. If start is None then the route can start
+// anywhere. Returns None if there is no route that reaches every node.
+pub fn solve(
+    distances: &DistanceTable,
+    start: Option<usize>,
+    route: Route,
+    goal: Goal,
+) -> Option<Tour> {
+    let n_nodes = distances.n_nodes();
+
+    if n_nodes == 0 {
+        return None;
+    }
+
+    // Where a closed route starts doesn’t matter
+    let start = match route {
+        Route::Open => start,
+        Route::Closed => Some(start.unwrap_or(0)),
+    };
+
+    let n_masks = 1usize << n_nodes;
+    // The best cost and the previous node for each mask of visited
+    // nodes and the last node visited
+    let mut costs = vec![None::<u32>; n_masks * n_nodes];
+    let mut parents = vec![usize::MAX; n_masks * n_nodes];
+
+    for first in 0..n_nodes {
+        if start.is_none_or(|start| start == first) {
+            costs[(1 << first) * n_nodes + first] = Some(0);
+        }
+    }
+
+    // Adding a node always makes the mask bigger so iterating the
+    // masks in order visits every subset before its supersets
+    for mask in 1..n_masks {
+        for last in 0..n_nodes {
+            let Some(cost) = costs[mask * n_nodes + last]
+            else { continue; };
+
+            for next in 0..n_nodes {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+
+                let Some(distance) = distances.get(last, next)
+                else { continue; };
+
+                let index = (mask | (1 << next)) * n_nodes + next;
+                let cost = cost + distance;
+
+                if costs[index].is_none_or(|old| goal.is_better(cost, old)) {
+                    costs[index] = Some(cost);
+                    parents[index] = last;
+                }
+            }
+        }
+    }
+
+    let full_mask = n_masks - 1;
+    let mut best: Option<(u32, usize)> = None;
+
+    for last in 0..n_nodes {
+        let Some(cost) = costs[full_mask * n_nodes + last]
+        else { continue; };
+
+        let cost = match route {
+            Route::Open => cost,
+            Route::Closed => {
+                let Some(distance) = distances.get(last, start.unwrap())
+                else { continue; };
+
+                cost + distance
+            },
+        };
+
+        if best.is_none_or(|(best_cost, _)| goal.is_better(cost, best_cost)) {
+            best = Some((cost, last));
+        }
+    }
+
+    let (cost, mut last) = best?;
+
+    // Follow the parents back to the start to get the order
+    let mut order = Vec::with_capacity(n_nodes);
+    let mut mask = full_mask;
+
+    loop {
+        order.push(last);
+
+        let parent = parents[mask * n_nodes + last];
+
+        mask &= !(1 << last);
+
+        if mask == 0 {
+            break;
+        }
+
+        last = parent;
+    }
+
+    order.reverse();
+
+    Some(Tour { cost, order })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn route_cost(
+        distances: &DistanceTable,
+        order: &[usize],
+        route: Route,
+    ) -> Option<u32> {
+        let mut cost = 0;
+
+        for pair in order.windows(2) {
+            cost += distances.get(pair[0], pair[1])?;
+        }
+
+        if route == Route::Closed {
+            cost += distances.get(*order.last()?, order[0])?;
+        }
+
+        Some(cost)
+    }
+
+    // Tries every order to find the best cost
+    fn brute_force(
+        distances: &DistanceTable,
+        start: Option<usize>,
+        route: Route,
+        goal: Goal,
+    ) -> Option<u32> {
+        let mut order = (0..distances.n_nodes()).collect::<Vec<_>>();
+        let mut stack = vec![0];
+        let mut best = None::<u32>;
+
+        // Heap’s algorithm without recursion
+        let mut check = |order: &[usize]| {
+            if start.is_some_and(|start| route == Route::Open
+                                 && order[0] != start)
+            {
+                return;
+            }
+
+            if let Some(cost) = route_cost(distances, order, route) {
+                if best.is_none_or(|best| goal.is_better(cost, best)) {
+                    best = Some(cost);
+                }
+            }
+        };
+
+        check(&order);
+        stack.resize(order.len(), 0);
+
+        let mut i = 1;
+
+        while i < order.len() {
+            if stack[i] < i {
+                order.swap(if i % 2 == 0 { 0 } else { stack[i] }, i);
+                check(&order);
+                stack[i] += 1;
+                i = 1;
+            } else {
+                stack[i] = 0;
+                i += 1;
+            }
+        }
+
+        best
+    }
+
+    #[test]
+    fn cities() {
+        // London, Dublin and Belfast
+        let table = [[0, 464, 518], [464, 0, 141], [518, 141, 0]];
+        let distances = DistanceTable::from_fn(3, |a, b| Some(table[a][b]));
+
+        let tour = solve(&distances, None, Route::Open, Goal::Minimise)
+            .unwrap();
+        assert_eq!(tour.cost, 605);
+        assert_eq!(route_cost(&distances, &tour.order, Route::Open), Some(605));
+
+        let tour = solve(&distances, None, Route::Open, Goal::Maximise)
+            .unwrap();
+        assert_eq!(tour.cost, 982);
+        assert_eq!(tour.order.len(), 3);
+
+        let tour = solve(&distances, Some(0), Route::Closed, Goal::Minimise)
+            .unwrap();
+        assert_eq!(tour.cost, 464 + 141 + 518);
+        assert_eq!(tour.order[0], 0);
+
+        // Can’t get back to the start
+        let distances = DistanceTable::from_fn(3, |a, b| {
+            (b != 0).then_some(table[a][b])
+        });
+        assert_eq!(solve(&distances, None, Route::Closed, Goal::Minimise), None);
+        assert_eq!(
+            solve(&distances, None, Route::Open, Goal::Minimise),
+            Some(Tour { cost: 605, order: vec![0, 1, 2] }),
+        );
+    }
+
+    #[test]
+    fn compare_brute_force() {
+        let mut seed = 7u32;
+
+        for n_nodes in 1..=6 {
+            for _ in 0..20 {
+                let mut random = || {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    (seed >> 16) % 100
+                };
+                let table = (0..n_nodes * n_nodes)
+                    .map(|_| {
+                        let distance = random();
+                        (distance >= 10).then_some(distance)
+                    })
+                    .collect::<Vec<_>>();
+                let distances = DistanceTable::from_fn(n_nodes, |a, b| {
+                    table[a * n_nodes + b]
+                });
+
+                for start in [None, Some(n_nodes - 1)] {
+                    for route in [Route::Open, Route::Closed] {
+                        for goal in [Goal::Minimise, Goal::Maximise] {
+                            let tour = solve(&distances, start, route, goal);
+
+                            assert_eq!(
+                                tour.as_ref().map(|tour| tour.cost),
+                                brute_force(&distances, start, route, goal),
+                            );
+
+                            let Some(tour) = tour
+                            else { continue; };
+
+                            let mut sorted = tour.order.clone();
+                            sorted.sort_unstable();
+                            assert!(sorted.into_iter().eq(0..n_nodes));
+
+                            assert_eq!(
+                                route_cost(&distances, &tour.order, route),
+                                Some(tour.cost),
+                            );
+
+                            if route == Route::Open {
+                                if let Some(start) = start {
+                                    assert_eq!(tour.order[0], start);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
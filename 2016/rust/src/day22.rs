@@ -1,464 +1,286 @@
-use std::rc::Rc;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
-use std::io;
-use std::io::BufRead;
-use std::borrow::Borrow;
-
-const N_DIRECTIONS: u8 = 4;
-
-struct Node {
-    direction: u8,
-    depth: u32,
-    score: u32,
-    parent: Option<Rc<Node>>
+mod astar;
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::process::ExitCode;
+use astar::SearchState;
+
+static DIRECTIONS: [(char, i32, i32); 4] = [
+    ('U', 0, -1),
+    ('D', 0, 1),
+    ('L', -1, 0),
+    ('R', 1, 0),
+];
+
+struct Device {
+    x: usize,
+    y: usize,
+    size: u32,
+    used: u32,
 }
 
-impl Node {
-    fn new(direction: u8,
-           score: u32,
-           parent: Option<Rc<Node>>) -> Node {
-        let depth = match parent {
-            Some(ref p) => p.depth + 1,
-            None => 1
-        };
-        Node {
-            direction: direction,
-            score: score,
-            parent: parent,
-            depth: depth
-        }
-    }
-
-    fn less(&self, other: &Node) -> bool {
-        macro_rules! compare {
-            ($field:ident) => {{
-                if self.$field < other.$field {
-                    return true;
-                }
-                if self.$field > other.$field {
-                    return false;
-                }
-            }}
-        }
-
-        compare!(score);
-        compare!(depth);
-        compare!(direction);
-
-        false
-    }
+struct Puzzle {
+    width: usize,
+    height: usize,
+    // True for the nodes that have too much data to ever be moved
+    walls: Vec<bool>,
+    start_state: State,
 }
 
-struct NodeHeap {
-    entries: Vec<Rc<Node>>
+// Which node is empty and which node has the data that we want, as
+// indices into the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    empty: u16,
+    goal: u16,
 }
 
-// Implements a priority queue. I know that Rust has a type for this
-// builtin, but it was a learning exercise to implement it myself.
-impl NodeHeap {
-    fn new() -> NodeHeap {
-        NodeHeap { entries: Vec::new() }
-    }
-
-    fn push(&mut self, node: Rc<Node>) {
-        let mut pos = self.entries.len();
-        self.entries.push(node);
-
-        while pos > 0 {
-            let parent = (pos - 1) / 2;
-
-            if !self.entries[pos].less(&self.entries[parent]) {
-                break;
-            }
-
-            self.entries.swap(pos, parent);
-
-            pos = parent;
-        }
+impl Puzzle {
+    fn pos(&self, index: u16) -> (usize, usize) {
+        (index as usize % self.width, index as usize / self.width)
     }
 
-    fn pop(&mut self) -> Rc<Node> {
-        if self.entries.len() <= 1 {
-            return self.entries.pop().unwrap();
+    // Returns the state after moving the data from the node in the
+    // given direction into the empty node, or None if that isn’t
+    // possible
+    fn apply_move(&self, state: State, (dx, dy): (i32, i32)) -> Option<State> {
+        let (x, y) = self.pos(state.empty);
+        let x = x.checked_add_signed(dx as isize)
+            .filter(|&x| x < self.width)?;
+        let y = y.checked_add_signed(dy as isize)
+            .filter(|&y| y < self.height)?;
+        let index = y * self.width + x;
+
+        if self.walls[index] {
+            return None;
         }
 
-        // The first entry is the at the root of the heap and we want
-        // to return it. However we also want to temporarily move the
-        // last entry to be the new root. To do this will swap the
-        // entries and then remove the old root with a pop.
-
-        let last = self.entries.len() - 1;
-        self.entries.swap(0, last);
-        let ret = self.entries.pop().unwrap();
-
-        let mut pos = 0;
-
-        loop {
-            let child = 2 * pos + 1;
-
-            if child >= self.entries.len() {
-                break;
-            }
-
-            // Find the smallest of the three nodes
-            let mut smallest = pos;
-
-            if self.entries[child].less(&self.entries[smallest]) {
-                smallest = child;
-            }
+        let index = index as u16;
 
-            if child + 1 < self.entries.len() &&
-                self.entries[child + 1].less(&self.entries[smallest]) {
-                smallest = child + 1;
-            }
-
-            // If the root is already the smallest then they are
-            // already in the correct order
-            if smallest == pos {
-                break;
-            }
-
-            self.entries.swap(pos, smallest);
-
-            pos = smallest;
-        }
-
-        ret
+        Some(State {
+            empty: index,
+            goal: if index == state.goal { state.empty } else { state.goal },
+        })
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
-struct Pos {
-    x: i8,
-    y: i8
+// The search state also needs the puzzle to know where the walls are
+// but only the compact state is used to compare and hash it
+#[derive(Clone, Copy)]
+struct Search<'a> {
+    puzzle: &'a Puzzle,
+    state: State,
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
-struct State {
-    zero_pos: Pos,
-    goal_pos: Pos,
+impl<'a> PartialEq for Search<'a> {
+    fn eq(&self, other: &Search<'a>) -> bool {
+        self.state == other.state
+    }
 }
 
-struct Puzzle {
-    width: i8,
-    height: i8,
-    start_state: State,
-    board: Vec<u8>
-}
+impl<'a> Eq for Search<'a> {}
 
-impl Puzzle {
-    fn is_valid_position(&self, pos: &Pos) -> bool {
-        if pos.x < 0 || pos.x >= self.width ||
-            pos.y < 0 || pos.y >= self.height {
-            false
-        } else {
-            self.board[pos.x as usize + pos.y as usize *
-                       self.width as usize] as char != '#'
-        }
+impl<'a> Hash for Search<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state.hash(state);
     }
 }
 
-impl State {
-    fn score(&self, puzzle: &Puzzle) -> u32 {
-        let goal_x = self.goal_pos.x as i32;
-        let goal_y = self.goal_pos.y as i32;
-        let zero_x = self.zero_pos.x as i32;
-        let zero_y = self.zero_pos.y as i32;
-        let puzzle_width = puzzle.width as i32;
-        let puzzle_height = puzzle.height as i32;
-
-        ((goal_x + goal_y) * (puzzle_width + puzzle_height) +
-         (goal_x - zero_x).abs() + (goal_y - zero_y).abs())
-            as u32
+fn distance((ax, ay): (usize, usize), (bx, by): (usize, usize)) -> u64 {
+    (ax.abs_diff(bx) + ay.abs_diff(by)) as u64
+}
+
+impl<'a> SearchState for Search<'a> {
+    fn successors(&self) -> impl Iterator<Item = (Search<'a>, u64)> {
+        DIRECTIONS.iter().filter_map(|&(_, dx, dy)| {
+            self.puzzle.apply_move(self.state, (dx, dy)).map(|state| {
+                (Search { puzzle: self.puzzle, state }, 1)
+            })
+        })
     }
 
-    fn apply_move(&mut self, direction: u8) {
-        let old_pos = self.zero_pos;
-        let diff = if (direction & 1) == 0 { -1 } else { 1 };
+    // The empty node has to get next to the goal data and swap with
+    // it. After that, every step that the goal data takes needs at
+    // least two moves to get the empty node from behind it to the
+    // next side and then the swap.
+    fn heuristic(&self) -> u64 {
+        let goal = self.puzzle.pos(self.state.goal);
+        let goal_distance = distance(goal, (0, 0));
 
-        if (direction & 2) == 0 {
-            self.zero_pos.y += diff;
+        if goal_distance == 0 {
+            0
         } else {
-            self.zero_pos.x += diff;
+            distance(self.puzzle.pos(self.state.empty), goal)
+                + (goal_distance - 1) * 3
         }
+    }
 
-        if self.zero_pos == self.goal_pos {
-            self.goal_pos = old_pos;
-        }
+    fn is_goal(&self) -> bool {
+        self.state.goal == 0
     }
 }
 
-struct History {
-    entries: HashMap<State, u32>
+// Draws the grid in the same notation as the puzzle description
+struct Frame<'a> {
+    puzzle: &'a Puzzle,
+    state: State,
 }
 
-impl History {
-    fn new() -> History {
-        History { entries: HashMap::new() }
-    }
+impl<'a> fmt::Display for Frame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let puzzle = self.puzzle;
+
+        for y in 0..puzzle.height {
+            if y > 0 {
+                writeln!(f)?;
+            }
+
+            for x in 0..puzzle.width {
+                let index = y * puzzle.width + x;
+
+                let ch = if index == self.state.empty as usize {
+                    '_'
+                } else if index == self.state.goal as usize {
+                    'G'
+                } else if puzzle.walls[index] {
+                    '#'
+                } else {
+                    '.'
+                };
 
-    fn add(&mut self, state: &State, depth: u32) -> bool {
-        // Add the entry to the hash map if it’s not already there or
-        // the existing entry has a higher depth
-        match self.entries.entry(*state) {
-            Entry::Vacant(v) => {
-                v.insert(depth);
-                true
-            },
-            Entry::Occupied(mut o) => {
-                let mut entry_depth = o.get_mut();
-
-                if *entry_depth > depth {
-                    *entry_depth = depth;
-                    true
+                if index == 0 {
+                    write!(f, "({})", ch)?;
                 } else {
-                    false
+                    write!(f, " {} ", ch)?;
                 }
             }
         }
+
+        Ok(())
     }
 }
 
-struct SearchData {
-    history: History,
-    node_heap: NodeHeap,
-
-    // This is just used as a small temporary buffer to be able to get
-    // the directions from the nodes and then process them in reverse
-    // order. The C version was using a vla on the stack for this but
-    // there doesn’t seem to be an equivalent in Rust.
-    direction_list: Vec<u8>
+fn parse_device(line: &str) -> Option<Device> {
+    let mut parts = line.split_whitespace();
+    let (x, y) = parts.next()?
+        .strip_prefix("/dev/grid/node-x")?
+        .split_once("-y")?;
+    let size = parts.next()?.strip_suffix('T')?.parse().ok()?;
+    let used = parts.next()?.strip_suffix('T')?.parse().ok()?;
+
+    Some(Device {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        size,
+        used,
+    })
 }
 
-fn expand_position(search_data: &mut SearchData,
-                   parent: Option<Rc<Node>>,
-                   puzzle: &Puzzle,
-                   start_state: &State) {
-    for direction in 0..N_DIRECTIONS {
-        let mut move_state = *start_state;
-        move_state.apply_move(direction);
-
-        if !puzzle.is_valid_position(&move_state.zero_pos) {
-            continue;
-        }
+fn read_puzzle() -> Result<Puzzle, String> {
+    let mut devices = Vec::new();
 
-        let depth = match parent {
-            Some(ref node) => node.depth + 1,
-            None => 1
-        };
+    for (line_num, line) in std::io::stdin().lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
 
-        if !search_data.history.add(&move_state, depth) {
+        if line.starts_with("root@") || line.starts_with("Filesystem") {
             continue;
         }
 
-        let score = move_state.score(&puzzle);
-        let node = Rc::new(Node::new(direction, score, parent.clone()));
-        search_data.node_heap.push(node);
+        devices.push(parse_device(&line).ok_or_else(|| {
+            format!("line {}: invalid syntax", line_num + 1)
+        })?);
     }
-}
 
-fn get_node_state(search_data: &mut SearchData,
-                  puzzle: &Puzzle,
-                  last_node: Rc<Node>,
-                  state: &mut State)
-{
-    search_data.direction_list.clear();
-
-    let mut node = last_node;
-
-    loop {
-        search_data.direction_list.push(node.direction);
-
-        node = match node.parent {
-            Some(ref n) => n.clone(),
-            None => break
-        }
-    }
+    let Some(min_size) = devices.iter().map(|d| d.size).min()
+    else {
+        return Err("no devices found".to_string());
+    };
 
-    *state = puzzle.start_state;
+    let width = devices.iter().map(|d| d.x).max().unwrap() + 1;
+    let height = devices.iter().map(|d| d.y).max().unwrap() + 1;
 
-    for direction in search_data.direction_list.iter().rev() {
-        state.apply_move(*direction);
+    if width * height > u16::MAX as usize {
+        return Err("grid is too big".to_string());
     }
-}
 
-fn expand_initial_nodes(search_data: &mut SearchData,
-                        puzzle: &Puzzle) {
-    expand_position(search_data,
-                    None, // parent
-                    puzzle,
-                    &puzzle.start_state);
-}
+    let mut walls = vec![false; width * height];
+    let mut empty = None;
 
-fn skip_string(p: &mut std::str::Chars, string: &str) -> Result<(), io::Error> {
-    for ch in string.chars() {
-        match p.next() {
-            Some(c) if c == ch => (),
-            _ => return Err(io::Error::new(io::ErrorKind::InvalidData,
-                                           "bad line"))
+    for d in devices.iter() {
+        let index = d.y * width + d.x;
 
-        }
-    }
+        // Any node with more data than the smallest node can hold can
+        // never be moved
+        walls[index] = d.used > min_size;
 
-    Ok(())
-}
-
-fn parse_number(p: &mut std::str::Chars) -> Result<i32, io::Error> {
-    let rest = p.as_str();
-    // Find the byte index of the first non-digit, or if not the
-    // length of the string in bytes
-    let length = match rest.char_indices().find(|&(_, ch)|
-                                                ch < '0' ||
-                                                ch > '9') {
-        Some((pos, _)) => pos,
-        None => rest.len()
-    };
-    // Skip the iterator ahead to the end of the number
-    p.clone_from(&rest[length..].chars());
-    // Parse the number using the substring
-    match rest[0..length].parse() {
-        Ok(n) => Ok(n),
-        Err(_) =>
-            Err(io::Error::new(io::ErrorKind::InvalidData, "bad line"))
-    }
-}
-
-fn skip_spaces(p: &mut std::str::Chars) {
-    let mut end = p.clone();
+        if d.used == 0 {
+            if empty.is_some() {
+                return Err("more than one empty node".to_string());
+            }
 
-    while let Some(n) = end.next() {
-        if n != ' ' {
-            break;
+            empty = Some(index as u16);
         }
-        p.next();
     }
-}
 
-fn read_board() -> Result<Puzzle, io::Error> {
-    struct Device {
-        pos: Pos,
-        used: i32
+    let Some(empty) = empty
+    else {
+        return Err("no empty node".to_string());
     };
-    let mut devices = Vec::<Device>::new();
 
-    let mut puzzle = Puzzle {
-        width: 0,
-        height: 0,
+    Ok(Puzzle {
+        width,
+        height,
+        walls,
         start_state: State {
-            goal_pos: Pos { x: 0, y: 0 },
-            zero_pos: Pos { x: 0, y: 0 },
+            empty,
+            goal: (width - 1) as u16,
         },
-        board: Vec::new()
-    };
-
-    let mut min_size = i32::max_value();
-
-    let stdin = io::stdin();
-
-    for line in stdin.lock().lines() {
-        let line = try!(line);
-
-        if line.starts_with("root@") || line.starts_with("Filesystem") {
-            continue;
-        }
-
-        let mut p = line.chars();
-
-        try!(skip_string(&mut p, "/dev/grid/node-x"));
-        let x = try!(parse_number(&mut p)) as i8;
-        try!(skip_string(&mut p, "-y"));
-        let y = try!(parse_number(&mut p)) as i8;
-        skip_spaces(&mut p);
-        let size = try!(parse_number(&mut p));
-        try!(skip_string(&mut p, "T"));
-        skip_spaces(&mut p);
-        let used = try!(parse_number(&mut p));
-
-        if x > puzzle.width {
-            puzzle.width = x;
-        }
-        if y > puzzle.height {
-            puzzle.height = y;
-        }
-        if size < min_size {
-            min_size = size;
-        }
+    })
+}
 
-        devices.push(Device {
-            pos: Pos { x: x, y: y },
-            used: used
-        });
-    }
+fn print_solution(puzzle: &Puzzle, steps: &[State]) {
+    println!("{}", Frame { puzzle, state: steps[0] });
 
-    puzzle.width += 1;
-    puzzle.height += 1;
-    puzzle.board.resize(puzzle.width as usize *
-                        puzzle.height as usize,
-                        ' ' as u8);
-
-    for d in devices {
-        puzzle.board[d.pos.x as usize + d.pos.y as usize *
-                     puzzle.width as usize] =
-            if d.used <= min_size {
-                ' ' as u8
-            } else {
-                '#' as u8
-            };
+    for (move_num, pair) in steps.windows(2).enumerate() {
+        let (x, y) = puzzle.pos(pair[1].empty);
+        let &(name, _, _) = DIRECTIONS.iter()
+            .find(|&&(_, dx, dy)| {
+                puzzle.apply_move(pair[0], (dx, dy)) == Some(pair[1])
+            })
+            .unwrap();
 
-        if d.used == 0 {
-            puzzle.start_state.zero_pos = d.pos;
-        }
+        println!();
+        println!("Move {}: {} to ({}, {})", move_num + 1, name, x, y);
+        println!("{}", Frame { puzzle, state: pair[1] });
     }
-
-    puzzle.start_state.goal_pos.x = puzzle.width - 1;
-    puzzle.start_state.goal_pos.y = 0;
-
-    Ok(puzzle)
 }
 
-#[allow(unused_variables)]
-fn print_solution(puzzle: &Puzzle,
-                  node: &Node)
-{
-    print!("{}\n", node.depth);
-}
-
-fn solve(puzzle: &Puzzle) {
-    let mut search_data = SearchData {
-        history: History::new(),
-        node_heap: NodeHeap::new(),
-        direction_list: Vec::new()
+fn main() -> ExitCode {
+    let puzzle = match read_puzzle() {
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+        Ok(puzzle) => puzzle,
     };
-    let mut best_score = u32::max_value();
 
-    expand_initial_nodes(&mut search_data, puzzle);
+    let start = Search { puzzle: &puzzle, state: puzzle.start_state };
 
-    while search_data.node_heap.entries.len() > 0 {
-        let node = search_data.node_heap.pop();
+    let Some(path) = astar::astar(start)
+    else {
+        eprintln!("the goal data can’t reach the target node");
+        return ExitCode::FAILURE;
+    };
 
-        if node.depth > best_score {
-            continue;
-        }
+    let steps = path.steps.iter()
+        .map(|(search, _)| search.state)
+        .collect::<Vec<_>>();
 
-        let mut state = puzzle.start_state;
-        get_node_state(&mut search_data, puzzle, node.clone(), &mut state);
+    print_solution(&puzzle, &steps);
 
-        if state.goal_pos.x == 0 && state.goal_pos.y == 0 {
-            best_score = node.depth;
-            print_solution(puzzle, node.borrow());
-        } else {
-            expand_position(&mut search_data, Some(node), puzzle, &state);
-        }
-    }
-}
+    println!();
+    println!("States expanded: {}", path.n_expanded);
+    println!("Part 2: {}", path.cost);
 
-fn main() {
-    match read_board() {
-        Err(e) => {
-            print!("{}\n", e.to_string());
-            return;
-        },
-        Ok(n) => solve(&n)
-    }
+    ExitCode::SUCCESS
 }
@@ -1,5 +1,11 @@
+// The graph modules have more operations than this day needs
+#[allow(dead_code)]
+mod graph;
+#[allow(dead_code)]
+mod distance_table;
+
 use std::process::ExitCode;
-use std::collections::HashSet;
+use graph::{Graph, UNREACHABLE};
 
 fn read_programs() -> Result<Vec<Vec<usize>>, String> {
     let re = regex::Regex::new(r"^(\d+) <-> (\d+(?:, \d+)*)$").unwrap();
@@ -44,24 +50,16 @@ fn read_programs() -> Result<Vec<Vec<usize>>, String> {
     Ok(programs)
 }
 
-fn add_group_nodes(
-    programs: &[Vec<usize>],
-    start: usize,
-    visited: &mut HashSet<usize>,
-) {
-    let mut stack = vec![start];
-
-    while let Some(program_num) = stack.pop() {
-        if let Some(links) = programs.get(program_num) {
-            if visited.contains(&program_num) {
-                continue;
-            }
-
-            visited.insert(program_num);
-
-            stack.extend_from_slice(&links);
-        }
-    }
+fn build_graph(programs: &[Vec<usize>]) -> Graph {
+    Graph::from_edges(
+        programs.len(),
+        programs.iter().enumerate().flat_map(|(program_num, links)| {
+            // Links to programs that aren’t in the list are ignored
+            links.iter()
+                .filter(|&&link| link < programs.len())
+                .map(move |&link| (program_num, link, 1))
+        }),
+    )
 }
 
 fn main() -> ExitCode {
@@ -73,18 +71,27 @@ fn main() -> ExitCode {
         },
     };
 
-    let mut visited = HashSet::new();
+    let graph = build_graph(&programs);
+    let mut visited = vec![false; graph.n_nodes()];
     let mut n_groups = 0usize;
 
-    for program_num in 0..programs.len() {
-        if visited.contains(&program_num) {
+    for program_num in 0..graph.n_nodes() {
+        if visited[program_num] {
             continue;
         }
 
-        add_group_nodes(&programs, program_num, &mut visited);
+        let distances = graph.bfs_distances(program_num);
+        let mut group_size = 0;
+
+        for (visited, &distance) in visited.iter_mut().zip(distances.iter()) {
+            if distance != UNREACHABLE {
+                *visited = true;
+                group_size += 1;
+            }
+        }
 
         if program_num == 0 {
-            println!("Part 1: {}", visited.len());
+            println!("Part 1: {}", group_size);
         }
 
         n_groups += 1;
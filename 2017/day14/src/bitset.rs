@@ -0,0 +1,590 @@
+use std::hash::{Hash, Hasher};
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign,
+    Range, Sub, SubAssign,
+};
+
+pub type Elem = u64;
+
+const ELEM_BITS: usize = Elem::BITS as usize;
+
+// The words that hold the bits. A Vec grows as needed whereas a fixed
+// array is stored inline and panics if a bit beyond its capacity is
+// set.
+pub trait Storage: AsRef<[Elem]> + AsMut<[Elem]> {
+    // Makes sure that there is a word to hold the bit
+    fn grow(&mut self, bit: usize);
+}
+
+impl Storage for Vec<Elem> {
+    fn grow(&mut self, bit: usize) {
+	let n_words = bit / ELEM_BITS + 1;
+
+	if self.len() < n_words {
+	    self.resize(n_words, 0);
+	}
+    }
+}
+
+impl<const N: usize> Storage for [Elem; N] {
+    fn grow(&mut self, bit: usize) {
+	assert!(
+	    bit < N * ELEM_BITS,
+	    "bit {} is out of range for an inline bit set",
+	    bit,
+	);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BitSet<S = Vec<Elem>> {
+    bits: S,
+}
+
+// A bit set stored in an array has a fixed capacity of N * 64 bits
+// and doesn’t need any allocations so it is cheap to copy around as a
+// search state.
+impl<const N: usize> Copy for BitSet<[Elem; N]> {}
+
+impl<const N: usize> Default for BitSet<[Elem; N]> {
+    fn default() -> Self {
+	BitSet { bits: [0; N] }
+    }
+}
+
+impl BitSet {
+    pub fn new() -> BitSet {
+	BitSet {
+	    bits: Vec::new(),
+	}
+    }
+}
+
+impl Default for BitSet {
+    fn default() -> Self {
+	BitSet::new()
+    }
+}
+
+// Returns a mask of the bits in the range start..end within a single
+// word, where end can be ELEM_BITS
+fn word_mask(start: usize, end: usize) -> Elem {
+    let high = if end >= ELEM_BITS {
+	Elem::MAX
+    } else {
+	(1 << end) - 1
+    };
+
+    high & (Elem::MAX << start)
+}
+
+// Calls func with the index and mask of every word touched by range
+fn for_each_word_in_range<F>(range: Range<usize>, mut func: F)
+    where F: FnMut(usize, Elem)
+{
+    let mut start = range.start;
+
+    while start < range.end {
+	let index = start / ELEM_BITS;
+	let word_end = (index + 1) * ELEM_BITS;
+	let end = range.end.min(word_end);
+
+	func(index, word_mask(start % ELEM_BITS, end - index * ELEM_BITS));
+
+	start = end;
+    }
+}
+
+impl<S: Storage> BitSet<S> {
+    fn words(&self) -> &[Elem] {
+	self.bits.as_ref()
+    }
+
+    fn words_mut(&mut self) -> &mut [Elem] {
+	self.bits.as_mut()
+    }
+
+    // Number of words up to and including the last non-zero one
+    fn used_words(&self) -> usize {
+	self.words().iter().rposition(|&w| w != 0).map_or(0, |p| p + 1)
+    }
+
+    pub fn set(&mut self, bit: usize) {
+	let index = bit / ELEM_BITS;
+
+	self.bits.grow(bit);
+
+	self.words_mut()[index] |= 1 << (bit % ELEM_BITS);
+    }
+
+    pub fn clear(&mut self, bit: usize) {
+	let index = bit / ELEM_BITS;
+
+	if let Some(word) = self.words_mut().get_mut(index) {
+	    *word &= !(1 << (bit % ELEM_BITS));
+	}
+    }
+
+    // Flips the bit and returns its new value
+    pub fn toggle(&mut self, bit: usize) -> bool {
+	let index = bit / ELEM_BITS;
+
+	self.bits.grow(bit);
+
+	let word = &mut self.words_mut()[index];
+
+	*word ^= 1 << (bit % ELEM_BITS);
+
+	*word & (1 << (bit % ELEM_BITS)) != 0
+    }
+
+    pub fn contains(&self, bit: usize) -> bool {
+	let index = bit / ELEM_BITS;
+
+	self.words().get(index).map(|b| {
+	    b & (1 << (bit % ELEM_BITS)) != 0
+	}).unwrap_or(false)
+    }
+
+    pub fn clear_all(&mut self) {
+	self.words_mut().fill(0);
+    }
+
+    pub fn set_range(&mut self, range: Range<usize>) {
+	if range.is_empty() {
+	    return;
+	}
+
+	self.bits.grow(range.end - 1);
+
+	let words = self.words_mut();
+
+	for_each_word_in_range(range, |index, mask| words[index] |= mask);
+    }
+
+    pub fn clear_range(&mut self, range: Range<usize>) {
+	let end = range.end.min(self.words().len() * ELEM_BITS);
+	let words = self.words_mut();
+
+	for_each_word_in_range(range.start..end, |index, mask| {
+	    words[index] &= !mask
+	});
+    }
+
+    // Number of bits that are set within the range
+    pub fn count_range(&self, range: Range<usize>) -> usize {
+	let end = range.end.min(self.words().len() * ELEM_BITS);
+	let words = self.words();
+	let mut count = 0;
+
+	for_each_word_in_range(range.start..end, |index, mask| {
+	    count += (words[index] & mask).count_ones() as usize;
+	});
+
+	count
+    }
+
+    pub fn merge<T: Storage>(&mut self, other: &BitSet<T>) {
+	if let Some(last) = other.last() {
+	    self.bits.grow(last);
+	}
+
+	for (a, b) in self.words_mut().iter_mut().zip(other.words()) {
+	    *a |= b;
+	}
+    }
+
+    pub fn intersect<T: Storage>(&mut self, other: &BitSet<T>) {
+	let other = other.words();
+
+	for (i, a) in self.words_mut().iter_mut().enumerate() {
+	    *a &= other.get(i).copied().unwrap_or(0);
+	}
+    }
+
+    pub fn subtract<T: Storage>(&mut self, other: &BitSet<T>) {
+	for (a, b) in self.words_mut().iter_mut().zip(other.words()) {
+	    *a &= !b;
+	}
+    }
+
+    pub fn symmetric_difference<T: Storage>(&mut self, other: &BitSet<T>) {
+	if let Some(last) = other.last() {
+	    self.bits.grow(last);
+	}
+
+	for (a, b) in self.words_mut().iter_mut().zip(other.words()) {
+	    *a ^= b;
+	}
+    }
+
+    pub fn is_subset<T: Storage>(&self, other: &BitSet<T>) -> bool {
+	let other = other.words();
+
+	self.words().iter().enumerate().all(|(i, a)| {
+	    a & !other.get(i).copied().unwrap_or(0) == 0
+	})
+    }
+
+    pub fn is_superset<T: Storage>(&self, other: &BitSet<T>) -> bool {
+	other.is_subset(self)
+    }
+
+    pub fn is_disjoint<T: Storage>(&self, other: &BitSet<T>) -> bool {
+	self.words().iter().zip(other.words()).all(|(a, b)| a & b == 0)
+    }
+
+    pub fn len(&self) -> usize {
+	self.words().iter().map(|bits| {
+	    bits.count_ones() as usize
+	}).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.words().iter().all(|&w| w == 0)
+    }
+
+    pub fn first(&self) -> Option<usize> {
+	self.words().iter().enumerate().find(|(_, &w)| w != 0).map(|(i, w)| {
+	    i * ELEM_BITS + w.trailing_zeros() as usize
+	})
+    }
+
+    pub fn last(&self) -> Option<usize> {
+	let index = self.used_words().checked_sub(1)?;
+	let word = self.words()[index];
+
+	Some(index * ELEM_BITS + ELEM_BITS - 1 - word.leading_zeros() as usize)
+    }
+
+    pub fn bits(&self) -> Bits<'_> {
+	Bits {
+	    s: self.words(),
+	    b: 0,
+	    offset: usize::MAX - ELEM_BITS + 1,
+	}
+    }
+
+    // Iterates over the non-zero words of the set along with the
+    // number of the first bit in the word. This can be used to
+    // process the set in bulk instead of bit by bit.
+    pub fn nonzero_words(&self) -> impl Iterator<Item = (usize, Elem)> + '_ {
+	self.words()
+	    .iter()
+	    .enumerate()
+	    .filter(|(_, &w)| w != 0)
+	    .map(|(i, &w)| (i * ELEM_BITS, w))
+    }
+}
+
+pub struct Bits<'a> {
+    s: &'a [Elem],
+    b: Elem,
+    offset: usize,
+}
+
+impl<'a> Iterator for Bits<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+	while self.b == 0 {
+	    let &next_b = self.s.first()?;
+
+	    self.s = &self.s[1..];
+	    self.offset = self.offset.wrapping_add(ELEM_BITS);
+
+	    self.b = next_b;
+	}
+
+	let bit_index = self.b.trailing_zeros();
+	self.b &= !(1 << bit_index);
+
+	Some(bit_index as usize + self.offset)
+    }
+}
+
+// Sets are equal if they have the same bits regardless of how many
+// words are allocated
+impl<S: Storage, T: Storage> PartialEq<BitSet<T>> for BitSet<S> {
+    fn eq(&self, other: &BitSet<T>) -> bool {
+	let a = &self.words()[0..self.used_words()];
+	let b = &other.words()[0..other.used_words()];
+
+	a == b
+    }
+}
+
+impl<S: Storage> Eq for BitSet<S> {}
+
+impl<S: Storage> Hash for BitSet<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+	self.words()[0..self.used_words()].hash(state);
+    }
+}
+
+impl<S: Storage + Default> FromIterator<usize> for BitSet<S> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+	let mut set = BitSet { bits: S::default() };
+	set.extend(iter);
+	set
+    }
+}
+
+impl<S: Storage> Extend<usize> for BitSet<S> {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+	for bit in iter {
+	    self.set(bit);
+	}
+    }
+}
+
+impl<S: Storage, T: Storage> BitOrAssign<&BitSet<T>> for BitSet<S> {
+    fn bitor_assign(&mut self, other: &BitSet<T>) {
+	self.merge(other);
+    }
+}
+
+impl<S: Storage, T: Storage> BitAndAssign<&BitSet<T>> for BitSet<S> {
+    fn bitand_assign(&mut self, other: &BitSet<T>) {
+	self.intersect(other);
+    }
+}
+
+impl<S: Storage, T: Storage> SubAssign<&BitSet<T>> for BitSet<S> {
+    fn sub_assign(&mut self, other: &BitSet<T>) {
+	self.subtract(other);
+    }
+}
+
+impl<S: Storage, T: Storage> BitXorAssign<&BitSet<T>> for BitSet<S> {
+    fn bitxor_assign(&mut self, other: &BitSet<T>) {
+	self.symmetric_difference(other);
+    }
+}
+
+impl<S: Storage + Clone, T: Storage> BitOr<&BitSet<T>> for &BitSet<S> {
+    type Output = BitSet<S>;
+
+    fn bitor(self, other: &BitSet<T>) -> BitSet<S> {
+	let mut result = self.clone();
+	result |= other;
+	result
+    }
+}
+
+impl<S: Storage + Clone, T: Storage> BitAnd<&BitSet<T>> for &BitSet<S> {
+    type Output = BitSet<S>;
+
+    fn bitand(self, other: &BitSet<T>) -> BitSet<S> {
+	let mut result = self.clone();
+	result &= other;
+	result
+    }
+}
+
+impl<S: Storage + Clone, T: Storage> Sub<&BitSet<T>> for &BitSet<S> {
+    type Output = BitSet<S>;
+
+    fn sub(self, other: &BitSet<T>) -> BitSet<S> {
+	let mut result = self.clone();
+	result -= other;
+	result
+    }
+}
+
+impl<S: Storage + Clone, T: Storage> BitXor<&BitSet<T>> for &BitSet<S> {
+    type Output = BitSet<S>;
+
+    fn bitxor(self, other: &BitSet<T>) -> BitSet<S> {
+	let mut result = self.clone();
+	result ^= other;
+	result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bit_set() {
+	let mut bit_set = BitSet::new();
+
+	assert_eq!(
+	    &bit_set.bits().collect::<Vec<_>>(),
+	    &[],
+	);
+
+	bit_set.set(0);
+	bit_set.set(31);
+	bit_set.set(68);
+
+	assert_eq!(
+	    &bit_set.bits().collect::<Vec<_>>(),
+	    &[0, 31, 68],
+	);
+
+	let mut other = BitSet::new();
+
+	other.set(1);
+	other.set(96);
+
+	bit_set.merge(&other);
+
+	assert_eq!(
+	    &bit_set.bits().collect::<Vec<_>>(),
+	    &[0, 1, 31, 68, 96],
+	);
+
+	assert!(bit_set.contains(0));
+	assert!(bit_set.contains(1));
+	assert!(!bit_set.contains(2));
+	assert!(bit_set.contains(96));
+	assert!(!bit_set.contains(128));
+
+	bit_set.clear(1);
+	bit_set.clear(1000);
+	assert!(!bit_set.contains(1));
+	assert!(!bit_set.toggle(0));
+	assert!(bit_set.toggle(200));
+	assert_eq!(
+	    &bit_set.bits().collect::<Vec<_>>(),
+	    &[31, 68, 96, 200],
+	);
+	assert_eq!(bit_set.first(), Some(31));
+	assert_eq!(bit_set.last(), Some(200));
+
+	bit_set.clear_all();
+	assert!(bit_set.is_empty());
+	assert_eq!(bit_set.first(), None);
+	assert_eq!(bit_set.last(), None);
+    }
+
+    #[test]
+    fn set_operations() {
+	let a = [1, 5, 64, 100, 300].into_iter().collect::<BitSet>();
+	let b = [5, 100, 130].into_iter().collect::<BitSet>();
+
+	assert_eq!(
+	    (&a | &b).bits().collect::<Vec<_>>(),
+	    &[1, 5, 64, 100, 130, 300],
+	);
+	assert_eq!((&a & &b).bits().collect::<Vec<_>>(), &[5, 100]);
+	assert_eq!((&a - &b).bits().collect::<Vec<_>>(), &[1, 64, 300]);
+	assert_eq!(
+	    (&a ^ &b).bits().collect::<Vec<_>>(),
+	    &[1, 64, 130, 300],
+	);
+	assert_eq!((&b & &a).bits().collect::<Vec<_>>(), &[5, 100]);
+
+	let mut c = a.clone();
+	c -= &a;
+	assert!(c.is_empty());
+	assert_eq!(c, BitSet::new());
+
+	assert!((&a & &b).is_subset(&a));
+	assert!((&a & &b).is_subset(&b));
+	assert!(!a.is_subset(&b));
+	assert!(a.is_superset(&(&a - &b)));
+	assert!(!a.is_disjoint(&b));
+	assert!((&a - &b).is_disjoint(&b));
+	assert!(BitSet::new().is_subset(&b));
+    }
+
+    #[test]
+    fn ranges() {
+	let mut bit_set = BitSet::new();
+
+	bit_set.set_range(3..7);
+	bit_set.set_range(60..200);
+	bit_set.set_range(10..10);
+
+	assert_eq!(bit_set.len(), 4 + 140);
+	assert_eq!(bit_set.count_range(0..64), 4 + 4);
+	assert_eq!(bit_set.count_range(64..128), 64);
+	assert_eq!(bit_set.count_range(199..1000), 1);
+	assert!(bit_set.contains(199));
+	assert!(!bit_set.contains(200));
+
+	bit_set.clear_range(5..190);
+	bit_set.clear_range(1000..2000);
+
+	assert_eq!(
+	    &bit_set.bits().collect::<Vec<_>>(),
+	    &[3, 4, 190, 191, 192, 193, 194, 195, 196, 197, 198, 199],
+	);
+
+	assert_eq!(
+	    &bit_set.nonzero_words().collect::<Vec<_>>(),
+	    &[(0, 0b11000), (128, 0b11 << 62), (192, 0xff)],
+	);
+    }
+
+    #[test]
+    fn inline() {
+	let mut inline = BitSet::<[Elem; 2]>::default();
+
+	inline.set(3);
+	inline.set(127);
+	inline.set_range(60..66);
+
+	let copy = inline;
+
+	assert_eq!(
+	    &copy.bits().collect::<Vec<_>>(),
+	    &[3, 60, 61, 62, 63, 64, 65, 127],
+	);
+
+	let heap = copy.bits().collect::<BitSet>();
+	assert_eq!(heap, copy);
+	assert_eq!(copy, heap);
+
+	let mut other = BitSet::<[Elem; 2]>::default();
+	other.set(3);
+	other |= &[62, 70].into_iter().collect::<BitSet>();
+	assert_eq!((&copy & &other).bits().collect::<Vec<_>>(), &[3, 62]);
+
+	// Bits outside of the capacity are never set
+	assert!(!other.contains(1000));
+	other.clear(1000);
+	other.clear_range(100..1000);
+	assert_eq!(other.count_range(0..1000), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "bit 64 is out of range")]
+    fn inline_overflow() {
+	let mut inline = BitSet::<[Elem; 1]>::default();
+	inline.set(64);
+    }
+
+    #[test]
+    #[should_panic(expected = "bit 129 is out of range")]
+    fn inline_range_overflow() {
+	let mut inline = BitSet::<[Elem; 2]>::default();
+	inline.set_range(100..130);
+    }
+
+    #[test]
+    fn hash_eq() {
+	use std::collections::HashSet;
+
+	let mut a = BitSet::new();
+	a.set(1000);
+	a.clear(1000);
+	a.set(5);
+
+	let b = [5].into_iter().collect::<BitSet>();
+
+	assert_eq!(a, b);
+
+	let mut states = HashSet::new();
+	assert!(states.insert(a));
+	assert!(!states.insert(b));
+	assert!(states.insert([5, 6].into_iter().collect::<BitSet>()));
+
+	let mut inline_states = HashSet::new();
+	assert!(inline_states.insert(BitSet::<[Elem; 1]>::default()));
+	assert!(!inline_states.insert(BitSet::<[Elem; 1]>::default()));
+    }
+}
@@ -1,4 +1,7 @@
+mod bitset;
+
 use std::process::ExitCode;
+use bitset::{BitSet, Elem};
 
 struct Hasher {
     string: [u8; 256],
@@ -68,51 +71,48 @@ fn hash<I>(
     hasher.hash()
 }
 
-fn visit_region(disk: &[u128], visited: &mut [u128], start_pos: usize) {
+// The disk is a 128×128 grid with one bit per square
+const DISK_SIZE: usize = 128;
+
+type Disk = BitSet<[Elem; DISK_SIZE * DISK_SIZE / Elem::BITS as usize]>;
+
+fn visit_region(disk: &Disk, visited: &mut Disk, start_pos: usize) {
     let mut stack = vec![start_pos];
 
     while let Some(pos) = stack.pop() {
-        let row = pos / 128;
-        let col = pos % 128;
-        let bit = 1u128 << col;
+        let row = pos / DISK_SIZE;
+        let col = pos % DISK_SIZE;
 
-        if disk[row] & bit != 0 && visited[row] & bit == 0 {
-            visited[row] |= bit;
+        if disk.contains(pos) && !visited.contains(pos) {
+            visited.set(pos);
 
             if row >= 1 {
-                stack.push(pos - 128);
+                stack.push(pos - DISK_SIZE);
             }
-            if row + 1 < 128 {
-                stack.push(pos + 128);
+            if row + 1 < DISK_SIZE {
+                stack.push(pos + DISK_SIZE);
             }
             if col >= 1 {
                 stack.push(pos - 1);
             }
-            if col + 1 < 128 {
+            if col + 1 < DISK_SIZE {
                 stack.push(pos + 1);
             }
         }
     }
 }
 
-fn count_regions(disk: &[u128]) -> u32 {
-    let mut visited = [0u128; 128];
+fn count_regions(disk: &Disk) -> u32 {
+    let mut visited = Disk::default();
     let mut count = 0;
 
-    for (row, &(mut bits)) in disk.iter().enumerate() {
-        while bits != 0 {
-            let col = bits.trailing_zeros();
-            let bit = 1u128 << col;
-
-            if visited[row] & bit == 0 {
-                visit_region(disk, &mut visited, row * 128 + col as usize);
-                count += 1;
-            }
-
-            assert!(visited[row] & bit != 0);
-
-            bits &= !bit;
+    for pos in disk.bits() {
+        if !visited.contains(pos) {
+            visit_region(disk, &mut visited, pos);
+            count += 1;
         }
+
+        assert!(visited.contains(pos));
     }
 
     count
@@ -125,17 +125,22 @@ fn main() -> ExitCode {
         return ExitCode::FAILURE;
     };
 
-    let disk = (0..128).map(|row| {
+    let mut disk = Disk::default();
+
+    for row in 0..DISK_SIZE {
         let suffix = format!("-{}", row);
         let data = key.as_encoded_bytes().into_iter().cloned()
             .chain(suffix.as_bytes().into_iter().cloned());
-        hash(data)
-    }).collect::<Vec<_>>();
+        let mut bits = hash(data);
+
+        while bits != 0 {
+            let col = bits.trailing_zeros() as usize;
+            disk.set(row * DISK_SIZE + col);
+            bits &= bits - 1;
+        }
+    }
 
-    println!(
-        "Part 1: {}",
-        disk.iter().map(|row| row.count_ones()).sum::<u32>(),
-    );
+    println!("Part 1: {}", disk.len());
 
     println!("Part 2: {}", count_regions(&disk));
 
@@ -1,7 +1,10 @@
+// The cycle module has more helpers than this day needs
+#[allow(dead_code)]
+mod cycle;
+
 use std::process::ExitCode;
 use std::str::FromStr;
 use std::fmt;
-use std::collections::HashMap;
 
 #[derive(Copy, Clone)]
 enum DanceMove {
@@ -10,7 +13,7 @@ enum DanceMove {
     Partner(char, char),
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum DanceError {
     SpinOutOfRange(usize),
     ExchangeOutOfRange(usize),
@@ -117,33 +120,50 @@ fn read_dance_moves() -> Result<Vec<DanceMove>, String> {
         }).collect()
 }
 
-fn make_loop(
+fn dance(
     dance_moves: &[DanceMove],
-) -> Result<(Vec<String>, usize), DanceError> {
-    let mut dance_hall = (0..16).map(|pos| {
-        char::from_u32(pos + 'a' as u32).unwrap()
-    }).collect::<Vec<_>>();
+    dance_hall: &mut [char],
+) -> Result<(), DanceError> {
+    for dance_move in dance_moves.iter() {
+        dance_move.apply(dance_hall)?;
+    }
 
-    let mut loop_list = vec![dance_hall.iter().cloned().collect::<String>()];
+    Ok(())
+}
 
-    let mut seen = HashMap::from([(loop_list[0].clone(), 0)]);
+fn start_dance_hall() -> Vec<char> {
+    (0..16).map(|pos| {
+        char::from_u32(pos + 'a' as u32).unwrap()
+    }).collect::<Vec<_>>()
+}
 
-    loop {
-        for dance_move in dance_moves.iter() {
-            dance_move.apply(&mut dance_hall)?;
-        }
+fn find_dance_loop(
+    dance_moves: &[DanceMove],
+) -> Result<cycle::Cycle, DanceError> {
+    let start = start_dance_hall();
 
-        let order = dance_hall.iter().cloned().collect::<String>();
+    // Try the dance once first so that any errors are reported. After
+    // that the dance can’t fail because the same dancers are always
+    // in the hall.
+    dance(dance_moves, &mut start.clone())?;
 
-        if let Some(loop_start) = seen.insert(
-            order.clone(),
-            loop_list.len()
-        ) {
-            return Ok((loop_list, loop_start));
-        }
+    Ok(cycle::find(&start, |dance_hall| {
+        dance(dance_moves, dance_hall).unwrap();
+    }))
+}
+
+fn dance_hall_after(
+    dance_moves: &[DanceMove],
+    dance_loop: &cycle::Cycle,
+    iterations: usize,
+) -> String {
+    let mut dance_hall = start_dance_hall();
 
-        loop_list.push(order);
+    for _ in 0..dance_loop.reduce(iterations) {
+        dance(dance_moves, &mut dance_hall).unwrap();
     }
+
+    dance_hall.into_iter().collect::<String>()
 }
 
 fn main() -> ExitCode {
@@ -155,8 +175,8 @@ fn main() -> ExitCode {
         },
     };
 
-    let (dance_loop, loop_start) = match make_loop(&dance_moves) {
-        Ok(dl) => dl,
+    let dance_loop = match find_dance_loop(&dance_moves) {
+        Ok(dance_loop) => dance_loop,
         Err(e) => {
             eprintln!("{}", e);
             return ExitCode::FAILURE;
@@ -164,16 +184,10 @@ fn main() -> ExitCode {
     };
 
     for (part, iterations) in [1, 1_000_000_000].into_iter().enumerate() {
-        let index = if iterations < loop_start {
-            iterations
-        } else {
-            (iterations - loop_start) % dance_loop.len() + loop_start
-        };
-
         println!(
             "Part {}: {}",
             part + 1,
-            dance_loop[index],
+            dance_hall_after(&dance_moves, &dance_loop, iterations),
         );
     }
 
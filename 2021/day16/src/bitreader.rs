@@ -0,0 +1,498 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BitOrder {
+    // The first bit in the stream is the highest bit of the first
+    // byte and multi-bit values are read most-significant bit first.
+    Msb,
+    // The first bit in the stream is the lowest bit of the first byte
+    // and multi-bit values are read least-significant bit first. The
+    // BITS format doesn’t need this so only the tests use it.
+    Lsb,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    ch: char,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unexpected character {} in hex data", self.ch)
+    }
+}
+
+// Converts a string of hex digits to bytes. Each digit is four bits
+// with the high nibble first so the result can be read with
+// BitOrder::Msb. If there are an odd number of digits then the last
+// byte is padded with zeroes. Returns the bytes and the number of
+// bits.
+pub fn parse_hex(data: &str) -> Result<(Vec<u8>, usize), Error> {
+    let mut bytes = Vec::with_capacity(data.len().div_ceil(2));
+    let mut n_bits = 0;
+
+    for ch in data.chars() {
+        let Some(digit) = ch.to_digit(16)
+        else { return Err(Error { ch }); };
+
+        if n_bits & 4 == 0 {
+            bytes.push((digit << 4) as u8);
+        } else {
+            *bytes.last_mut().unwrap() |= digit as u8;
+        }
+
+        n_bits += 4;
+    }
+
+    Ok((bytes, n_bits))
+}
+
+fn mask(n_bits: usize) -> u64 {
+    if n_bits >= u64::BITS as usize {
+        u64::MAX
+    } else {
+        (1 << n_bits) - 1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    order: BitOrder,
+    // The bit range of data that this reader covers. Sub-readers
+    // share the data with their parent but have a smaller range.
+    start: usize,
+    end: usize,
+    // Absolute position of the next bit to read
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn with_len(
+        data: &'a [u8],
+        n_bits: usize,
+        order: BitOrder,
+    ) -> BitReader<'a> {
+        assert!(n_bits <= data.len() * 8);
+
+        BitReader {
+            data,
+            order,
+            start: 0,
+            end: n_bits,
+            pos: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    // Position of the next bit to read relative to the start of the
+    // reader
+    pub fn pos(&self) -> usize {
+        self.pos - self.start
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.end - self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.end
+    }
+
+    // Moves to a position relative to the start of the reader. Returns
+    // false and leaves the position alone if it is past the end.
+    pub fn seek(&mut self, pos: usize) -> bool {
+        if pos > self.len() {
+            false
+        } else {
+            self.pos = self.start + pos;
+            true
+        }
+    }
+
+    // Moves the position by n_bits without reading anything
+    pub fn skip(&mut self, n_bits: usize) -> bool {
+        if n_bits > self.remaining() {
+            false
+        } else {
+            self.pos += n_bits;
+            true
+        }
+    }
+
+    // Gets the bits starting at pos as a word where bit 0 is the bit
+    // at pos for Lsb order and bit 127 is the bit at pos for Msb
+    // order.
+    fn load_word(&self, pos: usize, n_bits: usize) -> u128 {
+        let first_byte = pos / 8;
+        let last_byte = (pos + n_bits).div_ceil(8);
+        let bytes = &self.data[first_byte..last_byte];
+
+        match self.order {
+            BitOrder::Msb => {
+                let word = bytes.iter().enumerate().fold(0, |word, (i, &b)| {
+                    word | ((b as u128) << (120 - i * 8))
+                });
+                word << (pos % 8)
+            },
+            BitOrder::Lsb => {
+                let word = bytes.iter().enumerate().fold(0, |word, (i, &b)| {
+                    word | ((b as u128) << (i * 8))
+                });
+                word >> (pos % 8)
+            },
+        }
+    }
+
+    // Returns the next n_bits without advancing the position or None
+    // if there aren’t enough bits left.
+    pub fn peek_bits(&self, n_bits: usize) -> Option<u64> {
+        assert!(n_bits <= u64::BITS as usize);
+
+        if n_bits > self.remaining() {
+            return None;
+        }
+
+        if n_bits == 0 {
+            return Some(0);
+        }
+
+        let word = self.load_word(self.pos, n_bits);
+
+        Some(match self.order {
+            BitOrder::Msb => (word >> (128 - n_bits)) as u64,
+            BitOrder::Lsb => word as u64 & mask(n_bits),
+        })
+    }
+
+    pub fn read_bits(&mut self, n_bits: usize) -> Option<u64> {
+        let result = self.peek_bits(n_bits)?;
+        self.pos += n_bits;
+        Some(result)
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        self.read_bits(1).map(|bit| bit != 0)
+    }
+
+    // Returns a reader covering the next n_bits and advances this
+    // reader past them.
+    pub fn sub_reader(&mut self, n_bits: usize) -> Option<BitReader<'a>> {
+        if n_bits > self.remaining() {
+            return None;
+        }
+
+        let sub_reader = BitReader {
+            data: self.data,
+            order: self.order,
+            start: self.pos,
+            end: self.pos + n_bits,
+            pos: self.pos,
+        };
+
+        self.pos += n_bits;
+
+        Some(sub_reader)
+    }
+
+    // Returns true if all of the remaining bits are zero
+    pub fn is_trailing_zeroes(&self) -> bool {
+        let mut pos = self.pos;
+
+        while pos < self.end {
+            let n_bits = std::cmp::min(self.end - pos, u64::BITS as usize);
+
+            if self.load_word(pos, n_bits) & {
+                match self.order {
+                    BitOrder::Msb => !(u128::MAX >> n_bits),
+                    BitOrder::Lsb => mask(n_bits) as u128,
+                }
+            } != 0 {
+                return false;
+            }
+
+            pos += n_bits;
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    order: BitOrder,
+    n_bits: usize,
+}
+
+impl BitWriter {
+    pub fn new(order: BitOrder) -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            order,
+            n_bits: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.n_bits
+    }
+
+    // Appends the low n_bits of value. The bits are written in the
+    // same order that BitReader::read_bits would read them back.
+    pub fn write_bits(&mut self, value: u64, n_bits: usize) {
+        assert!(n_bits <= u64::BITS as usize);
+
+        let value = value & mask(n_bits);
+        let mut bit_offset = self.n_bits % 8;
+        let mut written = 0;
+
+        self.bytes.resize((self.n_bits + n_bits).div_ceil(8), 0);
+
+        while written < n_bits {
+            let byte = &mut self.bytes[(self.n_bits + written) / 8];
+            let chunk = std::cmp::min(8 - bit_offset, n_bits - written);
+            let chunk_mask = mask(chunk);
+
+            match self.order {
+                BitOrder::Msb => {
+                    let bits = (value >> (n_bits - written - chunk))
+                        & chunk_mask;
+                    *byte |= (bits << (8 - bit_offset - chunk)) as u8;
+                },
+                BitOrder::Lsb => {
+                    let bits = (value >> written) & chunk_mask;
+                    *byte |= (bits << bit_offset) as u8;
+                },
+            }
+
+            written += chunk;
+            bit_offset = 0;
+        }
+
+        self.n_bits += n_bits;
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u64, 1);
+    }
+
+    // Appends all of the remaining bits from the reader
+    pub fn write_reader(&mut self, reader: &mut BitReader) {
+        while !reader.is_empty() {
+            let n_bits = std::cmp::min(reader.remaining(), u64::BITS as usize);
+            self.write_bits(reader.read_bits(n_bits).unwrap(), n_bits);
+        }
+    }
+
+    pub fn reader(&self) -> BitReader<'_> {
+        BitReader::with_len(&self.bytes, self.n_bits, self.order)
+    }
+
+    // Formats the bits as hex digits with the high nibble of each byte
+    // first, which is the inverse of parse_hex when the order is Msb.
+    // The last digit is padded with zeroes.
+    pub fn to_hex(&self) -> String {
+        let mut result = String::with_capacity(self.n_bits.div_ceil(4));
+
+        for digit_num in 0..self.n_bits.div_ceil(4) {
+            let byte = self.bytes[digit_num / 2];
+            let digit = if digit_num & 1 == 0 { byte >> 4 } else { byte & 0xf };
+            result.push(char::from_digit(digit as u32, 16)
+                        .unwrap()
+                        .to_ascii_uppercase());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn msb_reader() {
+        let (bytes, n_bits) = parse_hex("D2FE28").unwrap();
+
+        assert_eq!(&bytes, &[0xd2, 0xfe, 0x28]);
+        assert_eq!(n_bits, 24);
+
+        let mut reader = BitReader::with_len(&bytes, n_bits, BitOrder::Msb);
+
+        assert_eq!(reader.peek_bits(3), Some(6));
+        assert_eq!(reader.read_bits(3), Some(6));
+        assert_eq!(reader.read_bits(3), Some(4));
+        assert_eq!(reader.pos(), 6);
+        assert_eq!(reader.read_bit(), Some(true));
+        assert_eq!(reader.read_bits(4), Some(0b0111));
+        assert_eq!(reader.read_bits(14), None);
+        assert_eq!(reader.read_bits(13), Some(0b1111000101000));
+        assert_eq!(reader.read_bits(1), None);
+        assert!(reader.is_empty());
+
+        let (bytes, n_bits) = parse_hex("0123456789abcdef01").unwrap();
+        let mut reader = BitReader::with_len(&bytes, n_bits, BitOrder::Msb);
+        assert_eq!(reader.read_bits(4), Some(0));
+        assert_eq!(reader.read_bits(64), Some(0x123456789abcdef0));
+        assert_eq!(reader.read_bits(4), Some(1));
+
+        assert!(parse_hex("12g").is_err());
+
+        let (bytes, n_bits) = parse_hex("abc").unwrap();
+        assert_eq!(&bytes, &[0xab, 0xc0]);
+        assert_eq!(n_bits, 12);
+    }
+
+    #[test]
+    fn lsb_reader() {
+        let bytes = [0b1011_0110, 0b0000_0001, 0xff];
+        let mut reader = BitReader::with_len(&bytes, 24, BitOrder::Lsb);
+
+        assert_eq!(reader.remaining(), 24);
+        assert_eq!(reader.read_bit(), Some(false));
+        assert_eq!(reader.read_bits(3), Some(0b011));
+        assert_eq!(reader.read_bits(6), Some(0b01_1011));
+        assert_eq!(reader.read_bits(14), Some(0b11_1111_1100_0000));
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn seek() {
+        let bytes = (0..24u8).map(|i| i.wrapping_mul(37) ^ 0x5a)
+            .collect::<Vec<_>>();
+
+        for order in [BitOrder::Msb, BitOrder::Lsb] {
+            let mut reader = BitReader::with_len(&bytes, 192, order);
+
+            // Read every bit on its own to compare against
+            let bits = (0..reader.len())
+                .map(|_| reader.read_bit().unwrap() as u64)
+                .collect::<Vec<_>>();
+            let expected = |pos: usize, n_bits: usize| {
+                bits[pos..pos + n_bits].iter().enumerate()
+                    .fold(0, |value, (i, &bit)| match order {
+                        BitOrder::Msb => (value << 1) | bit,
+                        BitOrder::Lsb => value | (bit << i),
+                    })
+            };
+
+            // Positions either side of the byte and 64-bit boundaries
+            for pos in [0, 1, 7, 8, 9, 60, 63, 64, 65, 127, 128, 129, 191] {
+                for n_bits in [1, 8, 13, 64] {
+                    assert!(reader.seek(pos));
+                    assert_eq!(reader.pos(), pos);
+
+                    if pos + n_bits <= reader.len() {
+                        assert_eq!(
+                            reader.read_bits(n_bits),
+                            Some(expected(pos, n_bits)),
+                        );
+                    } else {
+                        assert_eq!(reader.read_bits(n_bits), None);
+                    }
+                }
+            }
+
+            assert!(reader.seek(192));
+            assert!(reader.is_empty());
+            assert!(!reader.seek(193));
+            assert_eq!(reader.pos(), 192);
+
+            assert!(reader.seek(60));
+            assert!(reader.skip(10));
+            assert_eq!(reader.pos(), 70);
+            assert_eq!(reader.read_bits(64), Some(expected(70, 64)));
+            assert!(!reader.skip(59));
+            assert_eq!(reader.pos(), 134);
+            assert!(reader.skip(58));
+            assert!(reader.is_empty());
+
+            // Positions in a sub-reader are relative to its start
+            assert!(reader.seek(3));
+            let mut sub_reader = reader.sub_reader(100).unwrap();
+            assert_eq!(sub_reader.len(), 100);
+            assert!(sub_reader.seek(70));
+            assert_eq!(sub_reader.read_bits(30), Some(expected(73, 30)));
+            assert!(sub_reader.is_empty());
+            assert!(!sub_reader.seek(101));
+            assert!(sub_reader.seek(0));
+            assert_eq!(sub_reader.read_bits(64), Some(expected(3, 64)));
+            assert!(!sub_reader.skip(37));
+        }
+    }
+
+    #[test]
+    fn sub_reader() {
+        let (bytes, n_bits) = parse_hex("38006F45291200").unwrap();
+        let mut reader = BitReader::with_len(&bytes, n_bits, BitOrder::Msb);
+
+        assert_eq!(reader.read_bits(7), Some(0b0011100));
+        let length = reader.read_bits(15).unwrap() as usize;
+        assert_eq!(length, 27);
+
+        let mut sub_reader = reader.sub_reader(length).unwrap();
+
+        assert_eq!(reader.pos(), 22 + 27);
+        assert!(reader.is_trailing_zeroes());
+
+        assert_eq!(sub_reader.remaining(), 27);
+        assert_eq!(sub_reader.pos(), 0);
+        assert_eq!(sub_reader.read_bits(6), Some(0b110100));
+        assert_eq!(sub_reader.read_bits(5), Some(0b01010));
+        let mut literal = sub_reader.sub_reader(6).unwrap();
+        assert_eq!(literal.read_bits(6), Some(0b010100));
+        assert!(literal.is_empty());
+        assert_eq!(sub_reader.remaining(), 10);
+        assert!(!sub_reader.is_trailing_zeroes());
+        assert!(sub_reader.sub_reader(11).is_none());
+        assert_eq!(sub_reader.read_bits(10), Some(0b1000100100));
+        assert_eq!(sub_reader.read_bit(), None);
+    }
+
+    #[test]
+    fn writer() {
+        for order in [BitOrder::Msb, BitOrder::Lsb] {
+            let mut writer = BitWriter::new(order);
+            let values = [
+                (6, 3),
+                (1, 1),
+                (0x123456789abcdef0, 64),
+                (0, 5),
+                (0x7fff, 15),
+                (u64::MAX, 64),
+                (5, 3),
+            ];
+
+            for &(value, n_bits) in values.iter() {
+                writer.write_bits(value, n_bits);
+            }
+
+            writer.write_bit(true);
+
+            assert_eq!(writer.len(), 156);
+
+            let mut reader = writer.reader();
+
+            for &(value, n_bits) in values.iter() {
+                assert_eq!(reader.read_bits(n_bits), Some(value));
+            }
+
+            assert_eq!(reader.read_bit(), Some(true));
+            assert!(reader.is_empty());
+
+            let mut copy = BitWriter::new(order);
+            let mut reader = writer.reader();
+            copy.write_reader(&mut reader);
+            assert_eq!(copy.to_hex(), writer.to_hex());
+        }
+
+        let mut writer = BitWriter::new(BitOrder::Msb);
+        writer.write_bits(0xd2fe28, 24);
+        writer.write_bits(0xa, 4);
+        assert_eq!(writer.to_hex(), "D2FE28A");
+    }
+}
@@ -1,50 +1,54 @@
-mod bitvec;
+// The bit reader has more operations than this day needs
+#[allow(dead_code)]
+mod bitreader;
 
-use bitvec::BitVec;
+use bitreader::{BitReader, BitWriter, BitOrder};
 use std::cmp::{min, max};
+use std::fmt;
 use num_enum::{TryFromPrimitive, IntoPrimitive};
 
-#[derive(Copy, Clone, Debug)]
-enum TreeIterRemaining {
+#[derive(Clone, Debug)]
+enum TreeIterRemaining<'a> {
     Literal,
-    EndPos(usize),
+    // The children of a length type 0 operator are read from a
+    // sub-reader covering exactly their bits
+    Bits(BitReader<'a>),
     PacketsRemaining(usize),
 }
 
 #[derive(Clone, Debug)]
 struct TreeIter<'a> {
-    bv: &'a BitVec,
-    pos: usize,
-    stack: Vec<TreeIterRemaining>,
+    stack: Vec<TreeIterRemaining<'a>>,
 }
 
 impl<'a> TreeIter<'a> {
-    fn new(bv: &BitVec) -> TreeIter {
+    fn new(reader: BitReader<'a>) -> TreeIter<'a> {
         TreeIter {
-            bv,
-            pos: 0,
-            stack: vec![TreeIterRemaining::EndPos(bv.size())],
+            stack: vec![TreeIterRemaining::Bits(reader)],
         }
     }
 
-    fn read_bits(&mut self, n_bits: usize) -> u64 {
-        let result = self.bv.read_bits(self.pos, n_bits);
-
-        self.pos += n_bits;
+    // The innermost reader on the stack, which is where the next
+    // packet comes from
+    fn reader(&mut self) -> &mut BitReader<'a> {
+        self.stack.iter_mut().rev().find_map(|entry| match entry {
+            TreeIterRemaining::Bits(reader) => Some(reader),
+            _ => None,
+        }).unwrap()
+    }
 
-        result
+    fn read_bits(&mut self, n_bits: usize) -> u64 {
+        self.reader().read_bits(n_bits).expect("unexpected end of packet data")
     }
 
     fn read_literal(&mut self) -> u64 {
         let mut result = 0;
 
         loop {
-            let last = !self.bv.read_bit(self.pos);
-            let nibble = self.bv.read_bits(self.pos + 1, 4);
+            let group = self.read_bits(5);
+            let last = group & 0x10 == 0;
 
-            self.pos += 5;
-
-            result = (result << 4) | nibble;
+            result = (result << 4) | (group & 0xf);
 
             if last {
                 break result;
@@ -52,24 +56,26 @@ impl<'a> TreeIter<'a> {
         }
     }
 
-    fn read_packet(&mut self) -> Packet {
+    fn read_packet(&mut self) -> PacketHeader {
         let version = self.read_bits(3) as u8;
         let type_id = TypeId::try_from(self.read_bits(3) as u8).unwrap();
 
         let data = if type_id == TypeId::Literal {
             self.stack.push(TreeIterRemaining::Literal);
             PacketData::Literal(self.read_literal())
-        } else if self.read_bits(1) == 0 {
-            let remaining = self.read_bits(15) as usize;
-            self.stack.push(TreeIterRemaining::EndPos(self.pos + remaining));
-            PacketData::BitOperator(remaining)
+        } else if !self.reader().read_bit().expect("missing length type") {
+            let length = self.read_bits(15) as usize;
+            let sub_reader = self.reader().sub_reader(length)
+                .expect("sub-packet length is too long");
+            self.stack.push(TreeIterRemaining::Bits(sub_reader));
+            PacketData::BitOperator(length)
         } else {
             let remaining = self.read_bits(11) as usize;
             self.stack.push(TreeIterRemaining::PacketsRemaining(remaining));
             PacketData::PacketOperator(remaining)
         };
 
-        Packet {
+        PacketHeader {
             version,
             type_id,
             data,
@@ -85,7 +91,7 @@ enum PacketData {
 }
 
 #[derive(Clone, Copy, Debug)]
-struct Packet {
+struct PacketHeader {
     version: u8,
     type_id: TypeId,
     data: PacketData,
@@ -94,50 +100,49 @@ struct Packet {
 #[derive(Clone, Copy, Debug)]
 enum TreeDirection {
     Up,
-    Down(Packet),
+    Down(PacketHeader),
 }
 
 impl<'a> Iterator for TreeIter<'a> {
     type Item = TreeDirection;
 
     fn next(&mut self) -> Option<TreeDirection> {
-        if let Some(tail) = self.stack.pop() {
-            let is_end = match tail {
-                TreeIterRemaining::EndPos(end_pos) => {
-                    if self.pos < end_pos &&
-                        (!self.stack.is_empty() ||
-                         !self.bv.is_trailing_zeroes(self.pos))
-                    {
-                        self.stack.push(TreeIterRemaining::EndPos(end_pos));
-                        false
-                    } else {
-                        true
-                    }
-                },
-                TreeIterRemaining::PacketsRemaining(remaining) => {
-                    if remaining > 0 {
-                        self.stack.push(TreeIterRemaining::PacketsRemaining(
-                            remaining - 1
-                        ));
-                        false
-                    } else {
-                        true
-                    }
-                },
-                TreeIterRemaining::Literal => true,
-            };
+        let tail = self.stack.pop()?;
 
-            if is_end {
+        let is_end = match tail {
+            TreeIterRemaining::Bits(ref reader) => {
+                // The top-level reader can be padded with zeroes
                 if self.stack.is_empty() {
-                    None
+                    reader.is_trailing_zeroes()
+                } else {
+                    reader.is_empty()
+                }
+            },
+            TreeIterRemaining::PacketsRemaining(remaining) => {
+                if remaining > 0 {
+                    self.stack.push(TreeIterRemaining::PacketsRemaining(
+                        remaining - 1
+                    ));
+                    false
                 } else {
-                    Some(TreeDirection::Up)
+                    true
                 }
+            },
+            TreeIterRemaining::Literal => true,
+        };
+
+        if is_end {
+            if self.stack.is_empty() {
+                None
             } else {
-                Some(TreeDirection::Down(self.read_packet()))
+                Some(TreeDirection::Up)
             }
         } else {
-            None
+            if let TreeIterRemaining::Bits(_) = tail {
+                self.stack.push(tail);
+            }
+
+            Some(TreeDirection::Down(self.read_packet()))
         }
     }
 }
@@ -147,9 +152,9 @@ struct PacketIter<'a> {
 }
 
 impl<'a> Iterator for PacketIter<'a> {
-    type Item = Packet;
+    type Item = PacketHeader;
 
-    fn next(&mut self) -> Option<Packet> {
+    fn next(&mut self) -> Option<PacketHeader> {
         loop {
             match self.base.next() {
                 None => break None,
@@ -161,15 +166,15 @@ impl<'a> Iterator for PacketIter<'a> {
 }
 
 impl<'a> PacketIter<'a> {
-    fn new(bv: &BitVec) -> PacketIter {
+    fn new(reader: BitReader<'a>) -> PacketIter<'a> {
         PacketIter {
-            base: TreeIter::new(bv),
+            base: TreeIter::new(reader),
         }
     }
 }
 
-fn part1(bv: &BitVec) -> u32 {
-    PacketIter::new(bv).map(|p| p.version as u32).sum()
+fn part1(reader: &BitReader) -> u32 {
+    PacketIter::new(reader.clone()).map(|p| p.version as u32).sum()
 }
 
 #[derive(Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive, Copy, Clone)]
@@ -186,6 +191,19 @@ enum TypeId {
 }
 
 impl TypeId {
+    fn name(self) -> &'static str {
+        match self {
+            TypeId::Add => "sum",
+            TypeId::Multiply => "product",
+            TypeId::Minimum => "min",
+            TypeId::Maximum => "max",
+            TypeId::Literal => "literal",
+            TypeId::GreaterThan => "gt",
+            TypeId::LessThan => "lt",
+            TypeId::EqualTo => "eq",
+        }
+    }
+
     fn apply(self, a: u64, b: u64) -> u64 {
         match self {
             TypeId::Add => a + b,
@@ -209,14 +227,14 @@ struct EvaluateEntry {
     is_first: bool,
 }
 
-fn part2(bv: &BitVec) -> u64 {
+fn part2(reader: &BitReader) -> u64 {
     let mut stack = vec![EvaluateEntry {
         value: 0,
         type_id: TypeId::Add,
         is_first: true,
     }];
 
-    for direction in TreeIter::new(bv) {
+    for direction in TreeIter::new(reader.clone()) {
         match direction {
             TreeDirection::Up => {
                 let value = stack.pop().unwrap().value;
@@ -249,7 +267,210 @@ fn part2(bv: &BitVec) -> u64 {
     stack.last().unwrap().value
 }
 
-fn process_lines<I>(lines: I) -> std::process::ExitCode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LengthType {
+    // The operator is followed by the number of bits in its children
+    Bits,
+    // The operator is followed by the number of children
+    Packets,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PacketBody {
+    Literal(u64),
+    Operator(LengthType, Vec<Packet>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Packet {
+    version: u8,
+    type_id: TypeId,
+    body: PacketBody,
+}
+
+impl Packet {
+    fn literal(version: u8, value: u64) -> Packet {
+        Packet {
+            version,
+            type_id: TypeId::Literal,
+            body: PacketBody::Literal(value),
+        }
+    }
+
+    fn operator(
+        version: u8,
+        type_id: TypeId,
+        length_type: LengthType,
+        children: Vec<Packet>,
+    ) -> Packet {
+        assert!(type_id != TypeId::Literal);
+        assert!(!children.is_empty());
+
+        Packet {
+            version,
+            type_id,
+            body: PacketBody::Operator(length_type, children),
+        }
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Packet, String> {
+        let read_bits = |reader: &mut BitReader, n_bits| {
+            reader.read_bits(n_bits).ok_or_else(|| {
+                format!("unexpected end of packet data at bit {}",
+                        reader.pos())
+            })
+        };
+
+        let version = read_bits(reader, 3)? as u8;
+        let type_id = TypeId::try_from(read_bits(reader, 3)? as u8).unwrap();
+
+        if type_id == TypeId::Literal {
+            let mut value = 0u64;
+
+            loop {
+                let group = read_bits(reader, 5)?;
+
+                value = value.checked_mul(16).ok_or_else(|| {
+                    "literal is too big".to_string()
+                })? | (group & 0xf);
+
+                if group & 0x10 == 0 {
+                    break Ok(Packet::literal(version, value));
+                }
+            }
+        } else {
+            let mut children = Vec::new();
+
+            let length_type = if read_bits(reader, 1)? == 0 {
+                let length = read_bits(reader, 15)? as usize;
+                let mut sub_reader = reader.sub_reader(length)
+                    .ok_or_else(|| {
+                        format!("sub-packet length {} is too long", length)
+                    })?;
+
+                while !sub_reader.is_empty() {
+                    children.push(Packet::decode(&mut sub_reader)?);
+                }
+
+                LengthType::Bits
+            } else {
+                let n_packets = read_bits(reader, 11)?;
+
+                for _ in 0..n_packets {
+                    children.push(Packet::decode(reader)?);
+                }
+
+                LengthType::Packets
+            };
+
+            if children.is_empty() {
+                return Err("operator packet has no sub-packets".to_string());
+            }
+
+            Ok(Packet::operator(version, type_id, length_type, children))
+        }
+    }
+
+    // Appends the packet to the writer. Returns an error if an
+    // operator’s children don’t fit in the size of its length field.
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), String> {
+        writer.write_bits(self.version as u64, 3);
+        writer.write_bits(u8::from(self.type_id) as u64, 3);
+
+        match self.body {
+            PacketBody::Literal(value) => {
+                let n_groups = std::cmp::max(
+                    (u64::BITS - value.leading_zeros()).div_ceil(4),
+                    1,
+                );
+
+                for group in (0..n_groups).rev() {
+                    writer.write_bit(group > 0);
+                    writer.write_bits(value >> (group * 4), 4);
+                }
+            },
+            PacketBody::Operator(LengthType::Bits, ref children) => {
+                let mut child_writer = BitWriter::new(BitOrder::Msb);
+
+                for child in children.iter() {
+                    child.encode(&mut child_writer)?;
+                }
+
+                if child_writer.len() >= 1 << 15 {
+                    return Err(format!(
+                        "sub-packets are {} bits long but the length field \
+                         only has 15 bits",
+                        child_writer.len(),
+                    ));
+                }
+
+                writer.write_bit(false);
+                writer.write_bits(child_writer.len() as u64, 15);
+                writer.write_reader(&mut child_writer.reader());
+            },
+            PacketBody::Operator(LengthType::Packets, ref children) => {
+                if children.len() >= 1 << 11 {
+                    return Err(format!(
+                        "operator has {} sub-packets but the count field \
+                         only has 11 bits",
+                        children.len(),
+                    ));
+                }
+
+                writer.write_bit(true);
+                writer.write_bits(children.len() as u64, 11);
+
+                for child in children.iter() {
+                    child.encode(writer)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn to_hex(&self) -> Result<String, String> {
+        let mut writer = BitWriter::new(BitOrder::Msb);
+        self.encode(&mut writer)?;
+        // Pad to a whole number of bytes like the puzzle transmissions
+        writer.write_bits(0, (8 - writer.len() % 8) % 8);
+        Ok(writer.to_hex())
+    }
+
+    fn value(&self) -> u64 {
+        match self.body {
+            PacketBody::Literal(value) => value,
+            PacketBody::Operator(_, ref children) => {
+                children[1..].iter().fold(children[0].value(), |a, child| {
+                    self.type_id.apply(a, child.value())
+                })
+            },
+        }
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.body {
+            PacketBody::Literal(value) => write!(f, "{}", value),
+            PacketBody::Operator(_, ref children) => {
+                write!(f, "{}(", self.type_id.name())?;
+
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", child)?;
+                }
+
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+fn process_lines<I>(lines: I, show_tree: bool) -> std::process::ExitCode
     where I: Iterator<Item = Result<String, std::io::Error>>
 {
     let mut ret = std::process::ExitCode::SUCCESS;
@@ -261,17 +482,40 @@ fn process_lines<I>(lines: I) -> std::process::ExitCode
                 return std::process::ExitCode::FAILURE;
             },
             Ok(line) => {
-                match BitVec::new(&line) {
+                match bitreader::parse_hex(&line) {
                     Err(e) => {
                         eprintln!("line {}: {}", line_num + 1, e);
                         ret = std::process::ExitCode::FAILURE;
                     },
-                    Ok(bv) => {
+                    Ok((bytes, n_bits)) => {
+                        let reader = BitReader::with_len(
+                            &bytes,
+                            n_bits,
+                            BitOrder::Msb,
+                        );
+
                         println!(
                             "part1: {}, part2: {}",
-                            part1(&bv),
-                            part2(&bv),
+                            part1(&reader),
+                            part2(&reader),
                         );
+
+                        if show_tree {
+                            match Packet::decode(&mut reader.clone()) {
+                                // A decoded packet always fits in its
+                                // length fields so it can be re-encoded
+                                Ok(packet) => println!(
+                                    "{} = {} ({})",
+                                    packet,
+                                    packet.value(),
+                                    packet.to_hex().unwrap(),
+                                ),
+                                Err(e) => {
+                                    eprintln!("line {}: {}", line_num + 1, e);
+                                    ret = std::process::ExitCode::FAILURE;
+                                },
+                            }
+                        }
                     },
                 };
             },
@@ -282,13 +526,227 @@ fn process_lines<I>(lines: I) -> std::process::ExitCode
 }
 
 fn main() -> std::process::ExitCode {
-    let mut args = std::env::args();
+    let mut args = std::env::args().peekable();
 
     args.next();
 
-    if args.len() > 0 {
-        process_lines(args.map(|arg| Ok(arg)))
+    let show_tree = args.next_if(|arg| arg == "--tree").is_some();
+
+    if args.peek().is_some() {
+        process_lines(args.map(|arg| Ok(arg)), show_tree)
     } else {
-        process_lines(std::io::stdin().lines())
+        process_lines(std::io::stdin().lines(), show_tree)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_hex(hex: &str) -> Packet {
+        let (bytes, n_bits) = bitreader::parse_hex(hex).unwrap();
+        let mut reader = BitReader::with_len(&bytes, n_bits, BitOrder::Msb);
+        let packet = Packet::decode(&mut reader).unwrap();
+        assert!(reader.is_trailing_zeroes());
+        packet
+    }
+
+    #[test]
+    fn decode_examples() {
+        let packet = decode_hex("D2FE28");
+        assert_eq!(packet, Packet::literal(6, 2021));
+        assert_eq!(packet.to_hex().unwrap(), "D2FE28");
+
+        let packet = decode_hex("38006F45291200");
+        assert_eq!(
+            packet,
+            Packet::operator(
+                1,
+                TypeId::LessThan,
+                LengthType::Bits,
+                vec![Packet::literal(6, 10), Packet::literal(2, 20)],
+            ),
+        );
+        assert_eq!(packet.to_hex().unwrap(), "38006F45291200");
+
+        let packet = decode_hex("EE00D40C823060");
+        assert_eq!(
+            packet,
+            Packet::operator(
+                7,
+                TypeId::Maximum,
+                LengthType::Packets,
+                vec![
+                    Packet::literal(2, 1),
+                    Packet::literal(4, 2),
+                    Packet::literal(1, 3),
+                ],
+            ),
+        );
+        assert_eq!(packet.to_hex().unwrap(), "EE00D40C823060");
+
+        for (hex, expression, value) in [
+            ("C200B40A82", "sum(1, 2)", 3),
+            ("04005AC33890", "product(6, 9)", 54),
+            ("880086C3E88112", "min(7, 8, 9)", 7),
+            ("CE00C43D881120", "max(7, 8, 9)", 9),
+            ("D8005AC2A8F0", "lt(5, 15)", 1),
+            ("F600BC2D8F", "gt(5, 15)", 0),
+            ("9C005AC2F8F0", "eq(5, 15)", 0),
+            ("9C0141080250320F1802104A08", "eq(sum(1, 3), product(2, 2))", 1),
+        ] {
+            let packet = decode_hex(hex);
+            assert_eq!(packet.to_string(), expression);
+            assert_eq!(packet.value(), value);
+        }
+    }
+
+    #[test]
+    fn decode_errors() {
+        for (hex, message) in [
+            ("D2FE", "unexpected end of packet data at bit 16"),
+            ("38006F452912", "sub-packet length 27 is too long"),
+            ("38006F4529", "sub-packet length 27 is too long"),
+            ("EE00D40C82", "unexpected end of packet data at bit 40"),
+            ("3800", "unexpected end of packet data at bit 7"),
+            ("380000", "operator packet has no sub-packets"),
+            ("3800FF", "sub-packet length 63 is too long"),
+        ] {
+            let (bytes, n_bits) = bitreader::parse_hex(hex).unwrap();
+            let mut reader = BitReader::with_len(&bytes, n_bits, BitOrder::Msb);
+            assert_eq!(Packet::decode(&mut reader).unwrap_err(), message);
+        }
+    }
+
+    // Simple xorshift generator so that the tests are reproducible
+    // without needing an external crate
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, max: u64) -> u64 {
+            self.next() % max
+        }
+    }
+
+    fn random_packet(random: &mut Random, depth: u32) -> Packet {
+        let version = random.below(8) as u8;
+
+        if depth == 0 || random.below(3) == 0 {
+            let n_bits = random.below(65);
+            let value = random.next() & (u64::MAX >> (64 - n_bits.max(1)));
+            return Packet::literal(version, value);
+        }
+
+        let type_id = loop {
+            let type_id = TypeId::try_from(random.below(8) as u8).unwrap();
+
+            if type_id != TypeId::Literal {
+                break type_id;
+            }
+        };
+
+        let length_type = if random.below(2) == 0 {
+            LengthType::Bits
+        } else {
+            LengthType::Packets
+        };
+
+        let children = (0..random.below(4) + 1)
+            .map(|_| random_packet(random, depth - 1))
+            .collect();
+
+        Packet::operator(version, type_id, length_type, children)
+    }
+
+    fn version_sum(packet: &Packet) -> u32 {
+        let children_sum = match packet.body {
+            PacketBody::Literal(_) => 0,
+            PacketBody::Operator(_, ref children) => {
+                children.iter().map(version_sum).sum()
+            },
+        };
+
+        packet.version as u32 + children_sum
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut random = Random(0x2021_1216);
+
+        for _ in 0..1000 {
+            let packet = random_packet(&mut random, 5);
+
+            let mut writer = BitWriter::new(BitOrder::Msb);
+            packet.encode(&mut writer).unwrap();
+
+            assert_eq!(Packet::decode(&mut writer.reader()).unwrap(), packet);
+            assert_eq!(decode_hex(&packet.to_hex().unwrap()), packet);
+
+            // The iterator used for part 1 should see the same packets
+            assert_eq!(
+                part1(&writer.reader()),
+                version_sum(&packet),
+            );
+        }
+    }
+
+    #[test]
+    fn length_limits() {
+        // Each zero literal is 11 bits so this is the most that fit in
+        // the 15-bit length field
+        for (length_type, n_children, error) in [
+            (LengthType::Bits, 2978, None),
+            (
+                LengthType::Bits,
+                2979,
+                Some("sub-packets are 32769 bits long but the length field \
+                      only has 15 bits"),
+            ),
+            (LengthType::Packets, 2047, None),
+            (
+                LengthType::Packets,
+                2048,
+                Some("operator has 2048 sub-packets but the count field \
+                      only has 11 bits"),
+            ),
+        ] {
+            let packet = Packet::operator(
+                3,
+                TypeId::Add,
+                length_type,
+                vec![Packet::literal(0, 0); n_children],
+            );
+
+            match error {
+                None => {
+                    let hex = packet.to_hex().unwrap();
+                    assert_eq!(decode_hex(&hex), packet);
+                },
+                Some(error) => {
+                    assert_eq!(packet.to_hex().unwrap_err(), error);
+                },
+            }
+        }
+
+        // An oversized child makes its parent fail too
+        let packet = Packet::operator(
+            0,
+            TypeId::Maximum,
+            LengthType::Packets,
+            vec![Packet::operator(
+                0,
+                TypeId::Add,
+                LengthType::Packets,
+                vec![Packet::literal(0, 1); 2048],
+            )],
+        );
+        assert!(packet.to_hex().is_err());
     }
 }
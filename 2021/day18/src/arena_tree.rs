@@ -0,0 +1,571 @@
+use std::fmt;
+
+// Handle to a node in an ArenaTree. It stays valid until the node is
+// removed, after which the slot may be reused for a new node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node<T> {
+    Leaf(T),
+    Pair(NodeId, NodeId),
+}
+
+#[derive(Debug, Clone)]
+enum Slot<T> {
+    Used {
+        parent: Option<NodeId>,
+        node: Node<T>,
+    },
+    // A deleted slot with a link to the next one in the free list
+    Free(Option<usize>),
+}
+
+// A binary tree where every node is stored in a single vector and
+// deleted nodes are put on a free list to be reused. The tree can
+// contain nodes that aren’t connected to the root while it is being
+// built.
+#[derive(Debug, Clone)]
+pub struct ArenaTree<T> {
+    slots: Vec<Slot<T>>,
+    root: Option<NodeId>,
+    free_list: Option<usize>,
+    n_free: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step<T> {
+    // About to visit the children of a pair
+    Enter(NodeId, usize),
+    // Finished the left child of a pair and about to visit the right
+    Between(NodeId, usize),
+    // Finished both children of a pair
+    Leave(NodeId, usize),
+    Leaf(NodeId, usize, T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WalkState {
+    Start,
+    Left,
+    Right,
+}
+
+// Iterator that walks the tree in order without recursion. The depth
+// is the number of pairs that contain the node.
+pub struct Walk<'a, T> {
+    tree: &'a ArenaTree<T>,
+    stack: Vec<(NodeId, WalkState)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError<E> {
+    UnmatchedBracket,
+    UnexpectedEnd,
+    MissingComma,
+    TrailingData,
+    Leaf(E),
+}
+
+impl<T> ArenaTree<T> {
+    pub fn new() -> ArenaTree<T> {
+        ArenaTree {
+            slots: Vec::new(),
+            root: None,
+            free_list: None,
+            n_free: 0,
+        }
+    }
+
+    pub fn root(&self) -> Option<NodeId> {
+        self.root
+    }
+
+    pub fn set_root(&mut self, root: NodeId) {
+        self.root = Some(root);
+    }
+
+    // Number of deleted slots waiting to be reused
+    #[cfg(test)]
+    pub fn n_free(&self) -> usize {
+        self.n_free
+    }
+
+    fn add_node(&mut self, node: Node<T>) -> NodeId {
+        let slot = Slot::Used { parent: None, node };
+
+        match self.free_list {
+            Some(pos) => {
+                let Slot::Free(next) = self.slots[pos]
+                else { unreachable!(); };
+
+                self.slots[pos] = slot;
+                self.free_list = next;
+                self.n_free -= 1;
+
+                NodeId(pos)
+            },
+            None => {
+                self.slots.push(slot);
+                NodeId(self.slots.len() - 1)
+            },
+        }
+    }
+
+    fn set_parent(&mut self, child: NodeId, parent: Option<NodeId>) {
+        let Slot::Used { parent: ref mut p, .. } = self.slots[child.0]
+        else { unreachable!(); };
+
+        *p = parent;
+    }
+
+    pub fn add_leaf(&mut self, value: T) -> NodeId {
+        self.add_node(Node::Leaf(value))
+    }
+
+    // Creates a pair from two nodes that don’t have a parent yet
+    pub fn add_pair(&mut self, a: NodeId, b: NodeId) -> NodeId {
+        assert!(self.parent(a).is_none() && self.parent(b).is_none());
+
+        let pair = self.add_node(Node::Pair(a, b));
+
+        self.set_parent(a, Some(pair));
+        self.set_parent(b, Some(pair));
+
+        pair
+    }
+
+    pub fn get(&self, id: NodeId) -> &Node<T> {
+        match self.slots[id.0] {
+            Slot::Used { ref node, .. } => node,
+            Slot::Free(_) => panic!("tried to use a deleted node"),
+        }
+    }
+
+    pub fn leaf_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        match self.slots[id.0] {
+            Slot::Used { node: Node::Leaf(ref mut value), .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        match self.slots[id.0] {
+            Slot::Used { parent, .. } => parent,
+            Slot::Free(_) => panic!("tried to use a deleted node"),
+        }
+    }
+
+    pub fn children(&self, id: NodeId) -> Option<(NodeId, NodeId)> {
+        match *self.get(id) {
+            Node::Pair(a, b) => Some((a, b)),
+            Node::Leaf(_) => None,
+        }
+    }
+
+    // Deletes the node and all of its descendants. The node must
+    // have been detached from its parent first.
+    fn free_subtree(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+            if let Some((a, b)) = self.children(id) {
+                stack.push(a);
+                stack.push(b);
+            }
+
+            self.slots[id.0] = Slot::Free(self.free_list);
+            self.free_list = Some(id.0);
+            self.n_free += 1;
+        }
+    }
+
+    // Replaces the node, which can be a pair, with a new node while
+    // keeping its position in the tree. The handle to the node stays
+    // the same.
+    pub fn replace(&mut self, id: NodeId, node: Node<T>) {
+        if let Some((a, b)) = self.children(id) {
+            self.free_subtree(a);
+            self.free_subtree(b);
+        }
+
+        if let Node::Pair(a, b) = node {
+            assert!(self.parent(a).is_none() && self.parent(b).is_none());
+            self.set_parent(a, Some(id));
+            self.set_parent(b, Some(id));
+        }
+
+        let Slot::Used { node: ref mut old_node, .. } = self.slots[id.0]
+        else { unreachable!(); };
+
+        *old_node = node;
+    }
+
+    // Returns the leftmost or rightmost leaf in the subtree
+    fn edge_leaf(&self, mut id: NodeId, rightmost: bool) -> NodeId {
+        while let Some((a, b)) = self.children(id) {
+            id = if rightmost { b } else { a };
+        }
+
+        id
+    }
+
+    pub fn first_leaf(&self, id: NodeId) -> NodeId {
+        self.edge_leaf(id, false)
+    }
+
+    pub fn last_leaf(&self, id: NodeId) -> NodeId {
+        self.edge_leaf(id, true)
+    }
+
+    fn neighbour_leaf(&self, mut id: NodeId, next: bool) -> Option<NodeId> {
+        // Go up until we reach a pair where we came from the other
+        // side and then go down the nearest edge of the sibling
+        while let Some(parent) = self.parent(id) {
+            let (a, b) = self.children(parent).unwrap();
+
+            if next && a == id {
+                return Some(self.first_leaf(b));
+            } else if !next && b == id {
+                return Some(self.last_leaf(a));
+            }
+
+            id = parent;
+        }
+
+        None
+    }
+
+    // The leaf that comes before the node in an in-order walk
+    pub fn prev_leaf(&self, id: NodeId) -> Option<NodeId> {
+        self.neighbour_leaf(id, false)
+    }
+
+    // The leaf that comes after the node in an in-order walk
+    pub fn next_leaf(&self, id: NodeId) -> Option<NodeId> {
+        self.neighbour_leaf(id, true)
+    }
+
+    // Returns the sides to take to get from the root down to the node
+    pub fn path(&self, mut id: NodeId) -> Vec<Side> {
+        let mut path = Vec::new();
+
+        while let Some(parent) = self.parent(id) {
+            let (a, _) = self.children(parent).unwrap();
+
+            path.push(if a == id { Side::Left } else { Side::Right });
+
+            id = parent;
+        }
+
+        path.reverse();
+
+        path
+    }
+
+    pub fn walk(&self, id: NodeId) -> Walk<'_, T> {
+        Walk {
+            tree: self,
+            stack: vec![(id, WalkState::Start)],
+        }
+    }
+}
+
+impl<T: Clone> ArenaTree<T> {
+    // Copies the tree that is reachable from the other tree’s root
+    // into this tree and returns the handle of the copied root
+    pub fn graft(&mut self, other: &ArenaTree<T>) -> NodeId {
+        let mut stack = Vec::<NodeId>::new();
+
+        for step in other.walk(other.root.expect("grafting empty tree")) {
+            match step {
+                Step::Leaf(_, _, value) => stack.push(self.add_leaf(value)),
+                Step::Leave(..) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(self.add_pair(a, b));
+                },
+                Step::Enter(..) | Step::Between(..) => (),
+            }
+        }
+
+        stack.pop().unwrap()
+    }
+
+    // Parses nested pairs written like “[a,[b,c]]”. The parse_leaf
+    // function is called whenever a leaf is expected and it should
+    // return the value and the rest of the string after it.
+    pub fn parse<E, F>(
+        mut s: &str,
+        mut parse_leaf: F,
+    ) -> Result<ArenaTree<T>, ParseError<E>>
+        where F: FnMut(&str) -> Result<(T, &str), E>
+    {
+        let mut tree = ArenaTree::new();
+        // For each open bracket, the left side if it has been parsed
+        let mut stack = Vec::<Option<NodeId>>::new();
+
+        'parse_loop: loop {
+            match s.chars().next() {
+                None => return Err(ParseError::UnexpectedEnd),
+                Some('[') => {
+                    s = &s[1..];
+                    stack.push(None);
+                    continue;
+                },
+                Some(_) => (),
+            }
+
+            let (value, tail) = parse_leaf(s).map_err(ParseError::Leaf)?;
+            let mut node = tree.add_leaf(value);
+
+            s = tail;
+
+            loop {
+                match stack.pop() {
+                    Some(Some(a)) => {
+                        if let Some(tail) = s.strip_prefix(']') {
+                            s = tail;
+                        } else {
+                            return Err(ParseError::UnmatchedBracket);
+                        }
+
+                        node = tree.add_pair(a, node);
+                    },
+                    Some(None) => {
+                        if let Some(tail) = s.strip_prefix(',') {
+                            s = tail;
+                        } else {
+                            return Err(ParseError::MissingComma);
+                        }
+
+                        stack.push(Some(node));
+
+                        continue 'parse_loop;
+                    },
+                    None => {
+                        tree.set_root(node);
+                        break 'parse_loop;
+                    },
+                }
+            }
+        }
+
+        if s.is_empty() {
+            Ok(tree)
+        } else {
+            Err(ParseError::TrailingData)
+        }
+    }
+}
+
+impl<T> Default for ArenaTree<T> {
+    fn default() -> ArenaTree<T> {
+        ArenaTree::new()
+    }
+}
+
+impl<'a, T: Clone> Iterator for Walk<'a, T> {
+    type Item = Step<T>;
+
+    fn next(&mut self) -> Option<Step<T>> {
+        let depth = self.stack.len().checked_sub(1)?;
+        let (id, state) = self.stack.last_mut().unwrap();
+        let id = *id;
+
+        match *self.tree.get(id) {
+            Node::Leaf(ref value) => {
+                self.stack.pop();
+                Some(Step::Leaf(id, depth, value.clone()))
+            },
+            Node::Pair(a, b) => match *state {
+                WalkState::Start => {
+                    *state = WalkState::Left;
+                    self.stack.push((a, WalkState::Start));
+                    Some(Step::Enter(id, depth))
+                },
+                WalkState::Left => {
+                    *state = WalkState::Right;
+                    self.stack.push((b, WalkState::Start));
+                    Some(Step::Between(id, depth))
+                },
+                WalkState::Right => {
+                    self.stack.pop();
+                    Some(Step::Leave(id, depth))
+                },
+            },
+        }
+    }
+}
+
+// Writes the tree with the same syntax that parse accepts
+impl<T: fmt::Display + Clone> fmt::Display for ArenaTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(root) = self.root
+        else { return Ok(()); };
+
+        for step in self.walk(root) {
+            match step {
+                Step::Enter(..) => write!(f, "[")?,
+                Step::Between(..) => write!(f, ",")?,
+                Step::Leave(..) => write!(f, "]")?,
+                Step::Leaf(_, _, value) => value.fmt(f)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_char(s: &str) -> Result<(char, &str), ()> {
+        let mut chars = s.chars();
+
+        match chars.next() {
+            Some(ch) if ch.is_alphabetic() => Ok((ch, chars.as_str())),
+            _ => Err(()),
+        }
+    }
+
+    fn parse(s: &str) -> ArenaTree<char> {
+        ArenaTree::parse(s, parse_char).unwrap()
+    }
+
+    fn leaf_values(tree: &ArenaTree<char>) -> String {
+        tree.walk(tree.root().unwrap()).filter_map(|step| match step {
+            Step::Leaf(_, _, value) => Some(value),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        for s in ["a", "[a,b]", "[[a,b],[c,[d,e]]]", "[a,[b,[c,[d,e]]]]"] {
+            assert_eq!(parse(s).to_string(), s);
+        }
+
+        assert_eq!(
+            ArenaTree::parse("[a,", parse_char).unwrap_err(),
+            ParseError::UnexpectedEnd,
+        );
+        assert_eq!(
+            ArenaTree::parse("[a,1]", parse_char).unwrap_err(),
+            ParseError::Leaf(()),
+        );
+        assert_eq!(
+            ArenaTree::parse("[a]", parse_char).unwrap_err(),
+            ParseError::MissingComma,
+        );
+        assert_eq!(
+            ArenaTree::parse("[a,b,c]", parse_char).unwrap_err(),
+            ParseError::UnmatchedBracket,
+        );
+        assert_eq!(
+            ArenaTree::parse("[a,b]c", parse_char).unwrap_err(),
+            ParseError::TrailingData,
+        );
+    }
+
+    #[test]
+    fn walk() {
+        let tree = parse("[[a,b],c]");
+        let root = tree.root().unwrap();
+        let (left, c) = tree.children(root).unwrap();
+        let (a, b) = tree.children(left).unwrap();
+
+        assert_eq!(
+            tree.walk(root).collect::<Vec<_>>(),
+            &[
+                Step::Enter(root, 0),
+                Step::Enter(left, 1),
+                Step::Leaf(a, 2, 'a'),
+                Step::Between(left, 1),
+                Step::Leaf(b, 2, 'b'),
+                Step::Leave(left, 1),
+                Step::Between(root, 0),
+                Step::Leaf(c, 1, 'c'),
+                Step::Leave(root, 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn navigation() {
+        let tree = parse("[[a,[b,c]],[[d,e],f]]");
+        let leaves = tree.walk(tree.root().unwrap())
+            .filter_map(|step| match step {
+                Step::Leaf(id, _, _) => Some(id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(leaves.len(), 6);
+
+        for (i, &leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.prev_leaf(leaf), i.checked_sub(1).map(|i| leaves[i]));
+            assert_eq!(tree.next_leaf(leaf), leaves.get(i + 1).copied());
+        }
+
+        let root = tree.root().unwrap();
+        let (left, right) = tree.children(root).unwrap();
+
+        assert_eq!(tree.first_leaf(root), leaves[0]);
+        assert_eq!(tree.last_leaf(left), leaves[2]);
+        assert_eq!(tree.next_leaf(left), Some(leaves[3]));
+        assert_eq!(tree.prev_leaf(right), Some(leaves[2]));
+        assert_eq!(tree.prev_leaf(root), None);
+        assert_eq!(tree.parent(left), Some(root));
+
+        assert_eq!(tree.path(root), &[]);
+        assert_eq!(tree.path(leaves[2]), &[Side::Left, Side::Right, Side::Right]);
+        assert_eq!(tree.path(leaves[3]), &[Side::Right, Side::Left, Side::Left]);
+    }
+
+    #[test]
+    fn free_list() {
+        let mut tree = parse("[[a,b],c]");
+        let root = tree.root().unwrap();
+        let (left, _) = tree.children(root).unwrap();
+
+        assert_eq!(tree.slots.len(), 5);
+        assert_eq!(tree.n_free, 0);
+
+        tree.replace(left, Node::Leaf('z'));
+
+        assert_eq!(tree.to_string(), "[z,c]");
+        // The pair and its two leaves are replaced by one leaf
+        assert_eq!(tree.slots.len() - tree.n_free, 3);
+        assert_eq!(tree.n_free, 2);
+
+        // Reusing the slots shouldn’t grow the vector
+        let x = tree.add_leaf('x');
+        let y = tree.add_leaf('y');
+        tree.replace(left, Node::Pair(x, y));
+
+        assert_eq!(tree.to_string(), "[[x,y],c]");
+        assert_eq!(tree.n_free, 0);
+        assert_eq!(tree.slots.len(), 5);
+
+        *tree.leaf_mut(y).unwrap() = 'w';
+        assert_eq!(leaf_values(&tree), "xwc");
+        assert!(tree.leaf_mut(left).is_none());
+
+        let mut other = parse("[d,e]");
+        let grafted = other.graft(&tree);
+        let other_root = other.root().unwrap();
+        other.set_root(grafted);
+        assert_eq!(other.to_string(), "[[x,w],c]");
+        let joined = other.add_pair(other_root, grafted);
+        other.set_root(joined);
+        assert_eq!(other.to_string(), "[[d,e],[[x,w],c]]");
+    }
+}
@@ -1,475 +1,186 @@
+mod arena_tree;
+
 use std::str::FromStr;
 use std::num::ParseIntError;
 use std::fmt;
+use arena_tree::{ArenaTree, Node, NodeId, Step, Side, ParseError};
 
 #[derive(Debug, Clone)]
 struct SnailFishNumber {
-    items: Vec<SnailFishItem>,
-    root: usize,
-    magazine: Option<usize>,
-}
-
-#[derive(Debug, Clone)]
-enum SnailFishItem {
-    Integer(i32),
-    Pair(usize, usize),
-    Deleted(Option<usize>),
+    tree: ArenaTree<i32>,
 }
 
 const EXPLODE_DEPTH: usize = 4;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum DescendDirection {
-    Start,
-    Left,
-    Right,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum AddDirection {
-    Left,
-    Right,
-}
-
-#[derive(Debug, Clone, Copy)]
-struct ActionStackEntry {
-    pos: usize,
-    direction: DescendDirection,
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    // The path from the root to the pair that exploded
+    Explode(Vec<Side>),
+    // The value of the integer that was split
+    Split(i32),
 }
 
-#[derive(Debug, Clone, Copy)]
-struct MagnitudeStackEntry {
-    pos: usize,
-    a: Option<i32>,
+#[derive(Debug, Clone)]
+struct TraceStep {
+    action: Action,
+    // The number after the action was applied
+    result: String,
 }
 
 impl SnailFishNumber {
-    fn add_item(&mut self, item: SnailFishItem) -> usize {
-        match self.magazine {
-            Some(deleted) => {
-                let SnailFishItem::Deleted(next) = self.items[deleted]
-                else { unreachable!(); };
-
-                self.items[deleted] = item;
-
-                self.magazine = next;
-
-                deleted
-            },
-            None => {
-                self.items.push(item);
-                self.items.len() - 1
-            },
-        }
-    }
-
-    fn add_to_child(
-        &mut self,
-        mut child: usize,
-        direction: AddDirection,
-        amount: i32,
-    ) {
-        loop {
-            match self.items[child] {
-                SnailFishItem::Integer(ref mut value) => {
-                    *value += amount;
-                    break;
-                },
-                SnailFishItem::Pair(a, b) => {
-                    child = match direction {
-                        AddDirection::Left => b,
-                        AddDirection::Right => a,
-                    };
-                },
-                SnailFishItem::Deleted(..) => unreachable!(),
-            }
-        }
+    fn root(&self) -> NodeId {
+        self.tree.root().unwrap()
     }
 
-    fn add_to_neighbour(
-        &mut self,
-        stack: &[ActionStackEntry; EXPLODE_DEPTH],
-        direction: AddDirection,
-        amount: i32,
-    ) {
-        for entry in stack.iter().rev() {
-            let SnailFishItem::Pair(a, b) = self.items[entry.pos]
-            else { unreachable!(); };
-
-            match direction {
-                AddDirection::Left => {
-                    if entry.direction == DescendDirection::Right {
-                        self.add_to_child(a, AddDirection::Left, amount);
-                        break;
-                    }
-                },
-                AddDirection::Right => {
-                    if entry.direction == DescendDirection::Left {
-                        self.add_to_child(b, AddDirection::Right, amount);
-                        break;
-                    }
-                },
-            }
+    fn add_to_leaf(&mut self, leaf: Option<NodeId>, amount: i32) {
+        if let Some(leaf) = leaf {
+            *self.tree.leaf_mut(leaf).unwrap() += amount;
         }
     }
 
-    fn delete_item(&mut self, item: usize) {
-        self.items[item] = SnailFishItem::Deleted(self.magazine);
-        self.magazine = Some(item);
-    }
-
-    fn explode_item(
-        &mut self,
-        stack: &[ActionStackEntry; EXPLODE_DEPTH],
-        child: usize,
-    ) {
-        let SnailFishItem::Pair(a, b) = self.items[child]
-        else { unreachable!() };
+    fn explode_item(&mut self, pair: NodeId) {
+        let (a, b) = self.tree.children(pair).unwrap();
 
-        let SnailFishItem::Integer(a_value) = self.items[a]
+        let &Node::Leaf(a_value) = self.tree.get(a)
         else { unreachable!() };
-
-        self.delete_item(a);
-
-        let SnailFishItem::Integer(b_value) = self.items[b]
+        let &Node::Leaf(b_value) = self.tree.get(b)
         else { unreachable!() };
 
-        self.delete_item(b);
+        let prev = self.tree.prev_leaf(a);
+        let next = self.tree.next_leaf(b);
 
-        self.items[child] = SnailFishItem::Integer(0);
+        self.tree.replace(pair, Node::Leaf(0));
 
-        self.add_to_neighbour(&stack, AddDirection::Left, a_value);
-        self.add_to_neighbour(&stack, AddDirection::Right, b_value);
+        self.add_to_leaf(prev, a_value);
+        self.add_to_leaf(next, b_value);
     }
 
-    fn try_explode(&mut self) -> bool {
-        if !matches!(self.items[self.root], SnailFishItem::Pair(..)) {
-            return false;
-        }
-
-        let mut stack = [ActionStackEntry {
-            pos: self.root,
-            direction: DescendDirection::Start,
-        }; EXPLODE_DEPTH];
-
-        let mut depth = 1;
-
-        while depth > 0 {
-            let entry = &mut stack[depth - 1];
-            let item = &self.items[entry.pos];
-            depth -= 1;
-
-            let &SnailFishItem::Pair(a, b) = item
-            else { unreachable!(); };
-
-            let child = match entry.direction {
-                DescendDirection::Start => {
-                    entry.direction = DescendDirection::Left;
-                    a
-                },
-                DescendDirection::Left => {
-                    entry.direction = DescendDirection::Right;
-                    b
-                },
-                DescendDirection::Right => {
-                    continue;
-                },
-            };
-
-            depth += 1;
-
-            if !matches!(self.items[child], SnailFishItem::Pair(..)) {
-                continue;
-            }
-
-            if depth >= EXPLODE_DEPTH {
-                self.explode_item(&stack, child);
-                return true;
-            }
-
-            stack[depth] = ActionStackEntry {
-                pos: child,
-                direction: DescendDirection::Start,
-            };
-
-            depth += 1;
-        }
-
-        false
+    fn find_explode(&self) -> Option<NodeId> {
+        self.tree.walk(self.root()).find_map(|step| match step {
+            Step::Enter(id, depth) if depth >= EXPLODE_DEPTH => Some(id),
+            _ => None,
+        })
     }
 
-    fn split_item(&mut self, item: usize) {
-        let SnailFishItem::Integer(value) = self.items[item]
-        else { unreachable!(); };
-
-        let a_value = value / 2;
-        let b_value = (value + 1) / 2;
-
-        let a = self.add_item(SnailFishItem::Integer(a_value));
-        let b = self.add_item(SnailFishItem::Integer(b_value));
+    fn split_item(&mut self, item: NodeId, value: i32) {
+        let a = self.tree.add_leaf(value / 2);
+        let b = self.tree.add_leaf((value + 1) / 2);
 
-        self.items[item] = SnailFishItem::Pair(a, b);
+        self.tree.replace(item, Node::Pair(a, b));
     }
 
-    fn try_split(&mut self) -> bool {
-        let mut stack = [ActionStackEntry {
-            pos: self.root,
-            direction: DescendDirection::Start,
-        }; EXPLODE_DEPTH + 1];
-
-        let mut depth = 1;
-
-        while depth > 0 {
-            let entry = &mut stack[depth - 1];
-            depth -= 1;
-
-            match self.items[entry.pos] {
-                SnailFishItem::Pair(a, b) => {
-                    let child = match entry.direction {
-                        DescendDirection::Start => {
-                            entry.direction = DescendDirection::Left;
-                            a
-                        },
-                        DescendDirection::Left => {
-                            entry.direction = DescendDirection::Right;
-                            b
-                        },
-                        DescendDirection::Right => {
-                            continue;
-                        },
-                    };
-
-                    depth += 1;
-
-                    stack[depth] = ActionStackEntry {
-                        pos: child,
-                        direction: DescendDirection::Start,
-                    };
-
-                    depth += 1;
-                },
-
-                SnailFishItem::Integer(value) => {
-                    if value >= 10 {
-                        self.split_item(entry.pos);
-                        return true;
-                    }
-                },
-
-                SnailFishItem::Deleted(_) => unreachable!(),
-            }
-        }
-
-        false
+    fn find_split(&self) -> Option<(NodeId, i32)> {
+        self.tree.walk(self.root()).find_map(|step| match step {
+            Step::Leaf(id, _, value) if value >= 10 => Some((id, value)),
+            _ => None,
+        })
     }
 
     fn magnitude(&self) -> i32 {
-        let mut stack = vec![MagnitudeStackEntry {
-            pos: self.root,
-            a: None,
-        }];
-
-        'outer_loop: while let Some(entry) = stack.pop() {
-            match self.items[entry.pos] {
-                SnailFishItem::Pair(a, b) => {
-                    let child = match entry.a {
-                        None => a,
-                        Some(_) => b,
-                    };
-
-                    stack.push(entry);
-                    stack.push(MagnitudeStackEntry { pos: child, a: None });
+        let mut stack = Vec::<i32>::new();
+
+        for step in self.tree.walk(self.root()) {
+            match step {
+                Step::Leaf(_, _, value) => stack.push(value),
+                Step::Leave(..) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(a * 3 + b * 2);
                 },
-
-                SnailFishItem::Integer(mut value) => {
-                    while let Some(entry) = stack.pop() {
-                        match entry.a {
-                            Some(a) => value = a * 3 + value * 2,
-                            None => {
-                                stack.push(MagnitudeStackEntry {
-                                    pos: entry.pos,
-                                    a: Some(value),
-                                });
-                                continue 'outer_loop;
-                            },
-                        }
-                    }
-
-                    return value;
-                },
-
-                SnailFishItem::Deleted(_) => unreachable!(),
+                Step::Enter(..) | Step::Between(..) => (),
             }
         }
 
-        unreachable!();
+        stack.pop().unwrap()
     }
 
     fn add(&mut self, other: &SnailFishNumber) {
-        let offset = self.items.len() + 1;
+        let a = self.root();
+        let b = self.tree.graft(&other.tree);
+        let root = self.tree.add_pair(a, b);
 
-        self.items.push(SnailFishItem::Pair(self.root, other.root + offset));
-        self.root = self.items.len() - 1;
+        self.tree.set_root(root);
+    }
 
-        self.items.extend(other.items.iter().enumerate().map(|(pos, item)| {
-            match item {
-                SnailFishItem::Deleted(_) => {
-                    let old_next = self.magazine;
-                    self.magazine = Some(pos + offset);
-                    SnailFishItem::Deleted(old_next)
-                },
-                &SnailFishItem::Integer(value) => SnailFishItem::Integer(value),
-                SnailFishItem::Pair(a, b) => SnailFishItem::Pair(
-                    a + offset,
-                    b + offset,
-                ),
-            }
-        }));
+    // Performs a single explode or split and returns what it did, or
+    // None if the number is already reduced
+    fn reduce_step(&mut self) -> Option<Action> {
+        if let Some(pair) = self.find_explode() {
+            let path = self.tree.path(pair);
+            self.explode_item(pair);
+            Some(Action::Explode(path))
+        } else if let Some((item, value)) = self.find_split() {
+            self.split_item(item, value);
+            Some(Action::Split(value))
+        } else {
+            None
+        }
     }
 
     fn reduce(&mut self) {
-        while self.try_explode() || self.try_split() {
+        while self.reduce_step().is_some() {
         }
     }
-}
 
-struct StackEntry {
-    a: Option<usize>,
-}
+    fn reduce_trace(&mut self) -> Vec<TraceStep> {
+        let mut trace = Vec::new();
 
-impl FromStr for SnailFishNumber {
-    type Err = SnailFishError;
+        while let Some(action) = self.reduce_step() {
+            trace.push(TraceStep { action, result: self.to_string() });
+        }
 
-    fn from_str(mut s: &str) -> Result<SnailFishNumber, SnailFishError> {
-        let mut stack = Vec::<StackEntry>::new();
-        let mut items = Vec::<SnailFishItem>::new();
-
-        'parse_loop: loop {
-            let number_end = s.find(|c: char| !c.is_numeric())
-                .unwrap_or_else(|| s.len());
-
-            if number_end == 0 {
-                match s.chars().next() {
-                    None => {
-                        return Err(SnailFishError::UnexpectedEnd);
-                    },
-                    Some('[') => (),
-                    _ => {
-                        return Err(SnailFishError::InvalidCharacter);
-                    },
-                }
+        trace
+    }
+}
 
-                s = &s[1..];
+fn parse_integer(s: &str) -> Result<(i32, &str), SnailFishError> {
+    let number_end = s.find(|c: char| !c.is_numeric())
+        .unwrap_or(s.len());
 
-                stack.push(StackEntry { a: None });
+    if number_end == 0 {
+        return Err(SnailFishError::InvalidCharacter);
+    }
 
-                continue;
-            }
+    Ok((s[0..number_end].parse()?, &s[number_end..]))
+}
 
-            items.push(SnailFishItem::Integer(s[0..number_end].parse()?));
-
-            s = &s[number_end..];
-
-            loop {
-                match stack.pop() {
-                    Some(StackEntry { a: Some(a) }) => {
-                        if let Some(tail) = s.strip_prefix(']') {
-                            s = tail;
-                        } else {
-                            return Err(SnailFishError::UnmatchedBracket);
-                        }
-
-                        items.push(SnailFishItem::Pair(a, items.len() - 1));
-                    },
-                    Some(StackEntry { a: None }) => {
-                        if let Some(tail) = s.strip_prefix(',') {
-                            s = tail;
-                        } else {
-                            return Err(SnailFishError::MissingComma);
-                        }
-
-                        stack.push(StackEntry {
-                            a: Some(items.len() - 1),
-                        });
-
-                        continue 'parse_loop;
-                    },
-                    None => {
-                        break 'parse_loop;
-                    }
-                }
-            }
-        }
+impl FromStr for SnailFishNumber {
+    type Err = SnailFishError;
 
-        if !s.is_empty() {
-            Err(SnailFishError::TrailingData)
-        } else {
-            let root = items.len() - 1;
+    fn from_str(s: &str) -> Result<SnailFishNumber, SnailFishError> {
+        let tree = ArenaTree::parse(s, parse_integer)?;
 
-            Ok(SnailFishNumber { items, root, magazine: None })
-        }
+        Ok(SnailFishNumber { tree })
     }
 }
 
-enum DisplayEntryPos {
-    A,
-    B,
-    BRACKET,
-}
-
-struct DisplayEntry {
-    num: usize,
-    pos: DisplayEntryPos,
+impl fmt::Display for SnailFishNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.tree.fmt(f)
+    }
 }
 
-impl fmt::Display for SnailFishNumber {
+impl fmt::Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut stack = vec![DisplayEntry {
-            num: self.root,
-            pos: DisplayEntryPos::A,
-        }];
+        match self {
+            Action::Explode(path) => {
+                write!(f, "Explode at path ")?;
 
-        while let Some(entry) = stack.pop() {
-            let item = &self.items[entry.num];
+                for (i, side) in path.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
 
-            match &item {
-                SnailFishItem::Integer(value) => {
-                    value.fmt(f)?;
-                },
-                SnailFishItem::Pair(a, b) => {
-                    match entry.pos {
-                        DisplayEntryPos::A => {
-                            write!(f, "[")?;
-                            stack.push(DisplayEntry {
-                                num: entry.num,
-                                pos: DisplayEntryPos::B,
-                            });
-                            stack.push(DisplayEntry {
-                                num: *a,
-                                pos: DisplayEntryPos::A,
-                            });
-                        },
-                        DisplayEntryPos::B => {
-                            write!(f, ",")?;
-                            stack.push(DisplayEntry {
-                                num: entry.num,
-                                pos: DisplayEntryPos::BRACKET,
-                            });
-                            stack.push(DisplayEntry {
-                                num: *b,
-                                pos: DisplayEntryPos::A,
-                            });
-                        },
-                        DisplayEntryPos::BRACKET => {
-                            write!(f, "]")?;
-                        },
+                    match side {
+                        Side::Left => write!(f, "L")?,
+                        Side::Right => write!(f, "R")?,
                     }
-                },
-                SnailFishItem::Deleted(..) => unreachable!(),
-            }
-        }
+                }
 
-        Ok(())
+                Ok(())
+            },
+            Action::Split(value) => write!(f, "Split value {}", value),
+        }
     }
 }
 
@@ -496,6 +207,18 @@ enum SnailFishError {
     InvalidInteger(ParseIntError),
 }
 
+impl From<ParseError<SnailFishError>> for SnailFishError {
+    fn from(e: ParseError<SnailFishError>) -> SnailFishError {
+        match e {
+            ParseError::UnmatchedBracket => SnailFishError::UnmatchedBracket,
+            ParseError::UnexpectedEnd => SnailFishError::UnexpectedEnd,
+            ParseError::MissingComma => SnailFishError::MissingComma,
+            ParseError::TrailingData => SnailFishError::TrailingData,
+            ParseError::Leaf(e) => e,
+        }
+    }
+}
+
 impl From<ParseIntError> for SnailFishError {
     fn from(e: ParseIntError) -> SnailFishError {
         SnailFishError::InvalidInteger(e)
@@ -513,6 +236,33 @@ fn part1(numbers: &[SnailFishNumber]) -> i32 {
     value.magnitude()
 }
 
+// Prints the reduction steps for the first n additions in the same
+// format as the puzzle’s worked example
+fn print_trace(numbers: &[SnailFishNumber], n: usize) {
+    let mut value = numbers[0].clone();
+
+    for (i, other) in numbers[1..].iter().take(n).enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        value.add(other);
+
+        println!("after addition: {}", value);
+
+        for step in value.reduce_trace() {
+            let label = match step.action {
+                Action::Explode(_) => "explode:",
+                Action::Split(_) => "split:",
+            };
+
+            println!("after {:<9} {}  ({})", label, step.result, step.action);
+        }
+    }
+
+    println!();
+}
+
 fn part2(numbers: &[SnailFishNumber]) -> i32 {
     let mut best = i32::MIN;
 
@@ -537,7 +287,35 @@ fn part2(numbers: &[SnailFishNumber]) -> i32 {
     best
 }
 
+fn parse_args() -> Result<Option<usize>, String> {
+    let mut args = std::env::args().skip(1);
+    let mut trace = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--trace" {
+            let Some(n) = args.next().and_then(|n| n.parse().ok())
+            else {
+                return Err("--trace needs a number of additions".to_string());
+            };
+
+            trace = Some(n);
+        } else {
+            return Err(format!("unknown argument: {}", arg));
+        }
+    }
+
+    Ok(trace)
+}
+
 fn main() -> std::process::ExitCode {
+    let trace = match parse_args() {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("{}", e);
+            return std::process::ExitCode::FAILURE;
+        },
+    };
+
     let mut numbers = Vec::<SnailFishNumber>::new();
 
     for (line_num, line) in std::io::stdin().lines().enumerate() {
@@ -565,6 +343,10 @@ fn main() -> std::process::ExitCode {
         return std::process::ExitCode::FAILURE;
     }
 
+    if let Some(n) = trace {
+        print_trace(&numbers, n);
+    }
+
     println!("part 1: {}", part1(&numbers));
     println!("part 2: {}", part2(&numbers));
 
@@ -647,42 +429,68 @@ mod test {
 
         for (number, exploded) in tests.iter() {
             let mut number = number.parse::<SnailFishNumber>().unwrap();
-            assert!(number.try_explode());
+            assert!(matches!(number.reduce_step(), Some(Action::Explode(_))));
             assert_eq!(&number.to_string(), exploded);
 
-            // The magazine should contain exactly two items, ie, the
+            // The free list should contain exactly two items, ie, the
             // integer items deleted from the pair
-            let Some(deleted) = number.magazine
-            else { unreachable!(); };
-
-            let SnailFishItem::Deleted(Some(deleted)) = number.items[deleted]
-            else { unreachable!(); };
-
-            assert!(matches!(
-                number.items[deleted],
-                SnailFishItem::Deleted(None),
-            ));
+            assert_eq!(number.tree.n_free(), 2);
         }
 
-        assert!(!"[1,2]".parse::<SnailFishNumber>().unwrap().try_explode());
-        assert!(!"12".parse::<SnailFishNumber>().unwrap().try_explode());
+        assert!("[1,2]".parse::<SnailFishNumber>().unwrap().find_explode().is_none());
+        assert!("12".parse::<SnailFishNumber>().unwrap().find_explode().is_none());
     }
 
     #[test]
     fn split() {
         let tests = [
-            ("[11,5]", "[[5,6],5]"),
-            ("[[[[10,5],1],1],1]", "[[[[[5,5],5],1],1],1]"),
-            ("[1,[[[10,5],1],1]]", "[1,[[[[5,5],5],1],1]]"),
+            ("[11,5]", 11, "[[5,6],5]"),
+            ("[[[[10,5],1],1],1]", 10, "[[[[[5,5],5],1],1],1]"),
+            ("[1,[[[10,5],1],1]]", 10, "[1,[[[[5,5],5],1],1]]"),
         ];
 
-        for (number, split) in tests.iter() {
+        for &(number, value, split) in tests.iter() {
             let mut number = number.parse::<SnailFishNumber>().unwrap();
-            assert!(number.try_split());
+            assert_eq!(number.reduce_step(), Some(Action::Split(value)));
             assert_eq!(&number.to_string(), split);
         }
 
-        assert!(!"[1,2]".parse::<SnailFishNumber>().unwrap().try_split());
+        assert!("[1,2]".parse::<SnailFishNumber>().unwrap().find_split().is_none());
+    }
+
+    #[test]
+    fn trace() {
+        let mut number = "[[[[4,3],4],4],[7,[[8,4],9]]]"
+            .parse::<SnailFishNumber>()
+            .unwrap();
+        number.add(&"[1,1]".parse().unwrap());
+
+        let trace = number.reduce_trace()
+            .into_iter()
+            .map(|step| (step.action.to_string(), step.result))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            trace,
+            [
+                (
+                    "Explode at path L,L,L,L",
+                    "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]",
+                ),
+                (
+                    "Explode at path L,R,R,L",
+                    "[[[[0,7],4],[15,[0,13]]],[1,1]]",
+                ),
+                ("Split value 15", "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]"),
+                ("Split value 13", "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]"),
+                (
+                    "Explode at path L,R,R,R",
+                    "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]",
+                ),
+            ].map(|(a, b)| (a.to_string(), b.to_string())),
+        );
+
+        assert!(number.reduce_trace().is_empty());
     }
 
     #[test]
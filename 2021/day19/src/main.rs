@@ -1,39 +1,19 @@
+// The transforms have more operations than this day needs
+#[allow(dead_code)]
+mod transform;
+
 use std::str::FromStr;
 use std::process::ExitCode;
 use std::fmt;
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap, VecDeque};
+use transform::{Rotation3, Transform3};
 
-const N_ORIENTATIONS: usize = 24;
 const MIN_MATCHES: usize = 12;
+// Every pair of beacons in the overlapping area will appear in both
+// scanners
+const MIN_SHARED_PAIRS: usize = MIN_MATCHES * (MIN_MATCHES - 1) / 2;
 
-static TRANSFORMATIONS: [[i32; 9]; N_ORIENTATIONS] = [
-    [-1, 0, 0, 0, -1, 0, 0, 0, 1],
-    [-1, 0, 0, 0, 0, -1, 0, -1, 0],
-    [-1, 0, 0, 0, 0, 1, 0, 1, 0],
-    [-1, 0, 0, 0, 1, 0, 0, 0, -1],
-    [0, -1, 0, -1, 0, 0, 0, 0, -1],
-    [0, -1, 0, 0, 0, -1, 1, 0, 0],
-    [0, -1, 0, 0, 0, 1, -1, 0, 0],
-    [0, -1, 0, 1, 0, 0, 0, 0, 1],
-    [0, 0, -1, -1, 0, 0, 0, 1, 0],
-    [0, 0, -1, 0, -1, 0, -1, 0, 0],
-    [0, 0, -1, 0, 1, 0, 1, 0, 0],
-    [0, 0, -1, 1, 0, 0, 0, -1, 0],
-    [0, 0, 1, -1, 0, 0, 0, -1, 0],
-    [0, 0, 1, 0, -1, 0, 1, 0, 0],
-    [0, 0, 1, 0, 1, 0, -1, 0, 0],
-    [0, 0, 1, 1, 0, 0, 0, 1, 0],
-    [0, 1, 0, -1, 0, 0, 0, 0, 1],
-    [0, 1, 0, 0, 0, -1, -1, 0, 0],
-    [0, 1, 0, 0, 0, 1, 1, 0, 0],
-    [0, 1, 0, 1, 0, 0, 0, 0, -1],
-    [1, 0, 0, 0, -1, 0, 0, 0, -1],
-    [1, 0, 0, 0, 0, -1, 0, 1, 0],
-    [1, 0, 0, 0, 0, 1, 0, -1, 0],
-    [1, 0, 0, 0, 1, 0, 0, 0, 1],
-];
-
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 struct Position {
     coords: [i32; 3],
 }
@@ -78,29 +58,16 @@ impl fmt::Display for Position {
 }
 
 impl Position {
-    fn orientate(&self, orientation: usize) -> Position {
-        let x = self.coords[0];
-        let y = self.coords[1];
-        let z = self.coords[2];
-        let m = &TRANSFORMATIONS[orientation];
-
-        Position {
-            coords: [
-                m[0] * x + m[3] * y + m[6] * z,
-                m[1] * x + m[4] * y + m[7] * z,
-                m[2] * x + m[5] * y + m[8] * z,
-            ],
-        }
+    fn transform(&self, transform: &Transform3) -> Position {
+        Position { coords: transform.apply(self.coords) }
     }
 
-    fn offset(&self, offsets: &[i32; 3]) -> Position {
-        Position {
-            coords: [
-                self.coords[0] + offsets[0],
-                self.coords[1] + offsets[1],
-                self.coords[2] + offsets[2],
-            ],
-        }
+    fn difference(&self, other: &Position) -> [i32; 3] {
+        [
+            self.coords[0] - other.coords[0],
+            self.coords[1] - other.coords[1],
+            self.coords[2] - other.coords[2],
+        ]
     }
 }
 
@@ -117,18 +84,17 @@ impl Scanner {
         }
     }
 
-    fn matches_for_orientation_and_offset(
+    // Checks whether enough of the other scanner’s beacons line up
+    // with ours after converting them with the transform
+    fn matches_for_transform(
         &self,
         other: &Scanner,
-        orientation: usize,
-        offset: [i32; 3],
+        transform: &Transform3,
     ) -> bool {
         let mut n_matches = 0;
 
         for beacon in other.beacons.iter() {
-            let pos = beacon.orientate(orientation).offset(&offset);
-
-            if self.beacons.contains(&pos) {
+            if self.beacons.contains(&beacon.transform(transform)) {
                 n_matches += 1;
 
                 if n_matches >= MIN_MATCHES {
@@ -140,56 +106,255 @@ impl Scanner {
         false
     }
 
-    fn matches_for_orientation(
+    // Tries the alignments suggested by the fingerprints in order of
+    // how many beacon pairs voted for them until one of them actually
+    // matches enough beacons. The result converts the other scanner’s
+    // coordinates to ours.
+    fn matches(
         &self,
         other: &Scanner,
-        orientation: usize,
-    ) -> Option<[i32; 3]> {
-        // Try every pair of beacons as an offset
-        for a in self.beacons.iter() {
-            for b in other.beacons.iter() {
-                let b = b.orientate(orientation);
-
-                let offset = [
-                    a.coords[0] - b.coords[0],
-                    a.coords[1] - b.coords[1],
-                    a.coords[2] - b.coords[2],
-                ];
-
-                if self.matches_for_orientation_and_offset(
-                    other,
-                    orientation,
-                    offset.clone(),
-                ) {
-                    return Some(offset);
+        fingerprint: &Fingerprint,
+        other_fingerprint: &Fingerprint,
+    ) -> Option<Transform3> {
+        fingerprint.candidate_alignments(other_fingerprint)
+            .into_iter()
+            .find(|transform| self.matches_for_transform(other, transform))
+    }
+}
+
+// Rotation-invariant key for a pair of beacons. Rotating a scanner
+// only swaps the axes around and flips their signs so the sorted
+// absolute differences between the coordinates stay the same.
+type PairKey = [u32; 3];
+
+fn pair_key(a: &Position, b: &Position) -> PairKey {
+    let mut key: PairKey =
+        std::array::from_fn(|i| a.coords[i].abs_diff(b.coords[i]));
+    key.sort_unstable();
+    key
+}
+
+// Index of every pair of beacons seen by a scanner by their
+// rotation-invariant key
+struct Fingerprint {
+    beacons: Vec<Position>,
+    pairs: HashMap<PairKey, Vec<(usize, usize)>>,
+}
+
+impl Fingerprint {
+    fn new(scanner: &Scanner) -> Fingerprint {
+        let beacons = scanner.beacons.iter().copied().collect::<Vec<_>>();
+        let mut pairs = HashMap::<PairKey, Vec<(usize, usize)>>::new();
+
+        for (i, a) in beacons.iter().enumerate() {
+            for (j, b) in beacons.iter().enumerate().skip(i + 1) {
+                pairs.entry(pair_key(a, b)).or_default().push((i, j));
+            }
+        }
+
+        Fingerprint { beacons, pairs }
+    }
+
+    // Counts the pairs of beacons that could be the same in both
+    // scanners
+    fn n_shared_pairs(&self, other: &Fingerprint) -> usize {
+        self.pairs.iter().map(|(key, pairs)| {
+            other.pairs.get(key).map_or(0, |other_pairs| {
+                pairs.len().min(other_pairs.len())
+            })
+        }).sum()
+    }
+
+    // Each pair of pairs with the same key votes for the transform
+    // that would line them up. Returns the transforms that have enough
+    // votes to possibly be a match, with the most popular first.
+    fn candidate_alignments(&self, other: &Fingerprint) -> Vec<Transform3> {
+        let mut votes = HashMap::<Transform3, usize>::new();
+
+        for (key, pairs) in self.pairs.iter() {
+            let Some(other_pairs) = other.pairs.get(key)
+            else { continue; };
+
+            for &(i, j) in pairs.iter() {
+                let a = &self.beacons[i];
+                let a_diff = self.beacons[j].difference(a);
+
+                for &(k, l) in other_pairs.iter() {
+                    let b = &other.beacons[k];
+                    let b_diff = other.beacons[l].difference(b);
+
+                    for rotation in Rotation3::ALL.iter() {
+                        let diff = rotation.apply(b_diff);
+
+                        // The pairs might be the other way around
+                        let start = if diff == a_diff {
+                            a
+                        } else if diff.map(|c| -c) == a_diff {
+                            &self.beacons[j]
+                        } else {
+                            continue;
+                        };
+
+                        let transform = Transform3::new(
+                            *rotation,
+                            start.difference(&Position {
+                                coords: rotation.apply(b.coords),
+                            }),
+                        );
+
+                        *votes.entry(transform).or_insert(0) += 1;
+                    }
                 }
             }
         }
 
-        None
+        let mut candidates = votes.into_iter()
+            .filter(|&(_, n_votes)| n_votes >= MIN_SHARED_PAIRS)
+            .collect::<Vec<_>>();
+
+        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        candidates.into_iter().map(|(alignment, _)| alignment).collect()
     }
+}
+
+// Where a scanner ended up relative to scanner 0. The relative
+// transform converts the scanner’s coordinates to those of the scanner
+// it was matched to and the absolute transform converts them to the
+// coordinates of scanner 0.
+struct Placement {
+    parent: Option<usize>,
+    relative: Transform3,
+    absolute: Transform3,
+}
 
-    fn matches(&self, other: &Scanner) -> Option<(usize, [i32; 3])> {
-        for orientation in 0..N_ORIENTATIONS {
-            if let Some(offset) =
-                self.matches_for_orientation(other, orientation)
-            {
-                return Some((orientation, offset));
+fn format_matrix(rotation: &Rotation3) -> String {
+    let rows = rotation.to_matrix().map(|row| {
+        format!("{}, {}, {}", row[0], row[1], row[2])
+    });
+
+    format!("[{}]", rows.join("; "))
+}
+
+// Finds the scanners whose fingerprints share enough pairs of beacons
+// that they might overlap
+fn find_overlaps(fingerprints: &[Fingerprint]) -> Vec<Vec<usize>> {
+    let mut overlaps = vec![Vec::new(); fingerprints.len()];
+
+    for (i, a) in fingerprints.iter().enumerate() {
+        for (j, b) in fingerprints.iter().enumerate().skip(i + 1) {
+            if a.n_shared_pairs(b) >= MIN_SHARED_PAIRS {
+                overlaps[i].push(j);
+                overlaps[j].push(i);
             }
         }
+    }
+
+    overlaps
+}
 
-        None
+// Aligns every scanner that can be reached from scanner 0 through the
+// overlap graph. Each scanner is matched against its neighbour in its
+// own coordinates and the transforms are chained together to get back
+// to scanner 0.
+fn place_scanners(
+    scanners: &[Scanner],
+    fingerprints: &[Fingerprint],
+    overlaps: &[Vec<usize>],
+) -> Vec<Option<Placement>> {
+    let mut placements =
+        scanners.iter().map(|_| None).collect::<Vec<Option<Placement>>>();
+
+    if scanners.is_empty() {
+        return placements;
     }
 
-    fn normalise(&mut self, orientation: usize, offset: [i32; 3]) {
-        let mut beacons = HashSet::new();
+    placements[0] = Some(Placement {
+        parent: None,
+        relative: Transform3::IDENTITY,
+        absolute: Transform3::IDENTITY,
+    });
+
+    let mut queue = VecDeque::from([0]);
 
-        for beacon in self.beacons.iter() {
-            let pos = beacon.orientate(orientation).offset(&offset);
-            beacons.insert(pos);
+    while let Some(placed) = queue.pop_front() {
+        let parent_transform = placements[placed].as_ref().unwrap().absolute;
+
+        for &next in overlaps[placed].iter() {
+            if placements[next].is_some() {
+                continue;
+            }
+
+            let Some(relative) = scanners[placed].matches(
+                &scanners[next],
+                &fingerprints[placed],
+                &fingerprints[next],
+            )
+            else { continue; };
+
+            placements[next] = Some(Placement {
+                parent: Some(placed),
+                relative,
+                absolute: parent_transform.compose(&relative),
+            });
+
+            queue.push_back(next);
+        }
+    }
+
+    placements
+}
+
+fn print_report(
+    scanners: &[Scanner],
+    overlaps: &[Vec<usize>],
+    placements: &[Option<Placement>],
+) {
+    let edges = overlaps.iter().enumerate().flat_map(|(i, neighbours)| {
+        neighbours.iter()
+            .filter(move |&&j| j > i)
+            .map(move |&j| {
+                format!("{}-{}", scanners[i].name, scanners[j].name)
+            })
+    }).collect::<Vec<_>>();
+
+    println!("overlaps: {}", edges.join(", "));
+
+    for (scanner, placement) in scanners.iter().zip(placements.iter()) {
+        let Some(placement) = placement
+        else {
+            println!("scanner {}: unmatched", scanner.name);
+            continue;
+        };
+
+        let absolute = &placement.absolute;
+
+        print!(
+            "scanner {}: position {}",
+            scanner.name,
+            Position { coords: absolute.translation },
+        );
+
+        if let Some(parent) = placement.parent {
+            // How the parent looks from this scanner
+            let inverse = placement.relative.inverse();
+
+            print!(
+                ", matched to {} with {} at {} (seen from here with {} \
+                 at {})",
+                scanners[parent].name,
+                placement.relative.rotation,
+                Position { coords: placement.relative.translation },
+                inverse.rotation,
+                Position { coords: inverse.translation },
+            );
         }
 
-        self.beacons = beacons;
+        println!(
+            ", orientation {} {}",
+            absolute.rotation,
+            format_matrix(&absolute.rotation),
+        );
     }
 }
 
@@ -245,7 +410,7 @@ fn biggest_distance(offsets: &[[i32; 3]]) -> i32 {
 }
 
 fn main() -> ExitCode {
-    let mut scanners = match read_scanners() {
+    let scanners = match read_scanners() {
         Ok(scanners) => scanners,
         Err(e) => {
             eprintln!("{}", e);
@@ -253,47 +418,24 @@ fn main() -> ExitCode {
         }
     };
 
-    let mut matched_scanners = vec![scanners.swap_remove(0)];
-    let mut scanner_offsets = vec![[0, 0, 0]];
-
-    'match_loop: loop {
-        for (scanner_num, scanner) in scanners.iter().enumerate() {
-            for normalised in matched_scanners.iter() {
-                if let Some((orientation, offset)) =
-                    normalised.matches(scanner)
-                {
-                    let mut scanner = scanners.swap_remove(scanner_num);
-
-                    println!(
-                        "matched {} to {} with orientation {} and offset {:?}",
-                        scanner.name,
-                        normalised.name,
-                        orientation,
-                        offset,
-                    );
-
-                    scanner_offsets.push(offset.clone());
-
-                    scanner.normalise(orientation, offset);
+    let fingerprints = scanners.iter()
+        .map(Fingerprint::new)
+        .collect::<Vec<_>>();
+    let overlaps = find_overlaps(&fingerprints);
+    let placements = place_scanners(&scanners, &fingerprints, &overlaps);
 
-                    matched_scanners.push(scanner);
-
-                    continue 'match_loop;
-                }
-            }
-        }
-
-        break;
-    }
-
-    if !scanners.is_empty() {
-        println!("!!! unmatched beacons: {} !!!", scanners.len());
-    }
+    print_report(&scanners, &overlaps, &placements);
 
     let mut unique_beacons = HashSet::new();
-
-    for scanner in matched_scanners.into_iter() {
-        unique_beacons.extend(scanner.beacons.into_iter());
+    let mut scanner_offsets = Vec::new();
+
+    for (scanner, placement) in scanners.iter().zip(placements.iter()) {
+        if let Some(placement) = placement {
+            unique_beacons.extend(scanner.beacons.iter().map(|beacon| {
+                beacon.transform(&placement.absolute)
+            }));
+            scanner_offsets.push(placement.absolute.translation);
+        }
     }
 
     println!("part 1: {} unique beacons", unique_beacons.len());
@@ -307,15 +449,94 @@ mod test {
     use super::*;
 
     #[test]
-    fn all_orientations_different() {
-        const BASE_POS: Position = Position { coords: [3, 4, 5] };
+    fn pair_keys_rotation_invariant() {
+        let a = Position { coords: [3, -4, 5] };
+        let b = Position { coords: [-7, 2, 6] };
+        let key = pair_key(&a, &b);
+
+        assert_eq!(key, [1, 6, 10]);
+
+        for rotation in Rotation3::ALL.iter() {
+            let transform = Transform3::new(*rotation, [1, 2, 3]);
 
-        let mut orientations = HashSet::new();
+            assert_eq!(
+                pair_key(&a.transform(&transform), &b.transform(&transform)),
+                key,
+            );
+        }
+    }
 
-        for i in 0..N_ORIENTATIONS {
-            orientations.insert(BASE_POS.orientate(i));
+    #[test]
+    fn align_with_fingerprints() {
+        // Pseudo-random beacons. The first two scanners both see
+        // beacons 13..28 and the last two both see 28..40.
+        let mut seed = 1u32;
+        let mut beacons = Vec::new();
+
+        while beacons.len() < 40 {
+            let coords = std::array::from_fn(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as i32 % 2000 - 1000
+            });
+
+            beacons.push(Position { coords });
         }
 
-        assert_eq!(orientations.len(), N_ORIENTATIONS);
+        for rotation in Rotation3::ALL.iter() {
+            let transform = Transform3::new(*rotation, [500, -300, 1200]);
+
+            let mut a = Scanner::new(0);
+            a.beacons.extend(beacons[0..28].iter().copied());
+
+            // The second scanner sees the world from the offset and
+            // rotated so that applying the transform gets back to the
+            // original position
+            let mut b = Scanner::new(1);
+            b.beacons.extend(beacons[13..].iter().map(|pos| {
+                pos.transform(&transform.inverse())
+            }));
+
+            // A third scanner that only overlaps with the second, so it
+            // has to be placed by chaining through it
+            let mut c = Scanner::new(2);
+            let c_transform = Transform3::new(
+                rotation.inverse(),
+                [-20, 30, 0],
+            );
+            let world_to_c = c_transform.inverse().compose(&transform.inverse());
+            c.beacons.extend(beacons[28..].iter().map(|pos| {
+                pos.transform(&world_to_c)
+            }));
+
+            let scanners = [a, b, c];
+            let fingerprints = scanners.iter()
+                .map(Fingerprint::new)
+                .collect::<Vec<_>>();
+            let overlaps = find_overlaps(&fingerprints);
+
+            assert_eq!(overlaps, [vec![1], vec![0, 2], vec![1]]);
+            assert_eq!(
+                scanners[0].matches(
+                    &scanners[1],
+                    &fingerprints[0],
+                    &fingerprints[1],
+                ),
+                Some(transform),
+            );
+
+            let placements =
+                place_scanners(&scanners, &fingerprints, &overlaps);
+
+            let placement = placements[1].as_ref().unwrap();
+            assert_eq!(placement.parent, Some(0));
+            assert_eq!(placement.absolute, transform);
+
+            let placement = placements[2].as_ref().unwrap();
+            assert_eq!(placement.parent, Some(1));
+            assert_eq!(placement.relative, c_transform);
+            assert!(scanners[2].beacons.iter().all(|b| {
+                beacons.contains(&b.transform(&placement.absolute))
+            }));
+        }
     }
 }
@@ -0,0 +1,366 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub const N_ROTATIONS: usize = 24;
+
+static AXIS_NAMES: [char; 3] = ['x', 'y', 'z'];
+
+// A rotation by a multiple of 90° around the axes. Each coordinate of
+// the result is one of the coordinates of the input, possibly
+// negated, so it is stored as a permutation of the axes with a sign
+// for each one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rotation3 {
+    axes: [usize; 3],
+    signs: [i32; 3],
+}
+
+impl Rotation3 {
+    pub const IDENTITY: Rotation3 = Rotation3 {
+        axes: [0, 1, 2],
+        signs: [1, 1, 1],
+    };
+
+    // All of the rotations that keep the cube the right way out
+    pub const ALL: [Rotation3; N_ROTATIONS] = Rotation3::all();
+
+    // Returns None if the axes aren’t a permutation or the signs
+    // would make it a reflection instead of a rotation
+    pub const fn new(axes: [usize; 3], signs: [i32; 3]) -> Option<Rotation3> {
+        let mut i = 0;
+
+        while i < 3 {
+            if axes[i] >= 3 || (signs[i] != 1 && signs[i] != -1) {
+                return None;
+            }
+
+            i += 1;
+        }
+
+        if axes[0] == axes[1] || axes[0] == axes[2] || axes[1] == axes[2] {
+            return None;
+        }
+
+        // An odd permutation of the axes needs an odd number of
+        // negations to stay a rotation
+        let parity = if (axes[0] + 1) % 3 == axes[1] { 1 } else { -1 };
+
+        if signs[0] * signs[1] * signs[2] != parity {
+            return None;
+        }
+
+        Some(Rotation3 { axes, signs })
+    }
+
+    const fn all() -> [Rotation3; N_ROTATIONS] {
+        const PERMUTATIONS: [[usize; 3]; 6] = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+
+        let mut rotations = [Rotation3::IDENTITY; N_ROTATIONS];
+        let mut n_rotations = 0;
+        let mut permutation = 0;
+
+        while permutation < PERMUTATIONS.len() {
+            let mut bits = 0;
+
+            while bits < 8 {
+                let signs = [
+                    1 - 2 * (bits & 1),
+                    1 - (bits & 2),
+                    1 - ((bits & 4) >> 1),
+                ];
+
+                if let Some(rotation) =
+                    Rotation3::new(PERMUTATIONS[permutation], signs)
+                {
+                    rotations[n_rotations] = rotation;
+                    n_rotations += 1;
+                }
+
+                bits += 1;
+            }
+
+            permutation += 1;
+        }
+
+        assert!(n_rotations == N_ROTATIONS);
+
+        rotations
+    }
+
+    pub const fn apply(&self, pos: [i32; 3]) -> [i32; 3] {
+        [
+            self.signs[0] * pos[self.axes[0]],
+            self.signs[1] * pos[self.axes[1]],
+            self.signs[2] * pos[self.axes[2]],
+        ]
+    }
+
+    // Returns the rotation that applies other first and then self
+    pub const fn compose(&self, other: &Rotation3) -> Rotation3 {
+        let mut axes = [0; 3];
+        let mut signs = [0; 3];
+        let mut i = 0;
+
+        while i < 3 {
+            axes[i] = other.axes[self.axes[i]];
+            signs[i] = self.signs[i] * other.signs[self.axes[i]];
+            i += 1;
+        }
+
+        Rotation3 { axes, signs }
+    }
+
+    pub const fn inverse(&self) -> Rotation3 {
+        let mut axes = [0; 3];
+        let mut signs = [0; 3];
+        let mut i = 0;
+
+        while i < 3 {
+            axes[self.axes[i]] = i;
+            signs[self.axes[i]] = self.signs[i];
+            i += 1;
+        }
+
+        Rotation3 { axes, signs }
+    }
+
+    // The rows of the matrix that multiplies a column vector
+    pub const fn to_matrix(self) -> [[i32; 3]; 3] {
+        let mut matrix = [[0; 3]; 3];
+        let mut i = 0;
+
+        while i < 3 {
+            matrix[i][self.axes[i]] = self.signs[i];
+            i += 1;
+        }
+
+        matrix
+    }
+
+    pub fn from_matrix(matrix: &[[i32; 3]; 3]) -> Option<Rotation3> {
+        let mut axes = [0; 3];
+        let mut signs = [0; 3];
+
+        for (i, row) in matrix.iter().enumerate() {
+            let mut non_zero = row.iter().enumerate().filter(|(_, &v)| v != 0);
+            let (axis, &sign) = non_zero.next()?;
+
+            if non_zero.next().is_some() {
+                return None;
+            }
+
+            axes[i] = axis;
+            signs[i] = sign;
+        }
+
+        Rotation3::new(axes, signs)
+    }
+}
+
+// Prints the rotation as the axes that make up each coordinate of the
+// result, like “-y,x,z”
+impl fmt::Display for Rotation3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..3 {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+
+            if self.signs[i] < 0 {
+                write!(f, "-")?;
+            }
+
+            write!(f, "{}", AXIS_NAMES[self.axes[i]])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Rotation3 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rotation3, String> {
+        let mut axes = [0; 3];
+        let mut signs = [1; 3];
+        let mut parts = s.split(',');
+
+        for i in 0..3 {
+            let Some(part) = parts.next()
+            else {
+                return Err("Not enough axes".to_string());
+            };
+
+            let part = part.trim();
+
+            let name = match part.strip_prefix('-') {
+                Some(name) => {
+                    signs[i] = -1;
+                    name
+                },
+                None => part,
+            };
+
+            let Some(axis) = AXIS_NAMES.iter()
+                .position(|&axis_name| name == axis_name.to_string())
+            else {
+                return Err(format!("Invalid axis “{}”", part));
+            };
+
+            axes[i] = axis;
+        }
+
+        if parts.next().is_some() {
+            return Err("Too many axes".to_string());
+        }
+
+        Rotation3::new(axes, signs)
+            .ok_or_else(|| format!("“{}” isn’t a rotation", s))
+    }
+}
+
+// A rotation followed by a translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Transform3 {
+    pub rotation: Rotation3,
+    pub translation: [i32; 3],
+}
+
+impl Transform3 {
+    pub const IDENTITY: Transform3 = Transform3 {
+        rotation: Rotation3::IDENTITY,
+        translation: [0, 0, 0],
+    };
+
+    pub const fn new(rotation: Rotation3, translation: [i32; 3]) -> Transform3 {
+        Transform3 { rotation, translation }
+    }
+
+    pub const fn apply(&self, pos: [i32; 3]) -> [i32; 3] {
+        let pos = self.rotation.apply(pos);
+
+        [
+            pos[0] + self.translation[0],
+            pos[1] + self.translation[1],
+            pos[2] + self.translation[2],
+        ]
+    }
+
+    // Returns the transform that applies other first and then self
+    pub const fn compose(&self, other: &Transform3) -> Transform3 {
+        Transform3 {
+            rotation: self.rotation.compose(&other.rotation),
+            translation: self.apply(other.translation),
+        }
+    }
+
+    pub const fn inverse(&self) -> Transform3 {
+        let rotation = self.rotation.inverse();
+        let translation = rotation.apply(self.translation);
+
+        Transform3 {
+            rotation,
+            translation: [-translation[0], -translation[1], -translation[2]],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn all_rotations() {
+        const BASE_POS: [i32; 3] = [3, 4, 5];
+
+        let positions = Rotation3::ALL.iter()
+            .map(|r| r.apply(BASE_POS))
+            .collect::<HashSet<_>>();
+
+        assert_eq!(positions.len(), N_ROTATIONS);
+
+        let rotations = Rotation3::ALL.iter().collect::<HashSet<_>>();
+
+        for a in Rotation3::ALL.iter() {
+            assert_eq!(a.compose(&a.inverse()), Rotation3::IDENTITY);
+            assert_eq!(a.inverse().compose(a), Rotation3::IDENTITY);
+
+            for b in Rotation3::ALL.iter() {
+                let c = a.compose(b);
+
+                assert!(rotations.contains(&c));
+                assert_eq!(c.apply(BASE_POS), a.apply(b.apply(BASE_POS)));
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turns() {
+        // Turning 90° around z four times gets back to the start
+        let turn = "-y,x,z".parse::<Rotation3>().unwrap();
+
+        assert_eq!(turn.apply([1, 0, 0]), [0, 1, 0]);
+        assert_eq!(turn.apply([0, 1, 0]), [-1, 0, 0]);
+        assert_eq!(turn.compose(&turn).to_string(), "-x,-y,z");
+        assert_eq!(
+            turn.compose(&turn).compose(&turn).compose(&turn),
+            Rotation3::IDENTITY,
+        );
+        assert_eq!(turn.inverse().to_string(), "y,-x,z");
+    }
+
+    #[test]
+    fn notation() {
+        for rotation in Rotation3::ALL.iter() {
+            assert_eq!(rotation.to_string().parse(), Ok(*rotation));
+            assert_eq!(
+                Rotation3::from_matrix(&rotation.to_matrix()),
+                Some(*rotation),
+            );
+        }
+
+        assert_eq!(
+            Rotation3::IDENTITY.to_matrix(),
+            [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+        );
+        assert_eq!(
+            "z, -y, x".parse::<Rotation3>().unwrap().to_matrix(),
+            [[0, 0, 1], [0, -1, 0], [1, 0, 0]],
+        );
+
+        // Reflections aren’t allowed
+        assert!("-x,y,z".parse::<Rotation3>().is_err());
+        assert!("y,x,z".parse::<Rotation3>().is_err());
+        assert!("x,x,z".parse::<Rotation3>().is_err());
+        assert!("x,y".parse::<Rotation3>().is_err());
+        assert!("x,y,w".parse::<Rotation3>().is_err());
+        assert_eq!(
+            Rotation3::from_matrix(&[[1, 0, 0], [0, 1, 1], [0, 0, 1]]),
+            None,
+        );
+    }
+
+    #[test]
+    fn transforms() {
+        let a = Transform3::new("y,-x,z".parse().unwrap(), [10, 20, 30]);
+        let b = Transform3::new("-x,z,y".parse().unwrap(), [-1, 5, 2]);
+        let pos = [7, -3, 12];
+
+        assert_eq!(a.apply(pos), [7, 13, 42]);
+        assert_eq!(a.compose(&b).apply(pos), a.apply(b.apply(pos)));
+        assert_eq!(a.inverse().apply(a.apply(pos)), pos);
+        assert_eq!(a.compose(&a.inverse()), Transform3::IDENTITY);
+        assert_eq!(
+            a.compose(&b).inverse(),
+            b.inverse().compose(&a.inverse()),
+        );
+    }
+}
@@ -0,0 +1,623 @@
+// Half-open range of coordinates from min up to but not including max
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CoordRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+// An axis-aligned box in N dimensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoxRange<const N: usize> {
+    pub coords: [CoordRange; N],
+}
+
+impl CoordRange {
+    pub fn is_empty(&self) -> bool {
+        self.min >= self.max
+    }
+}
+
+impl<const N: usize> BoxRange<N> {
+    pub fn is_empty(&self) -> bool {
+        self.coords.iter().any(CoordRange::is_empty)
+    }
+
+    pub fn intersects(&self, other: &BoxRange<N>) -> bool {
+        for i in 0..N {
+            if other.coords[i].max <= self.coords[i].min
+                || other.coords[i].min >= self.coords[i].max
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn contains(&self, other: &BoxRange<N>) -> bool {
+        for i in 0..N {
+            if self.coords[i].min > other.coords[i].min
+                || self.coords[i].max < other.coords[i].max
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn contains_point(&self, point: [i32; N]) -> bool {
+        self.coords.iter().zip(point).all(|(range, p)| {
+            p >= range.min && p < range.max
+        })
+    }
+
+    pub fn intersection(&self, other: &BoxRange<N>) -> Option<BoxRange<N>> {
+        let mut result = *self;
+
+        for (range, other) in result.coords.iter_mut().zip(other.coords.iter()) {
+            range.min = range.min.max(other.min);
+            range.max = range.max.min(other.max);
+        }
+
+        (!result.is_empty()).then_some(result)
+    }
+
+    pub fn volume(&self) -> u64 {
+        let mut volume = 1;
+
+        for range in self.coords.iter() {
+            volume *= (range.max as i64 - range.min as i64) as u64;
+        }
+
+        volume
+    }
+
+    // Calls the function with each of the up to 2×N boxes that cover
+    // the parts of self that are outside of other
+    fn split_around<F>(&self, other: &BoxRange<N>, mut func: F)
+        where F: FnMut(BoxRange<N>)
+    {
+        let mut part = *self;
+
+        for i in 0..N {
+            if other.coords[i].min > part.coords[i].min {
+                let mut to_add = part;
+                to_add.coords[i].max = other.coords[i].min;
+                func(to_add);
+
+                part.coords[i].min = other.coords[i].min;
+            }
+
+            if other.coords[i].max < part.coords[i].max {
+                let mut to_add = part;
+                to_add.coords[i].min = other.coords[i].max;
+                func(to_add);
+
+                part.coords[i].max = other.coords[i].max;
+            }
+        }
+    }
+}
+
+// A set of points stored as a list of disjoint boxes. Overlapping
+// boxes are split when they are added so the volume is just the sum
+// of the box volumes.
+#[derive(Debug)]
+pub struct BoxSet<const N: usize> {
+    boxes: Vec<BoxRange<N>>,
+    temp: Vec<BoxRange<N>>,
+}
+
+impl<const N: usize> BoxSet<N> {
+    pub fn new() -> BoxSet<N> {
+        BoxSet {
+            boxes: Vec::new(),
+            temp: Vec::new(),
+        }
+    }
+
+    pub fn from_box(range: &BoxRange<N>) -> BoxSet<N> {
+        let mut set = BoxSet::new();
+
+        if !range.is_empty() {
+            set.boxes.push(*range);
+        }
+
+        set
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    // Iterates the disjoint boxes that make up the set
+    pub fn iter(&self) -> std::slice::Iter<'_, BoxRange<N>> {
+        self.boxes.iter()
+    }
+
+    pub fn subtract(&mut self, range: &BoxRange<N>) {
+        self.temp.clear();
+
+        for part in self.boxes.iter() {
+            // If the range is outside the part then just leave it as is
+            if !part.intersects(range) {
+                self.temp.push(*part);
+                continue;
+            }
+
+            // If the range covers the entire part then skip it
+            if range.contains(part) {
+                continue;
+            }
+
+            part.split_around(range, |to_add| self.temp.push(to_add));
+        }
+
+        std::mem::swap(&mut self.boxes, &mut self.temp);
+    }
+
+    pub fn add(&mut self, range: &BoxRange<N>) {
+        // Cut the range up so that only boxes that don’t already
+        // intersect with self are in it
+        let mut range = BoxSet::from_box(range);
+
+        for part in self.boxes.iter() {
+            range.subtract(part);
+        }
+
+        // Anything left in the range can just be added directly to
+        // the list of boxes
+        self.boxes.extend(range.boxes);
+    }
+
+    // Removes everything outside of the range
+    pub fn intersect(&mut self, range: &BoxRange<N>) {
+        self.boxes.retain_mut(|part| {
+            match part.intersection(range) {
+                Some(intersection) => {
+                    *part = intersection;
+                    true
+                },
+                None => false,
+            }
+        });
+    }
+
+    pub fn union_with(&mut self, other: &BoxSet<N>) {
+        for part in other.iter() {
+            self.add(part);
+        }
+    }
+
+    pub fn subtract_set(&mut self, other: &BoxSet<N>) {
+        for part in other.iter() {
+            self.subtract(part);
+        }
+    }
+
+    pub fn intersection(&self, other: &BoxSet<N>) -> BoxSet<N> {
+        let mut result = BoxSet::new();
+
+        // Both sets are disjoint so the intersections of each pair of
+        // boxes are disjoint too
+        for a in self.iter() {
+            for b in other.iter() {
+                if let Some(part) = a.intersection(b) {
+                    result.boxes.push(part);
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn contains_point(&self, point: [i32; N]) -> bool {
+        self.boxes.iter().any(|part| part.contains_point(point))
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.boxes.iter().map(BoxRange::volume).sum()
+    }
+}
+
+impl<const N: usize> Default for BoxSet<N> {
+    fn default() -> BoxSet<N> {
+        BoxSet::new()
+    }
+}
+
+impl<const N: usize> Clone for BoxSet<N> {
+    fn clone(&self) -> BoxSet<N> {
+        BoxSet {
+            boxes: self.boxes.clone(),
+            temp: Vec::new(),
+        }
+    }
+}
+
+// An alternative set of boxes that splits space into a grid of cells
+// along every box edge that will be used and stores a flag for each
+// cell. All of the boxes that will be added or removed need to be
+// known up front. This is faster when there are few boxes that
+// overlap a lot, but the memory needed grows with the number of boxes
+// to the power of N.
+#[derive(Clone, Debug)]
+pub struct CompressedBoxSet<const N: usize> {
+    // The sorted distinct edges along each axis. Cell i along an axis
+    // covers edges[i]..edges[i + 1].
+    edges: [Vec<i32>; N],
+    cells: Vec<bool>,
+}
+
+impl<const N: usize> CompressedBoxSet<N> {
+    pub fn new<'a, I>(boxes: I) -> CompressedBoxSet<N>
+        where I: IntoIterator<Item = &'a BoxRange<N>>
+    {
+        let mut edges: [Vec<i32>; N] = std::array::from_fn(|_| Vec::new());
+
+        for range in boxes {
+            for (axis, coord) in edges.iter_mut().zip(range.coords.iter()) {
+                axis.push(coord.min);
+                axis.push(coord.max);
+            }
+        }
+
+        for axis in edges.iter_mut() {
+            axis.sort_unstable();
+            axis.dedup();
+        }
+
+        let n_cells = edges.iter()
+            .map(|axis| axis.len().saturating_sub(1))
+            .product();
+
+        CompressedBoxSet {
+            edges,
+            cells: vec![false; n_cells],
+        }
+    }
+
+    fn cell_ranges(&self, range: &BoxRange<N>) -> [std::ops::Range<usize>; N] {
+        std::array::from_fn(|i| {
+            let edge_index = |coord| {
+                self.edges[i].binary_search(&coord)
+                    .expect("box wasn’t given when the set was created")
+            };
+
+            edge_index(range.coords[i].min)..edge_index(range.coords[i].max)
+        })
+    }
+
+    // Calls the function with the index of every cell within the cell
+    // ranges
+    fn for_each_cell<F>(&self, ranges: &[std::ops::Range<usize>; N], mut func: F)
+        where F: FnMut(usize, &[usize; N])
+    {
+        if ranges.iter().any(|range| range.is_empty()) {
+            return;
+        }
+
+        let mut pos: [usize; N] = std::array::from_fn(|i| ranges[i].start);
+
+        loop {
+            let mut index = 0;
+
+            for (p, axis) in pos.iter().zip(self.edges.iter()) {
+                index = index * (axis.len() - 1) + p;
+            }
+
+            func(index, &pos);
+
+            // Advance the position like an odometer
+            let mut axis = N;
+
+            loop {
+                if axis == 0 {
+                    return;
+                }
+
+                axis -= 1;
+                pos[axis] += 1;
+
+                if pos[axis] < ranges[axis].end {
+                    break;
+                }
+
+                pos[axis] = ranges[axis].start;
+            }
+        }
+    }
+
+    fn set_range(&mut self, range: &BoxRange<N>, value: bool) {
+        if range.is_empty() {
+            return;
+        }
+
+        let ranges = self.cell_ranges(range);
+        let mut indices = Vec::new();
+
+        self.for_each_cell(&ranges, |index, _| indices.push(index));
+
+        for index in indices {
+            self.cells[index] = value;
+        }
+    }
+
+    pub fn add(&mut self, range: &BoxRange<N>) {
+        self.set_range(range, true);
+    }
+
+    pub fn subtract(&mut self, range: &BoxRange<N>) {
+        self.set_range(range, false);
+    }
+
+    fn cell_box(&self, pos: &[usize; N]) -> BoxRange<N> {
+        BoxRange {
+            coords: std::array::from_fn(|i| CoordRange {
+                min: self.edges[i][pos[i]],
+                max: self.edges[i][pos[i] + 1],
+            }),
+        }
+    }
+
+    // Returns the boxes for every cell that is in the set
+    pub fn boxes(&self) -> Vec<BoxRange<N>> {
+        let ranges = std::array::from_fn(|i| {
+            0..self.edges[i].len().saturating_sub(1)
+        });
+        let mut boxes = Vec::new();
+
+        self.for_each_cell(&ranges, |index, pos| {
+            if self.cells[index] {
+                boxes.push(self.cell_box(pos));
+            }
+        });
+
+        boxes
+    }
+
+    // Removes everything outside of the range
+    pub fn intersect(&mut self, range: &BoxRange<N>) {
+        let mut inside = vec![false; self.cells.len()];
+
+        if !range.is_empty() {
+            let ranges = self.cell_ranges(range);
+            self.for_each_cell(&ranges, |index, _| inside[index] = true);
+        }
+
+        for (cell, inside) in self.cells.iter_mut().zip(inside) {
+            *cell &= inside;
+        }
+    }
+
+    // Combines each cell with the matching cell of the other set. Both
+    // sets need to have been created from the same boxes.
+    fn combine_cells<F>(&mut self, other: &CompressedBoxSet<N>, func: F)
+        where F: Fn(bool, bool) -> bool
+    {
+        assert!(
+            self.edges == other.edges,
+            "sets weren’t created from the same boxes",
+        );
+
+        for (cell, &other) in self.cells.iter_mut().zip(other.cells.iter()) {
+            *cell = func(*cell, other);
+        }
+    }
+
+    pub fn union_with(&mut self, other: &CompressedBoxSet<N>) {
+        self.combine_cells(other, |a, b| a || b);
+    }
+
+    pub fn subtract_set(&mut self, other: &CompressedBoxSet<N>) {
+        self.combine_cells(other, |a, b| a && !b);
+    }
+
+    pub fn intersection(
+        &self,
+        other: &CompressedBoxSet<N>,
+    ) -> CompressedBoxSet<N> {
+        let mut result = self.clone();
+        result.combine_cells(other, |a, b| a && b);
+        result
+    }
+
+    pub fn contains_point(&self, point: [i32; N]) -> bool {
+        let mut index = 0;
+
+        for (axis, p) in self.edges.iter().zip(point) {
+            // The cell is the last edge that is ≤ the point
+            let pos = axis.partition_point(|&edge| edge <= p);
+
+            if pos == 0 || pos >= axis.len() {
+                return false;
+            }
+
+            index = index * (axis.len() - 1) + pos - 1;
+        }
+
+        self.cells[index]
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.boxes().iter().map(BoxRange::volume).sum()
+    }
+
+    pub fn to_box_set(&self) -> BoxSet<N> {
+        BoxSet {
+            boxes: self.boxes(),
+            temp: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> BoxRange<2> {
+        BoxRange {
+            coords: [
+                CoordRange { min: x, max: x + w },
+                CoordRange { min: y, max: y + h },
+            ],
+        }
+    }
+
+    #[test]
+    fn rectangles() {
+        let mut set = BoxSet::from_box(&rect(0, 0, 10, 10));
+
+        set.add(&rect(5, 5, 10, 10));
+        assert_eq!(set.volume(), 175);
+
+        set.subtract(&rect(2, 2, 2, 2));
+        assert_eq!(set.volume(), 171);
+        assert!(set.contains_point([1, 1]));
+        assert!(!set.contains_point([2, 3]));
+        assert!(set.contains_point([14, 14]));
+        assert!(!set.contains_point([15, 14]));
+        assert!(!set.contains_point([12, 2]));
+
+        // The boxes should be disjoint
+        let boxes = set.iter().collect::<Vec<_>>();
+
+        for (i, a) in boxes.iter().enumerate() {
+            for b in boxes[i + 1..].iter() {
+                assert!(!a.intersects(b));
+            }
+        }
+
+        let other = BoxSet::from_box(&rect(8, 0, 10, 3));
+        assert_eq!(set.intersection(&other).volume(), 6);
+
+        let mut union = set.clone();
+        union.union_with(&other);
+        assert_eq!(union.volume(), 171 + 30 - 6);
+
+        let mut difference = set.clone();
+        difference.subtract_set(&other);
+        assert_eq!(difference.volume(), 171 - 6);
+
+        set.intersect(&rect(0, 0, 5, 5));
+        assert_eq!(set.volume(), 21);
+    }
+
+    #[test]
+    fn four_dimensions() {
+        let cube = |min, max| BoxRange::<4> {
+            coords: [CoordRange { min, max }; 4],
+        };
+
+        let mut set = BoxSet::from_box(&cube(0, 3));
+        set.add(&cube(2, 4));
+        assert_eq!(set.volume(), 81 + 16 - 1);
+
+        set.subtract(&cube(1, 2));
+        assert_eq!(set.volume(), 81 + 16 - 1 - 1);
+        assert!(!set.contains_point([1, 1, 1, 1]));
+        assert!(set.contains_point([1, 1, 1, 2]));
+    }
+
+    #[test]
+    fn compressed() {
+        // Pseudo-random boxes with lots of overlaps
+        let mut seed = 12345u32;
+        let mut random = |n: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % n) as i32
+        };
+
+        let actions = (0..40).map(|_| {
+            let mut range = BoxRange::<3> {
+                coords: [CoordRange { min: 0, max: 0 }; 3],
+            };
+
+            for coord in range.coords.iter_mut() {
+                coord.min = random(30) - 15;
+                coord.max = coord.min + random(10) + 1;
+            }
+
+            (random(3) > 0, range)
+        }).collect::<Vec<_>>();
+
+        let mut set = BoxSet::new();
+        let mut compressed =
+            CompressedBoxSet::new(actions.iter().map(|(_, range)| range));
+
+        for (on, range) in actions.iter() {
+            if *on {
+                set.add(range);
+                compressed.add(range);
+            } else {
+                set.subtract(range);
+                compressed.subtract(range);
+            }
+        }
+
+        assert_eq!(compressed.volume(), set.volume());
+        assert_eq!(compressed.to_box_set().volume(), set.volume());
+        assert_eq!(
+            compressed.boxes().len(),
+            compressed.to_box_set().iter().count(),
+        );
+
+        // Set operations with a second set made from the other half of
+        // the boxes
+        let mut other_set = BoxSet::new();
+        let mut other_compressed =
+            CompressedBoxSet::new(actions.iter().map(|(_, range)| range));
+
+        for (_, range) in actions.iter().skip(actions.len() / 2) {
+            other_set.add(range);
+            other_compressed.add(range);
+        }
+
+        assert_eq!(
+            compressed.intersection(&other_compressed).volume(),
+            set.intersection(&other_set).volume(),
+        );
+
+        let mut union = set.clone();
+        union.union_with(&other_set);
+        let mut compressed_union = compressed.clone();
+        compressed_union.union_with(&other_compressed);
+        assert_eq!(compressed_union.volume(), union.volume());
+
+        let mut difference = set.clone();
+        difference.subtract_set(&other_set);
+        let mut compressed_difference = compressed.clone();
+        compressed_difference.subtract_set(&other_compressed);
+        assert_eq!(compressed_difference.volume(), difference.volume());
+
+        let mut clipped = set.clone();
+        clipped.intersect(&actions[0].1);
+        let mut compressed_clipped = compressed.clone();
+        compressed_clipped.intersect(&actions[0].1);
+        assert_eq!(compressed_clipped.volume(), clipped.volume());
+        assert!(compressed_clipped.boxes().iter().all(|part| {
+            actions[0].1.contains(part)
+        }));
+
+        for x in -16..25 {
+            for y in -16..25 {
+                let point = [x, y, x - y];
+                assert_eq!(
+                    compressed.contains_point(point),
+                    set.contains_point(point),
+                );
+                assert_eq!(
+                    compressed_union.contains_point(point),
+                    union.contains_point(point),
+                );
+                assert_eq!(
+                    compressed_clipped.contains_point(point),
+                    clipped.contains_point(point),
+                );
+            }
+        }
+    }
+}
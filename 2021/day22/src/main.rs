@@ -1,54 +1,14 @@
+// The sets have more operations than this day needs
+#[allow(dead_code)]
+mod boxset;
+
 use regex::Regex;
 use lazy_static::lazy_static;
 use std::str::FromStr;
 use std::process::ExitCode;
+use boxset::{BoxRange, BoxSet, CompressedBoxSet, CoordRange};
 
-#[derive(Clone, Debug)]
-struct CoordRange {
-    min: i32,
-    max: i32,
-}
-
-#[derive(Clone, Debug)]
-struct CubeRange {
-    coords: [CoordRange; 3]
-}
-
-impl CubeRange {
-    fn intersects(&self, other: &CubeRange) -> bool {
-        for i in 0..self.coords.len() {
-            if other.coords[i].max <= self.coords[i].min
-                || other.coords[i].min >= self.coords[i].max
-            {
-                return false;
-            }
-        }
-
-        true
-    }
-
-    fn contains(&self, other: &CubeRange) -> bool {
-        for i in 0..self.coords.len() {
-            if self.coords[i].min > other.coords[i].min
-                || self.coords[i].max < other.coords[i].max
-            {
-                return false;
-            }
-        }
-
-        true
-    }
-
-    fn count_cubes(&self) -> u64 {
-        let mut n_cubes = 1;
-
-        for range in self.coords.iter() {
-            n_cubes *= (range.max - range.min) as u64;
-        }
-
-        n_cubes
-    }
-}
+type CubeRange = BoxRange<3>;
 
 impl FromStr for CubeRange {
     type Err = String;
@@ -94,101 +54,6 @@ impl FromStr for CubeRange {
     }
 }
 
-struct CubeList {
-    ranges: Vec<CubeRange>,
-    temp: Vec<CubeRange>,
-}
-
-impl CubeList {
-    fn new() -> CubeList {
-        CubeList {
-            ranges: Vec::new(),
-            temp: Vec::new(),
-        }
-    }
-
-    fn from_cube_range(range: &CubeRange) -> CubeList {
-        CubeList {
-            ranges: vec![range.clone()],
-            temp: Vec::new(),
-        }
-    }
-
-    fn subtract(&mut self, range: &CubeRange) {
-        self.temp.truncate(0);
-
-        for part in self.ranges.iter() {
-            // If the range is outside the part then just leave it as is
-            if !part.intersects(range) {
-                self.temp.push(part.clone());
-                continue;
-            }
-
-            // If the range covers the entire part then skip it
-            if range.contains(part) {
-                continue;
-            }
-
-            let mut part = part.clone();
-
-            for i in 0..part.coords.len() {
-                if range.coords[i].min > part.coords[i].min {
-                    let mut to_add = part.clone();
-                    to_add.coords[i].max = range.coords[i].min;
-                    self.temp.push(to_add);
-
-                    part.coords[i].min = range.coords[i].min;
-
-                    if part.coords[i].min >= part.coords[i].max {
-                        break;
-                    }
-                }
-
-                if range.coords[i].max < part.coords[i].max {
-                    let mut to_add = part.clone();
-                    to_add.coords[i].min = range.coords[i].max;
-                    self.temp.push(to_add);
-
-                    part.coords[i].max = range.coords[i].max;
-
-                    if part.coords[i].min >= part.coords[i].max {
-                        break;
-                    }
-                }
-            }
-        }
-
-        std::mem::swap(&mut self.ranges, &mut self.temp);
-    }
-
-    fn add(&mut self, range: &CubeRange) {
-        // Cut the range up so that only ranges that don’t already
-        // intersect with self are in it
-        let mut range = CubeList::from_cube_range(range);
-
-        for part in self.ranges.iter() {
-            range.subtract(part);
-        }
-
-        // Anything left in the range can just be added directly to
-        // the list of ranges
-        self.ranges.extend(range.ranges);
-    }
-
-    fn count_cubes(&self) -> u64 {
-        self.ranges.iter().map(|range| range.count_cubes()).sum()
-    }
-}
-
-impl Clone for CubeList {
-    fn clone(&self) -> CubeList {
-        CubeList {
-            ranges: self.ranges.clone(),
-            temp: Vec::new(),
-        }
-    }
-}
-
 enum ActionType {
     On,
     Off,
@@ -218,8 +83,44 @@ impl FromStr for Action {
     }
 }
 
+fn part1(actions: &[Action]) -> u64 {
+    // Only the cubes in the initialization area are needed so there
+    // are few enough edges to compress the coordinates
+    let area = BoxRange {
+        coords: [CoordRange { min: -50, max: 51 }; 3],
+    };
+
+    let actions = actions.iter().filter_map(|action| {
+        area.intersection(&action.range).map(|range| (&action.action_type, range))
+    }).collect::<Vec<_>>();
+
+    let mut cubes = CompressedBoxSet::new(actions.iter().map(|(_, range)| range));
+
+    for (action_type, range) in actions.iter() {
+        match action_type {
+            ActionType::On => cubes.add(range),
+            ActionType::Off => cubes.subtract(range),
+        }
+    }
+
+    cubes.volume()
+}
+
+fn part2(actions: &[Action]) -> u64 {
+    let mut cubes = BoxSet::new();
+
+    for action in actions.iter() {
+        match action.action_type {
+            ActionType::On => cubes.add(&action.range),
+            ActionType::Off => cubes.subtract(&action.range),
+        }
+    }
+
+    cubes.volume()
+}
+
 fn main() -> ExitCode {
-    let mut cube_list = CubeList::new();
+    let mut actions = Vec::new();
 
     for (line_num, line) in std::io::stdin().lines().enumerate() {
         let line = match line {
@@ -230,42 +131,17 @@ fn main() -> ExitCode {
             },
         };
 
-        let action = match line.parse::<Action>() {
-            Ok(action) => action,
+        match line.parse::<Action>() {
+            Ok(action) => actions.push(action),
             Err(e) => {
                 eprintln!("line {}: {}", line_num + 1, e);
                 return ExitCode::FAILURE;
             }
         };
-
-        match action.action_type {
-            ActionType::On => cube_list.add(&action.range),
-            ActionType::Off => cube_list.subtract(&action.range),
-        }
-    }
-
-    let mut small_region = cube_list.clone();
-
-    for i in 0..3 {
-        let all_region = CubeRange {
-            coords: [
-                CoordRange { min: i32::MIN, max: i32::MAX },
-                CoordRange { min: i32::MIN, max: i32::MAX },
-                CoordRange { min: i32::MIN, max: i32::MAX },
-            ],
-        };
-
-        let mut min_region = all_region.clone();
-        min_region.coords[i].max = -50;
-        small_region.subtract(&min_region);
-
-        let mut max_region = all_region.clone();
-        max_region.coords[i].min = 51;
-        small_region.subtract(&max_region);
     }
 
-    println!("part 1: {}", small_region.count_cubes());
-    println!("part 2: {}", cube_list.count_cubes());
+    println!("part 1: {}", part1(&actions));
+    println!("part 2: {}", part2(&actions));
 
     ExitCode::SUCCESS
 }
@@ -364,14 +240,12 @@ mod test {
         ];
 
         for &(a, b, result) in tests.iter() {
-            let mut a = CubeList::from_cube_range(
-                &a.parse::<CubeRange>().unwrap()
-            );
+            let mut a = BoxSet::from_box(&a.parse::<CubeRange>().unwrap());
             let b = b.parse::<CubeRange>().unwrap();
 
             a.subtract(&b);
 
-            assert_eq!(a.count_cubes(), result);
+            assert_eq!(a.volume(), result);
         }
     }
 
@@ -396,14 +270,12 @@ mod test {
         ];
 
         for &(a, b, result) in tests.iter() {
-            let mut a = CubeList::from_cube_range(
-                &a.parse::<CubeRange>().unwrap()
-            );
+            let mut a = BoxSet::from_box(&a.parse::<CubeRange>().unwrap());
             let b = b.parse::<CubeRange>().unwrap();
 
             a.add(&b);
 
-            assert_eq!(a.count_cubes(), result);
+            assert_eq!(a.volume(), result);
         }
     }
 }
@@ -1,9 +1,10 @@
+mod astar;
+
+use std::fmt;
 use std::str::FromStr;
 use std::process::ExitCode;
-use std::cmp::{min, max, Ordering};
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
-use std::collections::BinaryHeap;
+use std::cmp::{min, max};
+use astar::SearchState;
 
 const N_AMPHIPOD_TYPES: usize = 4;
 #[cfg(not(feature = "part2"))]
@@ -12,6 +13,7 @@ const N_AMPHIPODS_PER_TYPE: usize = 2;
 const N_AMPHIPODS_PER_TYPE: usize = 4;
 const TOTAL_N_AMPHIPODS: usize = N_AMPHIPOD_TYPES * N_AMPHIPODS_PER_TYPE;
 const N_SIDE_ROOMS: usize = 1;
+const HALLWAY_WIDTH: usize = N_SIDE_ROOMS * 2 + 1 + N_AMPHIPOD_TYPES * 2;
 
 const MOVES_PER_AMPHIPOD: usize =
 // Move into a room
@@ -101,6 +103,10 @@ impl Position {
     }
 }
 
+fn energy_per_step(amphipod_type: usize) -> u64 {
+    10u64.pow(amphipod_type as u32)
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct State {
     amphipods: [Position; TOTAL_N_AMPHIPODS],
@@ -190,15 +196,9 @@ impl State {
             return None
         }
 
-        let mut move_cost = 1;
-
-        for _ in 0..amphipod_type {
-            move_cost *= 10;
-        }
-
         Some((
             pos,
-            move_cost
+            energy_per_step(amphipod_type)
                 * (self.amphipods[amphipod_num].x().abs_diff(pos.x())
                    + self.amphipods[amphipod_num].y().abs_diff(pos.y())) as u64
         ))
@@ -217,27 +217,22 @@ impl State {
         true
     }
 
-    fn score(&self) -> i32 {
-        self.amphipods
-            .iter()
-            .enumerate()
-            .map(|(num, a)| {
-                 let amphipod_type = num / N_AMPHIPODS_PER_TYPE;
-
-                 match a {
-                     &Position::InRoom { room_num, .. } => {
-                         if room_num as usize == amphipod_type {
-                             10
-                         } else {
-                             -15
-                         }
-                     },
-                     Position::OutsideRoom(_) => -1,
-                     Position::LeftSideRoom(_) |
-                     Position::RightSideRoom(_) => -2,
-                 }
-            })
-            .sum()
+    // Returns true if the amphipod is in its own room but another type
+    // of amphipod is below it so it will have to move out of the way
+    fn is_blocking(&self, amphipod_num: usize) -> bool {
+        let &Position::InRoom { room_num, room_pos } =
+            &self.amphipods[amphipod_num]
+        else { return false; };
+
+        self.amphipods.iter().enumerate().any(|(num, amphipod)| {
+            matches!(
+                amphipod,
+                &Position::InRoom { room_num: other_room, room_pos: other_pos }
+                    if other_room == room_num
+                    && other_pos > room_pos
+                    && num / N_AMPHIPODS_PER_TYPE != room_num as usize
+            )
+        })
     }
 
     fn normalise_amphipod_type(&mut self, amphipod_type: usize) {
@@ -254,6 +249,102 @@ impl State {
     }
 }
 
+impl SearchState for State {
+    fn successors(&self) -> impl Iterator<Item = (State, u64)> {
+        (0..N_MOVES).filter_map(|move_num| {
+            let amphipod_num = move_num / MOVES_PER_AMPHIPOD;
+            let pos = Position::from_move_num(move_num % MOVES_PER_AMPHIPOD);
+            let (pos, cost) = self.try_move(amphipod_num, &pos)?;
+            let mut state = self.clone();
+
+            state.amphipods[amphipod_num] = pos;
+            state.normalise_amphipod_type(
+                amphipod_num / N_AMPHIPODS_PER_TYPE
+            );
+
+            Some((state, cost))
+        })
+    }
+
+    // The energy needed to move every amphipod that isn’t home to the
+    // top of its room, ignoring any amphipods in the way
+    fn heuristic(&self) -> u64 {
+        self.amphipods.iter().enumerate().map(|(num, amphipod)| {
+            let amphipod_type = num / N_AMPHIPODS_PER_TYPE;
+            let home = Position::InRoom {
+                room_num: amphipod_type as u8,
+                room_pos: 0,
+            };
+
+            let steps = match amphipod {
+                &Position::InRoom { room_num, .. }
+                if room_num as usize == amphipod_type => {
+                    if !self.is_blocking(num) {
+                        return 0;
+                    }
+
+                    // Out to the hallway, one step to the side and
+                    // back, and then into the room
+                    amphipod.y() + 3
+                },
+                _ => amphipod.x().abs_diff(home.x()) + amphipod.y() + 1,
+            };
+
+            steps as u64 * energy_per_step(amphipod_type)
+        }).sum()
+    }
+
+    fn is_goal(&self) -> bool {
+        self.is_solved()
+    }
+}
+
+// Draws the burrow in the same format as the puzzle input
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = HALLWAY_WIDTH + 2;
+        let height = N_AMPHIPODS_PER_TYPE + 3;
+        let mut cells = vec![b'#'; width * height];
+
+        // Hallway
+        cells[width + 1..width * 2 - 1].fill(b'.');
+
+        for y in 2..height {
+            for x in 0..width {
+                let in_rooms = (N_SIDE_ROOMS + 1..width - N_SIDE_ROOMS - 1)
+                    .contains(&x);
+
+                if y > 2 && !in_rooms {
+                    cells[y * width + x] = b' ';
+                } else if y < height - 1 && in_rooms && x & 1 == 1 {
+                    cells[y * width + x] = b'.';
+                }
+            }
+        }
+
+        for (num, amphipod) in self.amphipods.iter().enumerate() {
+            let x = amphipod.x() as usize + 1;
+            let y = amphipod.y() as usize + 1;
+
+            cells[y * width + x] = b'A' + (num / N_AMPHIPODS_PER_TYPE) as u8;
+        }
+
+        for (y, row) in cells.chunks(width).enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+
+            write!(
+                f,
+                "{}",
+                std::str::from_utf8(row).unwrap().trim_end(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 impl FromStr for State {
     type Err = String;
 
@@ -337,33 +428,6 @@ impl FromStr for State {
     }
 }
 
-#[derive(Clone, Eq)]
-struct HeapEntry {
-    state: State,
-    cost: u64,
-    score: i32,
-}
-
-impl Ord for HeapEntry {
-    fn cmp(&self, other: &HeapEntry) -> Ordering {
-        self.score.cmp(&other.score)
-            // Order swapped to minimise cost
-            .then_with(|| other.cost.cmp(&self.cost))
-    }
-}
-
-impl PartialOrd for HeapEntry {
-    fn partial_cmp(&self, other: &HeapEntry) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl PartialEq for HeapEntry {
-    fn eq(&self, other: &HeapEntry) -> bool {
-        self.cmp(other).is_eq()
-    }
-}
-
 fn read_state() -> Result<State, String> {
     let mut lines = Vec::new();
 
@@ -384,71 +448,6 @@ fn read_state() -> Result<State, String> {
     lines.join("\n").parse::<State>()
 }
 
-fn solve(original_state: &State) -> u64 {
-    let mut best_solution = u64::MAX;
-    let mut visited_states = HashMap::<State, u64>::new();
-
-    let mut heap = BinaryHeap::new();
-
-    heap.push(HeapEntry {
-        state: original_state.clone(),
-        cost: 0,
-        score: original_state.score(),
-    });
-
-    while let Some(entry) = heap.pop() {
-        if entry.cost >= best_solution {
-            continue;
-        }
-
-        match visited_states.entry(entry.state.clone()) {
-            Entry::Occupied(mut e) => {
-                if *e.get() <= entry.cost {
-                    continue;
-                }
-
-                e.insert(entry.cost);
-            },
-
-            Entry::Vacant(e) => {
-                e.insert(entry.cost);
-            },
-        }
-
-        if entry.state.is_solved() {
-            best_solution = entry.cost;
-            println!("{}", entry.cost);
-            continue;
-        }
-
-        for move_num in 0..N_MOVES {
-            let amphipod_num = move_num / MOVES_PER_AMPHIPOD;
-            let pos = Position::from_move_num(move_num % MOVES_PER_AMPHIPOD);
-
-            if let Some((pos, cost)) =
-                entry.state.try_move(amphipod_num, &pos)
-            {
-                let mut state = entry.state.clone();
-
-                state.amphipods[amphipod_num] = pos;
-                state.normalise_amphipod_type(
-                    amphipod_num / N_AMPHIPODS_PER_TYPE
-                );
-
-                let score = state.score();
-
-                heap.push(HeapEntry {
-                    state,
-                    cost: entry.cost + cost,
-                    score,
-                });
-            }
-        }
-    }
-
-    best_solution
-}
-
 fn main() -> ExitCode {
     let state = match read_state() {
         Err(e) => {
@@ -458,7 +457,28 @@ fn main() -> ExitCode {
         Ok(s) => s,
     };
 
-    println!("{}", solve(&state));
+    let Some(path) = astar::astar(state)
+    else {
+        eprintln!("no solution found");
+        return ExitCode::FAILURE;
+    };
+
+    for (move_num, pair) in path.steps.windows(2).enumerate() {
+        let (state, energy) = &pair[1];
+
+        println!();
+        println!(
+            "Move {}: {} energy (total {})",
+            move_num + 1,
+            energy - pair[0].1,
+            energy,
+        );
+        println!("{}", state);
+    }
+
+    println!();
+    println!("States expanded: {}", path.n_expanded);
+    println!("{}", path.cost);
 
     ExitCode::SUCCESS
 }
@@ -457,6 +457,22 @@ fn run_args(mod_adds: &[i64], offsets: &[i64]) {
     }
 }
 
+fn check_monad(program: &[Op], monad: &Monad) -> Result<bool, SearchError> {
+    let mut machine = Machine::new(&monad.digits);
+
+    for (op_num, op) in program.iter().enumerate() {
+        if let Err(machine_error) = machine.execute_op(op) {
+            return Err(SearchError {
+                line_num: op_num + 1,
+                monad: monad.clone(),
+                machine_error,
+            });
+        }
+    }
+
+    Ok(*machine.registers.last().unwrap() == 0)
+}
+
 fn search_interpret(
     program: &[Op],
     direction: SearchDirection
@@ -464,19 +480,7 @@ fn search_interpret(
     let mut monad = Monad::first(direction);
 
     loop {
-        let mut machine = Machine::new(&monad.digits);
-
-        for (op_num, op) in program.iter().enumerate() {
-            if let Err(machine_error) = machine.execute_op(op) {
-                return Err(SearchError {
-                    line_num: op_num + 1,
-                    monad,
-                    machine_error,
-                });
-            }
-        }
-
-        if *machine.registers.last().unwrap() == 0 {
+        if check_monad(program, &monad)? {
             break Ok(Some(monad));
         }
 
@@ -551,6 +555,155 @@ fn search_template(
     }
 }
 
+#[derive(Clone, Copy)]
+struct Constraint {
+    // The digit whose value is fixed by the other digit
+    digit: usize,
+    other: usize,
+    difference: i64,
+}
+
+impl Constraint {
+    // Returns the values of (digit, other) that maximise or minimise
+    // the model number.
+    fn solve(&self, direction: SearchDirection) -> (u8, u8) {
+        let other = match direction {
+            SearchDirection::Decreasing => {
+                std::cmp::min(9, 9 - self.difference)
+            },
+            SearchDirection::Increasing => {
+                std::cmp::max(1, 1 - self.difference)
+            },
+        };
+
+        ((other + self.difference) as u8, other as u8)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "d[{}] = d[{}]", self.digit, self.other)?;
+
+        if self.difference < 0 {
+            write!(f, " - {}", -self.difference)?;
+        } else if self.difference > 0 {
+            write!(f, " + {}", self.difference)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn derive_constraints(
+    mod_adds: &[i64],
+    offsets: &[i64],
+) -> Result<Vec<Constraint>, String> {
+    let mut stack = Vec::new();
+    let mut constraints = Vec::new();
+
+    for (digit, (&mod_add, &offset)) in mod_adds.iter()
+        .zip(offsets.iter())
+        .enumerate()
+    {
+        if mod_add >= 0 {
+            // The constraints only work if the digit can never match
+            // so that it is always pushed onto the stack
+            if mod_add <= 9 {
+                return Err(format!(
+                    "digit {}: positive mod_add {} might match the input",
+                    digit,
+                    mod_add,
+                ));
+            }
+
+            stack.push((digit, offset));
+        } else {
+            let Some((other, other_offset)) = stack.pop()
+            else {
+                return Err(format!("digit {}: nothing to pop", digit));
+            };
+
+            let constraint = Constraint {
+                digit,
+                other,
+                difference: other_offset + mod_add,
+            };
+
+            if constraint.difference.abs() >= 9 {
+                return Err(format!("no digits satisfy {}", constraint));
+            }
+
+            constraints.push(constraint);
+        }
+    }
+
+    if let Some((digit, _)) = stack.first() {
+        return Err(format!("digit {} is never popped", digit));
+    }
+
+    Ok(constraints)
+}
+
+fn solve_constraints(
+    constraints: &[Constraint],
+    direction: SearchDirection,
+) -> Monad {
+    let mut monad = Monad::first(direction);
+
+    for constraint in constraints.iter() {
+        let (digit, other) = constraint.solve(direction);
+
+        monad.digits[constraint.digit] = digit;
+        monad.digits[constraint.other] = other;
+    }
+
+    monad
+}
+
+fn explain_constraints(program: &[Op]) -> Result<(), String> {
+    let Some((mod_adds, offsets)) = match_template(program)
+    else {
+        return Err("program doesn't match the MONAD template".to_string());
+    };
+
+    let constraints = derive_constraints(&mod_adds, &offsets)?;
+
+    println!("constraints:");
+
+    for constraint in constraints.iter() {
+        let (high_digit, high_other) =
+            constraint.solve(SearchDirection::Decreasing);
+        let (low_digit, low_other) =
+            constraint.solve(SearchDirection::Increasing);
+
+        println!(
+            "  {:<18} d[{}] in {}..={}, d[{}] in {}..={}",
+            constraint.to_string(),
+            constraint.other,
+            low_other,
+            high_other,
+            constraint.digit,
+            low_digit,
+            high_digit,
+        );
+    }
+
+    for (part, direction) in [
+        (1, SearchDirection::Decreasing),
+        (2, SearchDirection::Increasing),
+    ] {
+        let monad = solve_constraints(&constraints, direction);
+
+        if !check_monad(program, &monad).map_err(|e| e.to_string())? {
+            return Err(format!("{} doesn't satisfy the program", monad));
+        }
+
+        println!("part {}: {}", part, monad);
+    }
+
+    Ok(())
+}
+
 fn search(
     program: &[Op],
     direction: SearchDirection
@@ -718,6 +871,16 @@ fn main() -> ExitCode {
         });
     }
 
+    if std::env::args().nth(1).is_some_and(|arg| arg == "--constraints") {
+        return match explain_constraints(&program) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            },
+        };
+    }
+
     match source_for_register(&program, N_REGISTERS as u8 - 1) {
         Source::Constant(value) => println!("z is constant: {}", value),
         Source::Inputs(inputs, min, max) => {
@@ -749,3 +912,84 @@ fn main() -> ExitCode {
 
     ExitCode::SUCCESS
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A hand-made template where each pair of digits is linked by a
+    // push and a pop:
+    //   d[2] = d[1], d[4] = d[3] - 2, d[5] = d[0] - 8,
+    //   d[9] = d[8] + 4, d[10] = d[7] - 5, d[11] = d[6] + 6,
+    //   d[13] = d[12]
+    static MOD_ADDS: [i64; MONAD_LENGTH] = [
+        11, 12, -6, 10, -3, -10, 13, 15, 11, -1, -5, -2, 12, -3,
+    ];
+    static OFFSETS: [i64; MONAD_LENGTH] = [
+        2, 6, 0, 1, 0, 0, 8, 0, 5, 0, 0, 0, 3, 0,
+    ];
+
+    #[test]
+    fn constraints() {
+        let constraints = derive_constraints(&MOD_ADDS, &OFFSETS).unwrap();
+
+        assert_eq!(
+            constraints.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            [
+                "d[2] = d[1]",
+                "d[4] = d[3] - 2",
+                "d[5] = d[0] - 8",
+                "d[9] = d[8] + 4",
+                "d[10] = d[7] - 5",
+                "d[11] = d[6] + 6",
+                "d[13] = d[12]",
+            ],
+        );
+
+        for (direction, expected) in [
+            (SearchDirection::Decreasing, "99997139594999"),
+            (SearchDirection::Increasing, "91131116151711"),
+        ] {
+            let monad = solve_constraints(&constraints, direction);
+
+            assert_eq!(monad.to_string(), expected);
+            assert_eq!(
+                search_template(&MOD_ADDS, &OFFSETS, direction)
+                    .map(|monad| monad.to_string()),
+                Some(expected.to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn bad_templates() {
+        // The last pop is turned into a push
+        let mut mod_adds = MOD_ADDS;
+        mod_adds[13] = 14;
+        assert_eq!(
+            derive_constraints(&mod_adds, &OFFSETS).map(|_| ()),
+            Err("digit 12 is never popped".to_string()),
+        );
+
+        // Pop before anything is pushed
+        let mut mod_adds = MOD_ADDS;
+        mod_adds[0] = -1;
+        assert_eq!(
+            derive_constraints(&mod_adds, &OFFSETS).map(|_| ()),
+            Err("digit 0: nothing to pop".to_string()),
+        );
+
+        // A push that could match the input
+        let mut mod_adds = MOD_ADDS;
+        mod_adds[0] = 5;
+        assert!(derive_constraints(&mod_adds, &OFFSETS).is_err());
+
+        // The digits would have to be 9 apart
+        let mut offsets = OFFSETS;
+        offsets[0] = 1;
+        assert_eq!(
+            derive_constraints(&MOD_ADDS, &offsets).map(|_| ()),
+            Err("no digits satisfy d[5] = d[0] - 9".to_string()),
+        );
+    }
+}
@@ -10,7 +10,7 @@ struct List {
     entries: Vec<ListEntry>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 enum ListEntry {
     Integer(i32),
     List(List),
@@ -47,160 +47,244 @@ impl std::fmt::Display for List {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ParseState {
-    AwaitingFirstItem,
-    AwaitingComma,
-    AwaitingNextItem,
-    InInteger,
+    ListStart,
+    AfterItem,
+    AfterComma,
 }
 
 use ParseState::*;
 
-fn add_digit(before: i32, digit: char) -> Result<i32, String> {
-    let digit = digit.to_digit(10).unwrap() as i32;
+// Parses a stream of lists separated by any amount of whitespace. The
+// input is only read one line at a time as the lists are needed.
+struct ListReader<R> {
+    reader: R,
+    line: Vec<char>,
+    line_num: usize,
+    pos: usize,
+    at_end: bool,
+}
 
-    match before.checked_mul(10).and_then(|n| n.checked_add(digit)) {
-        Some(n) => Ok(n),
-        None => Err("invalid number".to_string()),
+impl<R: std::io::BufRead> ListReader<R> {
+    fn new(reader: R) -> ListReader<R> {
+        ListReader {
+            reader,
+            line: Vec::new(),
+            line_num: 0,
+            pos: 0,
+            at_end: false,
+        }
     }
-}
 
-impl std::str::FromStr for List {
-    type Err = String;
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line_num,
+            column: self.pos + 1,
+            message,
+        })
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut stack = Vec::<List>::new();
-        let mut state = AwaitingFirstItem;
-
-        for (ch_pos, ch) in s.char_indices() {
-            match ch {
-                '[' => match state {
-                    AwaitingFirstItem | AwaitingNextItem => {
-                        stack.push(List::new());
-                        state = AwaitingFirstItem;
-                    },
-                    AwaitingComma | InInteger => {
-                        return Err("unexpected ‘[’".to_string());
-                    },
+    // Returns the next character without consuming it, or None at the
+    // end of the input. The end of each line is returned as ‘\n’.
+    fn peek(&mut self) -> Result<Option<char>, ParseError> {
+        while self.pos >= self.line.len() {
+            if self.at_end {
+                return Ok(None);
+            }
+
+            let mut line = String::new();
+
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.at_end = true;
+                    return Ok(None);
                 },
-                ']' => {
-                    if state == AwaitingNextItem {
-                        return Err("expected ‘[’ or digit".to_string());
-                    }
-                    match stack.pop() {
-                        None => return Err("unmatched ‘]’".to_string()),
-                        Some(list) => {
-                            if stack.len() == 0 {
-                                if ch_pos + ch.len_utf8() < s.len() {
-                                    return Err("extra data at end of string"
-                                               .to_string());
-                                }
-                                return Ok(list);
-                            } else {
-                                let entry = ListEntry::List(list);
-                                stack.last_mut().unwrap().entries.push(entry);
-                            }
+                Ok(_) => (),
+                Err(e) => {
+                    self.at_end = true;
+                    return self.error(e.to_string());
+                },
+            }
+
+            self.line.clear();
+            self.line.extend(line.chars());
+            self.line_num += 1;
+            self.pos = 0;
+        }
+
+        Ok(Some(self.line[self.pos]))
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<char>, ParseError> {
+        loop {
+            match self.peek()? {
+                Some(ch) if ch.is_whitespace() => self.pos += 1,
+                ch => return Ok(ch),
+            }
+        }
+    }
+
+    fn parse_integer(&mut self) -> Result<i32, ParseError> {
+        let start = self.pos;
+        let mut end = start;
+
+        if self.line[end] == '-' {
+            end += 1;
+        }
+
+        while self.line.get(end).is_some_and(|ch| ch.is_ascii_digit()) {
+            end += 1;
+        }
+
+        let text = self.line[start..end].iter().collect::<String>();
+
+        match text.parse::<i32>() {
+            Ok(n) => {
+                self.pos = end;
+                Ok(n)
+            },
+            Err(_) => self.error(format!("invalid number ‘{}’", text)),
+        }
+    }
+
+    // Parses the next list or returns None if there is only whitespace
+    // left in the input
+    fn next_list(&mut self) -> Result<Option<List>, ParseError> {
+        match self.skip_whitespace()? {
+            None => return Ok(None),
+            Some('[') => self.pos += 1,
+            Some(ch) => return self.error(format!("expected ‘[’, got ‘{}’", ch)),
+        }
+
+        let mut stack = vec![List::new()];
+        let mut state = ListStart;
+
+        loop {
+            let Some(ch) = self.skip_whitespace()?
+            else { return self.error("list not terminated".to_string()); };
+
+            match (ch, state) {
+                ('[', ListStart | AfterComma) => {
+                    self.pos += 1;
+                    stack.push(List::new());
+                    state = ListStart;
+                },
+                (']', ListStart | AfterItem) => {
+                    self.pos += 1;
+
+                    let list = stack.pop().unwrap();
+
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            parent.entries.push(ListEntry::List(list));
                         },
+                        None => return Ok(Some(list)),
                     }
-                    state = AwaitingComma;
+
+                    state = AfterItem;
                 },
-                ',' => match state {
-                    AwaitingComma | InInteger => {
-                        state = AwaitingNextItem;
-                    },
-                    AwaitingFirstItem | AwaitingNextItem => {
-                        return Err("unexpected ‘,’".to_string());
-                    },
+                (',', AfterItem) => {
+                    self.pos += 1;
+                    state = AfterComma;
                 },
-                ' ' => match state {
-                    AwaitingFirstItem |
-                    AwaitingComma |
-                    AwaitingNextItem => (),
-                    InInteger => {
-                        state = AwaitingComma;
-                    },
+                ('-' | '0'..='9', ListStart | AfterComma) => {
+                    let n = self.parse_integer()?;
+                    let entry = ListEntry::Integer(n);
+                    stack.last_mut().unwrap().entries.push(entry);
+                    state = AfterItem;
                 },
-                digit @ '0'..='9' => match state {
-                    AwaitingFirstItem |
-                    AwaitingNextItem => {
-                        if stack.len() == 0 {
-                            return Err("integer outside of list".to_string());
-                        }
-                        let digit = digit.to_digit(10).unwrap();
-                        let entry = ListEntry::Integer(digit as i32);
-                        stack.last_mut().unwrap().entries.push(entry);
-                        state = InInteger;
-                    },
-                    AwaitingComma => {
-                        return Err("unexpected digit".to_string());
-                    },
-                    InInteger => {
-                        let entry = &mut stack
-                            .last_mut().unwrap()
-                            .entries
-                            .last_mut().unwrap();
-                        if let ListEntry::Integer(ref mut n) = entry {
-                            *n = add_digit(*n, digit)?;
-                        } else {
-                            panic!("should be parsing an integer");
-                        }
-                    }
-                }
-                _ => return Err(format!("unexpected char ‘{}’", ch)),
+                (']', AfterComma) => {
+                    return self.error("expected ‘[’ or number".to_string());
+                },
+                _ => return self.error(format!("unexpected ‘{}’", ch)),
             }
         }
+    }
+}
+
+impl<R: std::io::BufRead> Iterator for ListReader<R> {
+    type Item = Result<List, ParseError>;
 
-        Err("list not terminated".to_string())
+    fn next(&mut self) -> Option<Result<List, ParseError>> {
+        self.next_list().transpose()
     }
 }
 
-fn compare_entry(a: &ListEntry, b: &ListEntry) -> Ordering {
-    match a {
-        ListEntry::Integer(int_a) => {
-            match b {
-                ListEntry::Integer(int_b) => int_a.cmp(int_b),
-                ListEntry::List(list_b) => {
-                    let array_a = [ListEntry::Integer(*int_a)];
-                    compare_list_slice(&array_a, &list_b.entries)
-                },
-            }
-        },
-        ListEntry::List(list_a) => {
-            match b {
-                ListEntry::Integer(int_b) => {
-                    let array_b = [ListEntry::Integer(*int_b)];
-                    compare_list_slice(&list_a.entries, &array_b)
-                },
-                ListEntry::List(list_b) =>
-                    compare_list_slice(&list_a.entries, &list_b.entries),
-            }
-        },
+impl std::str::FromStr for List {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut reader = ListReader::new(s.as_bytes());
+
+        let Some(list) = reader.next_list()?
+        else { return reader.error("empty string".to_string()); };
+
+        if reader.skip_whitespace()?.is_some() {
+            return reader.error("extra data at end of string".to_string());
+        }
+
+        Ok(list)
     }
 }
 
-fn compare_list_slice(a: &[ListEntry], b: &[ListEntry]) -> Ordering {
-    let mut a = a.into_iter();
-    let mut b = b.into_iter();
-
-    loop {
-        match a.next() {
-            Some(entry_a) => {
-                match b.next() {
-                    None => return Ordering::Greater,
-                    Some(entry_b) => match compare_entry(entry_a, entry_b) {
-                        Ordering::Equal => (),
-                        r @ (Ordering::Greater | Ordering::Less) => return r,
-                    }
-                }
+impl Ord for ListEntry {
+    fn cmp(&self, other: &ListEntry) -> Ordering {
+        match (self, other) {
+            (ListEntry::Integer(a), ListEntry::Integer(b)) => a.cmp(b),
+            // An integer compared with a list acts like a list
+            // containing only that integer
+            (ListEntry::Integer(_), ListEntry::List(b)) => {
+                std::slice::from_ref(self).cmp(&b.entries)
             },
-            None => match b.next() {
-                Some(_) => return Ordering::Less,
-                None => return Ordering::Equal,
+            (ListEntry::List(a), ListEntry::Integer(_)) => {
+                a.entries.as_slice().cmp(std::slice::from_ref(other))
             },
+            (ListEntry::List(a), ListEntry::List(b)) => a.cmp(b),
         }
     }
 }
 
+impl PartialOrd for ListEntry {
+    fn partial_cmp(&self, other: &ListEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Equality has to agree with the ordering, so for example 1 == [1]
+impl PartialEq for ListEntry {
+    fn eq(&self, other: &ListEntry) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ListEntry {
+}
+
+impl Ord for List {
+    fn cmp(&self, other: &List) -> Ordering {
+        self.entries.cmp(&other.entries)
+    }
+}
+
+impl PartialOrd for List {
+    fn partial_cmp(&self, other: &List) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 fn make_lists_with_dividers(pairs: &[(List, List)]) -> Vec<List> {
     let mut lists: Vec<List> =
         MARKERS.iter().map(|marker| marker.parse::<List>().unwrap()).collect();
@@ -210,50 +294,27 @@ fn make_lists_with_dividers(pairs: &[(List, List)]) -> Vec<List> {
         lists.push(b.clone());
     }
 
-    lists.sort_by(|a, b| compare_list_slice(&a.entries, &b.entries));
+    lists.sort();
 
     lists
 }
 
-fn read_lists<I>(lines: &mut I) -> Result<Vec<(List, List)>, String>
-    where I: Iterator<Item = Result<String, std::io::Error>>
+fn read_lists<R>(reader: R) -> Result<Vec<(List, List)>, String>
+    where R: std::io::BufRead
 {
     let mut lists = Vec::<(List, List)>::new();
     let mut list_a = Option::<List>::None;
-    let mut need_blank = false;
-
-    for (line_num, result) in lines.enumerate() {
-        let line = match result {
-            Err(e) => return Err(e.to_string()),
-            Ok(line) => line,
-        };
-
-        if line.len() == 0 {
-            if let Some(_) = list_a {
-                return Err(format!("line {}: unpaired list", line_num + 1));
-            }
-            need_blank = false;
-            continue;
-        } else if need_blank {
-            return Err(format!("line {}: expected blank line", line_num + 1));
-        }
 
-        let list = match line.parse::<List>() {
-            Ok(list) => list,
-            Err(e) => return Err(format!("line {}: {}", line_num + 1, e)),
-        };
+    for result in ListReader::new(reader) {
+        let list = result.map_err(|e| e.to_string())?;
 
-        match list_a {
+        match list_a.take() {
             None => list_a = Some(list),
-            Some(other_list) => {
-                lists.push((other_list, list));
-                list_a = None;
-                need_blank = true;
-            },
+            Some(other_list) => lists.push((other_list, list)),
         }
     }
 
-    if let Some(_) = list_a {
+    if list_a.is_some() {
         return Err("unpaired list at end of file".to_string());
     }
 
@@ -261,7 +322,7 @@ fn read_lists<I>(lines: &mut I) -> Result<Vec<(List, List)>, String>
 }
 
 fn main() -> std::process::ExitCode {
-    let lists = match read_lists(&mut std::io::stdin().lines()) {
+    let lists = match read_lists(std::io::stdin().lock()) {
         Err(e) => {
             eprintln!("{}", e);
             return std::process::ExitCode::FAILURE;
@@ -275,7 +336,7 @@ fn main() -> std::process::ExitCode {
         println!("a: {}\n\
                   b: {}",
                  a, b);
-        let comp = compare_list_slice(&a.entries, &b.entries);
+        let comp = a.cmp(b);
         println!("{:?}", comp);
 
         match comp {
@@ -300,3 +361,99 @@ fn main() -> std::process::ExitCode {
 
     std::process::ExitCode::SUCCESS
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_error(s: &str) -> (usize, usize, String) {
+        let e = s.parse::<List>().unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn parse() {
+        let list = " [ 1,[ -2 ,[]],\t-30 ] ".parse::<List>().unwrap();
+        assert_eq!(list.to_string(), "[1, [-2, []], -30]");
+
+        assert_eq!(
+            parse_error("[1,,2]"),
+            (1, 4, "unexpected ‘,’".to_string()),
+        );
+        assert_eq!(
+            parse_error("[1, ]"),
+            (1, 5, "expected ‘[’ or number".to_string()),
+        );
+        assert_eq!(
+            parse_error("[1, 99999999999]"),
+            (1, 5, "invalid number ‘99999999999’".to_string()),
+        );
+        assert_eq!(
+            parse_error("[1, -]"),
+            (1, 5, "invalid number ‘-’".to_string()),
+        );
+        assert_eq!(
+            parse_error("[[1]"),
+            (1, 5, "list not terminated".to_string()),
+        );
+        assert_eq!(
+            parse_error("[1]]"),
+            (1, 4, "extra data at end of string".to_string()),
+        );
+        assert_eq!(
+            parse_error("1"),
+            (1, 1, "expected ‘[’, got ‘1’".to_string()),
+        );
+    }
+
+    #[test]
+    fn reader() {
+        let input = "[1,\n  2]  [3]\n\n\n[[4],\n x]";
+        let mut reader = ListReader::new(input.as_bytes());
+
+        assert_eq!(reader.next().unwrap().unwrap().to_string(), "[1, 2]");
+        assert_eq!(reader.next().unwrap().unwrap().to_string(), "[3]");
+        assert_eq!(
+            reader.next().unwrap().unwrap_err(),
+            ParseError {
+                line: 6,
+                column: 2,
+                message: "unexpected ‘x’".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn order() {
+        let lists = [
+            "[]",
+            "[[]]",
+            "[[[]]]",
+            "[-1]",
+            "[1,1,3,1,1]",
+            "[1,1,5,1,1]",
+            "[[1],[2,3,4]]",
+            "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+            "[[1],4]",
+            "[[2]]",
+            "[3]",
+            "[[4,4],4,4]",
+            "[[4,4],4,4,4]",
+            "[[6]]",
+            "[7,7,7]",
+            "[7,7,7,7]",
+            "[[8,7,6]]",
+            "[9]",
+        ].map(|s| s.parse::<List>().unwrap());
+
+        let mut sorted = lists.clone();
+        sorted.reverse();
+        sorted.sort();
+
+        assert_eq!(sorted, lists);
+
+        // An integer is equal to a list containing only that integer
+        assert_eq!("[[1],2]".parse::<List>(), "[1,[[2]]]".parse::<List>());
+    }
+}
@@ -0,0 +1,276 @@
+use num_traits::PrimInt;
+use std::ops::Range;
+use std::fmt;
+
+// A set of integers stored as a sorted list of half-open ranges. The
+// ranges never overlap or touch each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Range<T>>,
+}
+
+impl<T: PrimInt> IntervalSet<T> {
+    pub fn new() -> IntervalSet<T> {
+        IntervalSet { intervals: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.intervals.clear();
+    }
+
+    pub fn insert(&mut self, range: Range<T>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        // Find all of the intervals that overlap or touch the range
+        let first = self.intervals.partition_point(|r| r.end < range.start);
+        let last = self.intervals.partition_point(|r| r.start <= range.end);
+
+        let merged = if first < last {
+            range.start.min(self.intervals[first].start)
+                ..range.end.max(self.intervals[last - 1].end)
+        } else {
+            range
+        };
+
+        self.intervals.splice(first..last, std::iter::once(merged));
+    }
+
+    pub fn remove(&mut self, range: Range<T>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        // Find all of the intervals that overlap the range
+        let first = self.intervals.partition_point(|r| r.end <= range.start);
+        let last = self.intervals.partition_point(|r| r.start < range.end);
+
+        if first >= last {
+            return;
+        }
+
+        let before = self.intervals[first].start..range.start;
+        let after = range.end..self.intervals[last - 1].end;
+
+        self.intervals.splice(
+            first..last,
+            [before, after].into_iter().filter(|r| r.start < r.end),
+        );
+    }
+
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut result = self.clone();
+
+        for range in other.intervals.iter() {
+            result.insert(range.clone());
+        }
+
+        result
+    }
+
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut intervals = Vec::new();
+        let mut a = self.intervals.iter().peekable();
+        let mut b = other.intervals.iter().peekable();
+
+        while let (Some(ra), Some(rb)) = (a.peek(), b.peek()) {
+            let start = ra.start.max(rb.start);
+            let end = ra.end.min(rb.end);
+
+            if start < end {
+                intervals.push(start..end);
+            }
+
+            // Move past whichever range finishes first
+            if ra.end < rb.end {
+                a.next();
+            } else {
+                b.next();
+            }
+        }
+
+        IntervalSet { intervals }
+    }
+
+    // Returns everything within the bounds that isn’t in the set
+    pub fn complement(&self, bounds: Range<T>) -> IntervalSet<T> {
+        IntervalSet { intervals: self.gaps(bounds).collect() }
+    }
+
+    // Iterates the ranges within the bounds that aren’t in the set
+    pub fn gaps(&self, bounds: Range<T>) -> Gaps<'_, T> {
+        let first = self.intervals.partition_point(|r| r.end <= bounds.start);
+
+        Gaps {
+            intervals: self.intervals[first..].iter(),
+            pos: bounds.start,
+            end: bounds.end,
+        }
+    }
+
+    // The number of integers in the set or None if there are too many
+    // to count in a u64
+    pub fn len(&self) -> Option<u64> {
+        self.intervals.iter().try_fold(0u64, |total, r| {
+            let len = r.end.to_i128()?.checked_sub(r.start.to_i128()?)?;
+            total.checked_add(len.try_into().ok()?)
+        })
+    }
+}
+
+impl<T: PrimInt> Default for IntervalSet<T> {
+    fn default() -> IntervalSet<T> {
+        IntervalSet::new()
+    }
+}
+
+pub struct Gaps<'a, T> {
+    intervals: std::slice::Iter<'a, Range<T>>,
+    pos: T,
+    end: T,
+}
+
+impl<T: PrimInt> Iterator for Gaps<'_, T> {
+    type Item = Range<T>;
+
+    fn next(&mut self) -> Option<Range<T>> {
+        while self.pos < self.end {
+            let start = self.pos;
+
+            match self.intervals.next() {
+                Some(interval) => {
+                    self.pos = self.end.min(interval.end);
+
+                    if interval.start > start {
+                        return Some(start..self.end.min(interval.start));
+                    }
+                },
+                None => {
+                    self.pos = self.end;
+                    return Some(start..self.end);
+                },
+            }
+        }
+
+        None
+    }
+}
+
+impl<T: PrimInt + fmt::Display> fmt::Display for IntervalSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, interval) in self.intervals.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "({}..{})", interval.start, interval.end)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_remove() {
+        let mut set = IntervalSet::new();
+        set.insert(i32::MIN..i32::MAX);
+
+        // Cut the middle out
+        set.remove(12..15);
+        assert_eq!(set.to_string(), "(-2147483648..12), (15..2147483647)");
+
+        // Cut the middle out again
+        set.remove(17..18);
+        assert_eq!(
+            set.to_string(),
+            "(-2147483648..12), (15..17), (18..2147483647)",
+        );
+
+        // Consume the middle range
+        set.remove(15..17);
+        assert_eq!(set.to_string(), "(-2147483648..12), (18..2147483647)");
+
+        // Subtract outside of the set
+        set.remove(12..18);
+        assert_eq!(set.to_string(), "(-2147483648..12), (18..2147483647)");
+        assert_eq!(set.len(), Some(u32::MAX as u64 - 6));
+
+        // Cut both sides off
+        set.remove(i32::MIN..0);
+        set.remove(20..i32::MAX);
+        assert_eq!(set.to_string(), "(0..12), (18..20)");
+
+        // Touching ranges are merged
+        set.insert(12..14);
+        assert_eq!(set.to_string(), "(0..14), (18..20)");
+        set.insert(25..30);
+        set.insert(14..18);
+        assert_eq!(set.to_string(), "(0..20), (25..30)");
+        set.insert(-5..40);
+        assert_eq!(set.to_string(), "(-5..40)");
+
+        set.remove(0..5);
+        assert_eq!(set.to_string(), "(-5..0), (5..40)");
+        assert_eq!(set.len(), Some(40));
+
+        set.remove(-10..50);
+        assert_eq!(set.to_string(), "");
+        assert_eq!(set.len(), Some(0));
+    }
+
+    #[test]
+    fn len() {
+        let mut set = IntervalSet::<u128>::new();
+        set.insert(5..u64::MAX as u128);
+        assert_eq!(set.len(), Some(u64::MAX - 5));
+        set.insert(0..1);
+        assert_eq!(set.len(), Some(u64::MAX - 4));
+        set.insert(u128::MAX - 10..u128::MAX);
+        assert_eq!(set.len(), None);
+
+        let mut set = IntervalSet::<i64>::new();
+        set.insert(i64::MIN..i64::MAX);
+        assert_eq!(set.len(), Some(u64::MAX));
+        set.remove(0..1);
+        set.insert(1..i64::MAX);
+        assert_eq!(set.len(), Some(u64::MAX - 1));
+        set.insert(0..1);
+        assert_eq!(set.len(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut a = IntervalSet::<u8>::new();
+        a.insert(0..10);
+        a.insert(20..30);
+
+        let mut b = IntervalSet::<u8>::new();
+        b.insert(5..25);
+        b.insert(28..40);
+
+        assert_eq!(a.union(&b).to_string(), "(0..40)");
+        assert_eq!(a.union(&b).len(), Some(40));
+        assert_eq!(
+            a.intersection(&b).to_string(),
+            "(5..10), (20..25), (28..30)",
+        );
+        assert_eq!(a.intersection(&IntervalSet::new()).to_string(), "");
+        assert_eq!(a.complement(0..50).to_string(), "(10..20), (30..50)");
+        assert_eq!(a.complement(5..25).to_string(), "(10..20)");
+        assert_eq!(a.complement(10..20).to_string(), "(10..20)");
+        assert_eq!(a.complement(2..8).to_string(), "");
+        assert_eq!(
+            b.gaps(0..255).collect::<Vec<_>>(),
+            [0..5, 25..28, 40..255],
+        );
+        assert!(IntervalSet::<u8>::new().gaps(3..6).eq(std::iter::once(3..6)));
+
+        a.clear();
+        assert_eq!(a.complement(0..3).to_string(), "(0..3)");
+    }
+}
@@ -1,83 +1,21 @@
+// The set has more operations than this day needs
+#[allow(dead_code)]
+mod interval_set;
+mod manhattan;
+
+use interval_set::IntervalSet;
+use manhattan::ManhattanBall;
+
 #[derive(Debug, Clone)]
 struct Sensor {
     sensor_pos: (i32, i32),
     beacon_pos: (i32, i32),
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-struct Interval {
-    start: i32,
-    end: i32,
-}
-
-impl Interval {
-    fn new(start: i32, end: i32) -> Interval {
-        assert!(start < end);
-        Interval { start, end }
-    }
-}
-
-#[derive(Debug, Clone)]
-struct CoordSet {
-    intervals: Vec<Interval>,
-    // Temporary interval set used to avoid reallocating every time we
-    // subtract a range
-    temp_intervals: Vec<Interval>,
-}
-
-impl CoordSet {
-    fn new() -> CoordSet {
-        let mut set = CoordSet {
-            intervals: Vec::<Interval>::new(),
-            temp_intervals: Vec::<Interval>::new(),
-        };
-
-        set.reset();
-
-        set
-    }
-
-    fn reset(&mut self) {
-        self.intervals.clear();
-        self.intervals.push(Interval::new(i32::MIN, i32::MAX));
-    }
-
-    fn subtract(&mut self, start: i32, end: i32) {
-        self.temp_intervals.clear();
-
-        for interval in self.intervals.iter() {
-            // If the interval doesn’t overlap the range to subtract
-            // then leave it alone
-            if end <= interval.start || start >= interval.end {
-                self.temp_intervals.push(*interval);
-                continue;
-            }
-
-            if start > interval.start {
-                self.temp_intervals.push(Interval::new(interval.start, start));
-            }
-
-            if end < interval.end {
-                self.temp_intervals.push(Interval::new(end, interval.end));
-            }
-        }
-
-        std::mem::swap(&mut self.intervals, &mut self.temp_intervals);
-    }
-}
-
-impl std::fmt::Display for CoordSet {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>)
-           -> Result<(), std::fmt::Error> {
-        for (i, interval) in self.intervals.iter().enumerate() {
-            if i > 0 {
-                write!(f, ", ")?;
-            }
-
-            write!(f, "({}..{})", interval.start, interval.end)?;
-        }
-
-        Ok(())
+impl Sensor {
+    // The area where there can’t be another beacon
+    fn ball(&self) -> ManhattanBall {
+        ManhattanBall::reaching(self.sensor_pos, self.beacon_pos)
     }
 }
 
@@ -122,93 +60,88 @@ fn read_sensors<I>(lines: &mut I) -> Result<Vec<Sensor>, String>
     Ok(sensors)
 }
 
-fn apply_sensors(set: &mut CoordSet,
-                 test_row: i32,
-                 sensors: &[Sensor],
-                 exclude_beacon_pos: bool) {
-    for sensor in sensors.iter() {
-        let distance_to_beacon =
-            (sensor.sensor_pos.0 - sensor.beacon_pos.0).abs() +
-            (sensor.sensor_pos.1 - sensor.beacon_pos.1).abs();
+fn run_part1(test_row: i32, sensors: &[Sensor]) -> u64 {
+    let mut set = IntervalSet::new();
 
-        let y_distance = (test_row - sensor.sensor_pos.1).abs();
-        let row_distance = distance_to_beacon - y_distance;
-
-        // If the range we want to subtract doesn’t reach the test row
-        // then skip it
-        if row_distance < 0 {
-            continue;
+    // Add the positions on the row that are within range of any sensor
+    for sensor in sensors.iter() {
+        if let Some(range) = sensor.ball().row_range(test_row) {
+            set.insert(range);
         }
+    }
 
-        let mut range_start = sensor.sensor_pos.0 - row_distance;
-        let mut range_end = sensor.sensor_pos.0 + row_distance + 1;
-
-        if exclude_beacon_pos {
-            // Special case if the beacon is on the test row. In that
-            // case we want to exclude the beacon position from the
-            // range because there definitely is a beacon there
-            if sensor.beacon_pos.1 == test_row {
-                if sensor.beacon_pos.0 < sensor.sensor_pos.0 {
-                    range_start += 1;
-                } else {
-                    range_end -= 1;
-                }
-            }
+    // Positions that have a beacon can obviously have a beacon
+    for sensor in sensors.iter() {
+        if sensor.beacon_pos.1 == test_row {
+            set.remove(sensor.beacon_pos.0..sensor.beacon_pos.0 + 1);
         }
-
-        set.subtract(range_start, range_end);
     }
+
+    // A row of i32 positions always fits in a u64
+    set.len().unwrap()
 }
 
-fn run_part1(test_row: i32, sensors: &[Sensor]) -> usize {
-    let mut set = CoordSet::new();
+// Finds the positions that could have a beacon by checking the gaps
+// in every row. This stops once it has found two positions.
+fn scan_rows(test_range: i32, balls: &[ManhattanBall]) -> Vec<(i32, i32)> {
+    let mut set = IntervalSet::new();
+    let mut positions = Vec::new();
 
-    apply_sensors(&mut set, test_row, sensors, true);
+    for test_row in 0..test_range {
+        set.clear();
 
-    let mut total_spaces = 0;
+        for ball in balls.iter() {
+            if let Some(range) = ball.row_range(test_row) {
+                set.insert(range);
+            }
+        }
 
-    if set.intervals.len() >= 2 {
-        for i in 0..set.intervals.len() - 1 {
-            let left = set.intervals[i];
-            let right = set.intervals[i + 1];
+        for gap in set.gaps(0..test_range) {
+            for x in gap {
+                positions.push((x, test_row));
 
-            total_spaces += (right.start - left.end) as usize;
+                if positions.len() >= 2 {
+                    return positions;
+                }
+            }
         }
     }
 
-    total_spaces
+    positions
 }
 
-
 fn run_part2(test_range: i32, sensors: &[Sensor]) -> String {
-    let mut set = CoordSet::new();
-    let mut found_pos = None;
-
-    for test_row in 0..test_range {
-        set.reset();
-
-        set.subtract(i32::MIN, 0);
-        set.subtract(test_range, i32::MAX);
-
-        apply_sensors(&mut set, test_row, sensors, false);
-
-        for interval in set.intervals.iter() {
-            for x in interval.start..interval.end {
-                if let Some(old_pos) = found_pos {
-                    return format!("Possible beacon at {:?} but there is \
-                                    already a possible beacon at {:?}",
-                                   (x, test_row),
-                                   old_pos);
-                }
-
-                found_pos = Some((x, test_row));
-            }
+    let balls = sensors.iter().map(Sensor::ball).collect::<Vec<_>>();
+
+    // Use the geometry to find the positions that could have a beacon
+    // instead of checking every row
+    let positions = manhattan::find_uncovered(
+        &balls,
+        0..test_range,
+        0..test_range,
+    );
+
+    // Cross-check it with the gaps in each row. The geometry only
+    // finds every position when there is just one so only compare
+    // then.
+    if positions.len() == 1 {
+        let scanned = scan_rows(test_range, &balls);
+
+        if scanned != positions {
+            return format!("The diamond search found {:?} but the row \
+                            scan found {:?}",
+                           positions,
+                           scanned);
         }
     }
 
-    match found_pos {
-        Some((x, y)) => format!("{}", x as i64 * 4_000_000 + y as i64),
-        None => "no possible beacon position found".to_string(),
+    match positions[..] {
+        [(x, y)] => format!("{}", x as i64 * 4_000_000 + y as i64),
+        [] => "no possible beacon position found".to_string(),
+        [a, b, ..] => format!("Possible beacon at {:?} but there is \
+                               already a possible beacon at {:?}",
+                              b,
+                              a),
     }
 }
 
@@ -253,39 +186,3 @@ fn main() -> std::process::ExitCode {
 
     std::process::ExitCode::SUCCESS
 }
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_coord_set() {
-        // Cut the right side off
-        let mut set = CoordSet::new();
-        set.subtract(15, i32::MAX);
-        assert_eq!(set.to_string(), "(-2147483648..15)");
-
-        // Cut the left side off
-        let mut set = CoordSet::new();
-        set.subtract(i32::MIN, 12);
-        assert_eq!(set.to_string(), "(12..2147483647)");
-
-        // Cut the middle out
-        let mut set = CoordSet::new();
-        set.subtract(12, 15);
-        assert_eq!(set.to_string(), "(-2147483648..12), (15..2147483647)");
-
-        // Cut the middle out again
-        set.subtract(17, 18);
-        assert_eq!(set.to_string(),
-                   "(-2147483648..12), (15..17), (18..2147483647)");
-
-        // Consume the middle range
-        set.subtract(15, 17);
-        assert_eq!(set.to_string(), "(-2147483648..12), (18..2147483647)");
-
-        // Subtract outside of the set
-        set.subtract(12, 18);
-        assert_eq!(set.to_string(), "(-2147483648..12), (18..2147483647)");
-    }
-}
@@ -0,0 +1,204 @@
+use std::ops::Range;
+
+pub fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+// Converts to coordinates rotated by 45° so that the edges of a
+// Manhattan ball become horizontal and vertical lines. The first
+// coordinate is constant along edges going down-left and the second
+// along edges going down-right.
+pub fn rotate(pos: (i32, i32)) -> (i64, i64) {
+    let (x, y) = (pos.0 as i64, pos.1 as i64);
+
+    (x + y, x - y)
+}
+
+// Converts rotated coordinates back again. This only gives an integer
+// position if u and v have the same parity.
+pub fn unrotate(u: i64, v: i64) -> Option<(i32, i32)> {
+    if (u - v) % 2 != 0 {
+        return None;
+    }
+
+    let x = (u + v) / 2;
+    let y = (u - v) / 2;
+
+    Some((x.try_into().ok()?, y.try_into().ok()?))
+}
+
+// All of the points within a Manhattan distance of a center point,
+// which looks like a diamond on a grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManhattanBall {
+    pub center: (i32, i32),
+    pub radius: i32,
+}
+
+impl ManhattanBall {
+    pub fn new(center: (i32, i32), radius: i32) -> ManhattanBall {
+        ManhattanBall { center, radius }
+    }
+
+    // Makes a ball that just reaches the edge point
+    pub fn reaching(center: (i32, i32), edge: (i32, i32)) -> ManhattanBall {
+        ManhattanBall::new(center, distance(center, edge))
+    }
+
+    pub fn contains(&self, pos: (i32, i32)) -> bool {
+        distance(self.center, pos) <= self.radius
+    }
+
+    // Returns the x coordinates covered on the row, if any
+    pub fn row_range(&self, y: i32) -> Option<Range<i32>> {
+        let half_width = self.radius - (y - self.center.1).abs();
+
+        (half_width >= 0).then(|| {
+            self.center.0 - half_width..self.center.0 + half_width + 1
+        })
+    }
+
+    // Returns the rotated coordinates of the lines that run just
+    // outside of the ball, ie, the points at a distance of radius + 1
+    // all lie on these lines. The first array is the u coordinates
+    // and the second is the v coordinates.
+    pub fn outside_lines(&self) -> ([i64; 2], [i64; 2]) {
+        let (u, v) = rotate(self.center);
+        let r = self.radius as i64 + 1;
+
+        ([u - r, u + r], [v - r, v + r])
+    }
+}
+
+// Finds the positions within the area that aren’t covered by any of
+// the balls without scanning through the whole area. If there is only
+// one uncovered position then it must be either be next to the edge
+// of at least two balls or on the edge of the area, so it must be at
+// the intersection of two of the lines that run just outside the
+// balls or of one of those lines and the edge of the area. The
+// returned list is sorted and doesn’t contain duplicates. If the area
+// has larger gaps then the result won’t include every position.
+pub fn find_uncovered(
+    balls: &[ManhattanBall],
+    x_range: Range<i32>,
+    y_range: Range<i32>,
+) -> Vec<(i32, i32)> {
+    let mut u_lines = Vec::new();
+    let mut v_lines = Vec::new();
+
+    for ball in balls.iter() {
+        let (us, vs) = ball.outside_lines();
+        u_lines.extend(us);
+        v_lines.extend(vs);
+    }
+
+    let mut candidates = Vec::new();
+
+    for &u in u_lines.iter() {
+        for &v in v_lines.iter() {
+            candidates.extend(unrotate(u, v));
+        }
+    }
+
+    // Points where the lines cross the edges of the area, and the
+    // corners
+    let edge_xs = [x_range.start, x_range.end - 1];
+    let edge_ys = [y_range.start, y_range.end - 1];
+
+    for &x in edge_xs.iter() {
+        for &y in edge_ys.iter() {
+            candidates.push((x, y));
+        }
+
+        for &u in u_lines.iter() {
+            candidates.extend((u - x as i64).try_into().ok().map(|y| (x, y)));
+        }
+
+        for &v in v_lines.iter() {
+            candidates.extend((x as i64 - v).try_into().ok().map(|y| (x, y)));
+        }
+    }
+
+    for &y in edge_ys.iter() {
+        for &u in u_lines.iter() {
+            candidates.extend((u - y as i64).try_into().ok().map(|x| (x, y)));
+        }
+
+        for &v in v_lines.iter() {
+            candidates.extend((v + y as i64).try_into().ok().map(|x| (x, y)));
+        }
+    }
+
+    candidates.retain(|&(x, y)| {
+        x_range.contains(&x)
+            && y_range.contains(&y)
+            && !balls.iter().any(|ball| ball.contains((x, y)))
+    });
+
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    candidates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ball() {
+        let ball = ManhattanBall::reaching((8, 7), (2, 10));
+
+        assert_eq!(ball.radius, 9);
+        assert!(ball.contains((8, -2)));
+        assert!(!ball.contains((8, -3)));
+        assert!(ball.contains((3, 3)));
+        assert!(!ball.contains((3, 2)));
+
+        assert_eq!(ball.row_range(7), Some(-1..18));
+        assert_eq!(ball.row_range(16), Some(8..9));
+        assert_eq!(ball.row_range(17), None);
+
+        // Every point just outside the ball lies on one of the lines
+        let (us, vs) = ball.outside_lines();
+
+        for y in -5..20 {
+            for x in -5..20 {
+                if distance((x, y), ball.center) == ball.radius + 1 {
+                    let (u, v) = rotate((x, y));
+                    assert!(us.contains(&u) || vs.contains(&v));
+                    assert_eq!(unrotate(u, v), Some((x, y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn uncovered() {
+        // Balls that cover all of a 10×10 area except for each
+        // position in turn
+        for gap_y in 0..10 {
+            for gap_x in 0..10 {
+                let mut balls = Vec::new();
+
+                for y in 0..10 {
+                    for x in 0..10 {
+                        if (x, y) != (gap_x, gap_y)
+                            && !balls.iter().any(|b: &ManhattanBall| {
+                                b.contains((x, y))
+                            })
+                        {
+                            let radius = distance((x, y), (gap_x, gap_y)) - 1;
+                            balls.push(ManhattanBall::new((x, y), radius));
+                        }
+                    }
+                }
+
+                assert_eq!(
+                    find_uncovered(&balls, 0..10, 0..10),
+                    [(gap_x, gap_y)],
+                );
+            }
+        }
+    }
+}
@@ -1,39 +1,37 @@
 // The algorithm for this solution is:
 //
-// Part 1:
-//
 // First we walk the entire graph starting from each valve in turn in
 // order to build a lookup table of shortest distances from each valve
 // to any other valve. Next we make a list of all the valves that have
-// a flow rate greater than zero. Then we just iterate over every
-// possible permutation of these valves. Each permutation represents
-// visiting the valves in that order. We can calculate a score for
-// each permutation using the distance table and just pick the best
-// one.
-//
-// As there are 15 valves with a flow rate in my input, that means
-// there are 15! = 1307674368000 possible permutations. In order to
-// cut down the search, the function that generates the permutations
-// can be told to skip iterating over permutations after a certain
-// point in the list. The callback function that analyses the
-// permutations uses this to skip permutations that are only different
-// by valves that are after visited after the 30 minutes are up. The
-// order of these won’t make any difference to the score so we can
-// calculate the score for just one of them and skip the rest.
+// a flow rate greater than zero. Only these valves are worth walking
+// to so the state of the search is just which of these valves we are
+// at, how much time is left and the bitmask of valves that have been
+// opened.
 //
-// Part 2:
+// We do a depth-first search over these states and record the best
+// pressure released for each bitmask of opened valves. If we reach a
+// state that we’ve already seen with at least as much pressure then
+// there’s no point in carrying on from it. Once the search is done
+// each score is copied to every superset of its mask so that the table
+// gives the best score for opening any subset of the mask.
 //
-// Part 2 takes the list of valves that have a flow rate generated in
-// part 1. Then it iterates over all possible subsets of the valves
-// using a function that returns two subsets, everything in the subset
-// and everything out of the subset. Then we just run the algorithm
-// from part 1 on each of the two subsets and add the scores together
-// to get a score for this pair of subsets. Then we just need to pick
-// the best score.
+// Part 1 is then just the score for the mask with every valve in it.
 //
-// The program can solve both parts in less than 10 seconds on my laptop.
+// For part 2 the human and the elephant open disjoint sets of valves,
+// so the answer is the best sum of the scores for a mask and its
+// complement. This generalises to any number of agents by splitting
+// the mask one agent at a time, which can be tried with the
+// --agents option.
+
+// The graph modules have more operations than this day needs
+#[allow(dead_code)]
+mod graph;
+#[allow(dead_code)]
+mod distance_table;
 
 use std::collections::HashMap;
+use graph::Graph;
+use distance_table::DistanceTable;
 
 #[derive(Debug, Clone)]
 struct Valve {
@@ -47,73 +45,6 @@ struct LoadingValve {
     tunnels: Vec<String>,
 }
 
-#[derive(Debug)]
-struct Walker<'a> {
-    stack: Vec<(u8, u8)>,
-    valves: &'a [Valve],
-    distances: &'a mut [u8],
-    pos: u8,
-}
-
-impl<'a> Walker<'a> {
-    fn new(valves: &'a [Valve], distances: &'a mut [u8]) -> Self {
-        Walker {
-            stack: Vec::<(u8, u8)>::new(),
-            valves,
-            distances,
-            pos: 0,
-        }
-    }
-
-    fn backtrack(&mut self) -> bool {
-        loop {
-            match self.stack.pop() {
-                Some((pos, last_tunnel)) => {
-                    let next_tunnel = last_tunnel as usize + 1;
-                    let valve = &self.valves[pos as usize];
-
-                    if let Some(&next_pos) = valve.tunnels.get(next_tunnel) {
-                        self.stack.push((pos, next_tunnel as u8));
-                        self.pos = next_pos;
-                        return true;
-                    }
-                },
-                None => return false,
-            }
-        }
-    }
-
-    fn walk(&mut self, pos: u8) {
-        self.pos = pos;
-
-        loop {
-            // Have we already been here with a shorter distance?
-            if self.distances[self.pos as usize] as usize <= self.stack.len() {
-                if self.backtrack() {
-                    continue;
-                } else {
-                    break;
-                }
-            }
-
-            self.distances[self.pos as usize] = self.stack.len() as u8;
-
-            let valve = &self.valves[self.pos as usize];
-
-            if valve.tunnels.is_empty() {
-                if self.backtrack() {
-                    continue;
-                } else {
-                    break;
-                }
-            } else {
-                self.stack.push((self.pos, 0));
-                self.pos = valve.tunnels[0];
-            }
-        }
-    }
-}
-
 const TOTAL_TIME: usize = 30;
 
 fn read_valves<I>(lines: &mut I) -> Result<Vec<Valve>, String>
@@ -197,17 +128,17 @@ fn read_valves<I>(lines: &mut I) -> Result<Vec<Valve>, String>
     Ok(final_valves)
 }
 
-fn calculate_distances(valves: &[Valve]) -> Vec<u8> {
-    let mut distances = vec![u8::MAX; valves.len() * valves.len()];
-
-    for valve_num in 0..valves.len() {
-        let this_valve_distances =
-            &mut distances[valve_num * valves.len()..
-                           (valve_num + 1) * valves.len()];
-        Walker::new(valves, this_valve_distances).walk(valve_num as u8);
-    }
-
-    distances
+fn calculate_distances(valves: &[Valve]) -> DistanceTable {
+    let graph = Graph::from_edges(
+        valves.len(),
+        valves.iter().enumerate().flat_map(|(valve_num, valve)| {
+            valve.tunnels.iter().map(move |&tunnel| {
+                (valve_num, tunnel as usize, 1)
+            })
+        }),
+    );
+
+    graph.all_pairs_bfs()
 }
 
 fn get_valves_with_flow(valves: &[Valve]) -> Vec<u8> {
@@ -217,146 +148,157 @@ fn get_valves_with_flow(valves: &[Valve]) -> Vec<u8> {
         .collect()
 }
 
-fn permute<F>(valves: &mut [u8], mut func: F)
-    where F: FnMut(&[u8]) -> usize
+// Finds the most pressure that a single agent can release for every
+// set of valves, given as a bitmask of indices into valves_with_flow.
+// Each entry is the best score for opening any subset of the valves
+// in the mask.
+fn best_for_each_mask(valves: &[Valve],
+                      distances: &DistanceTable,
+                      valves_with_flow: &[u8],
+                      total_time: usize) -> Vec<usize>
 {
-    let mut stack = Vec::<u8>::new();
+    let mut best = vec![0; 1 << valves_with_flow.len()];
+    // The best pressure seen for each state of (valve, time left,
+    // opened mask)
+    let mut seen = HashMap::<(u8, usize, usize), usize>::new();
+    let mut stack = vec![(0u8, total_time, 0usize, 0usize)];
+
+    while let Some((pos, time_left, mask, pressure)) = stack.pop() {
+        best[mask] = best[mask].max(pressure);
+
+        for (i, &valve_num) in valves_with_flow.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                continue;
+            }
 
-    'outer: loop {
-        if stack.len() >= valves.len() {
-            let cut_point = func(&valves);
+            let Some(distance) = distances.get(pos as usize,
+                                               valve_num as usize)
+            else { continue; };
 
-            stack.truncate(cut_point);
+            // Time to walk to the valve and open it
+            let cost = distance as usize + 1;
 
-            loop {
-                match stack.pop() {
-                    Some(n) => {
-                        // Put the element back
-                        if n > 0 {
-                            valves.swap(stack.len(), stack.len() + n as usize);
-                        }
-                        if (n as usize) + 1 < valves.len() - stack.len() {
-                            valves.swap(stack.len(),
-                                        stack.len() + n as usize + 1);
-                            stack.push(n + 1);
-                            break;
-                        }
-                    },
-                    None => break 'outer,
-                }
+            if cost >= time_left {
+                continue;
             }
-        } else {
-            stack.push(0);
-        }
-    }
-}
 
-fn score_valves(valves: &[Valve],
-                distances: &[u8],
-                valve_order: &[u8],
-                total_time: usize) -> (usize, usize) {
-    let mut score = 0;
-    let mut pos = 0;
-    let mut time = 0;
-    let mut cut_point = valve_order.len();
-
-    for (i, &valve_num) in valve_order.iter().enumerate() {
-        time += distances[pos * valves.len() +
-                          valve_num as usize] as usize + 1;
-
-        if time > total_time {
-            cut_point = i + 1;
-            break;
-        }
+            let time_left = time_left - cost;
+            let pressure = pressure +
+                time_left * valves[valve_num as usize].flow_rate as usize;
+            let mask = mask | (1 << i);
+            let key = (valve_num, time_left, mask);
 
-        score += (total_time - time) *
-            (valves[valve_num as usize].flow_rate as usize);
+            if seen.get(&key).is_some_and(|&p| p >= pressure) {
+                continue;
+            }
 
-        pos = valve_num as usize;
+            seen.insert(key, pressure);
+            stack.push((valve_num, time_left, mask, pressure));
+        }
     }
 
-    (score, cut_point)
-}
-
-fn subsets<F>(all_values: &[u8],
-              mut func: F)
-    where F: FnMut(&mut [u8], &mut [u8])
-{
-    let mut subset = Vec::<u8>::new();
-    let mut rest = Vec::<u8>::new();
-
-    for set_mask in 0..(1u64 << all_values.len()) {
-        subset.clear();
-        rest.clear();
-
-        for (i, &value) in all_values.iter().enumerate() {
-            if set_mask & (1u64 << i) == 0 {
-                rest.push(value);
-            } else {
-                subset.push(value);
+    // Spread each score to the supersets of its mask
+    for i in 0..valves_with_flow.len() {
+        for mask in 0..best.len() {
+            if mask & (1 << i) != 0 {
+                best[mask] = best[mask].max(best[mask ^ (1 << i)]);
             }
         }
-
-        func(&mut subset, &mut rest);
     }
+
+    best
 }
 
-fn best_score_for_valves(valves: &[Valve],
-                         distances: &[u8],
-                         valves_with_flow: &mut [u8],
-                         total_time: usize) -> usize
-{
-    let mut best_score = usize::MIN;
+// Finds the best score when the valves are shared between n_agents
+// that each open a disjoint set. best is the table for a single agent
+// from best_for_each_mask.
+fn best_for_agents(best: &[usize], n_agents: usize) -> usize {
+    let mut combined = best.to_vec();
 
-    permute(valves_with_flow, |valve_order| {
-        let (score, cut_point) = score_valves(valves,
-                                              distances,
-                                              valve_order,
-                                              total_time);
+    for _ in 1..n_agents {
+        combined = (0..best.len()).map(|mask| {
+            let mut best_score = 0;
+            let mut submask = mask;
 
-        if score > best_score {
-            best_score = score;
-        }
+            // Give each submask to the new agent and the rest to the
+            // agents so far
+            loop {
+                best_score = best_score.max(best[submask] +
+                                            combined[mask ^ submask]);
 
-        cut_point
-    });
+                if submask == 0 {
+                    break;
+                }
 
-    best_score
+                submask = (submask - 1) & mask;
+            }
+
+            best_score
+        }).collect();
+    }
+
+    if n_agents == 0 {
+        0
+    } else {
+        combined[combined.len() - 1]
+    }
 }
 
 fn part1(valves: &[Valve],
-         distances: &[u8],
-         valves_with_flow: &mut [u8]) -> usize
+         distances: &DistanceTable,
+         valves_with_flow: &[u8]) -> usize
 {
-    best_score_for_valves(valves, distances, valves_with_flow, TOTAL_TIME)
+    let best = best_for_each_mask(valves,
+                                  distances,
+                                  valves_with_flow,
+                                  TOTAL_TIME);
+
+    best_for_agents(&best, 1)
 }
 
 fn part2(valves: &[Valve],
-         distances: &[u8],
-         valves_with_flow: &mut [u8]) -> usize
+         distances: &DistanceTable,
+         valves_with_flow: &[u8],
+         n_agents: usize) -> usize
 {
-    let mut best_score = usize::MIN;
-
-    subsets(valves_with_flow, |you_valves, elephant_valves| {
-        let score =
-            best_score_for_valves(valves,
-                                  distances,
-                                  you_valves,
-                                  TOTAL_TIME - 4) +
-            best_score_for_valves(valves,
+    // It takes 4 minutes to teach the elephant
+    let best = best_for_each_mask(valves,
                                   distances,
-                                  elephant_valves,
+                                  valves_with_flow,
                                   TOTAL_TIME - 4);
 
-        if score > best_score {
-            best_score = score;
+    best_for_agents(&best, n_agents)
+}
+
+fn parse_args() -> Result<usize, String> {
+    let mut args = std::env::args().skip(1);
+    let mut n_agents = 2;
+
+    while let Some(arg) = args.next() {
+        if arg == "--agents" {
+            let Some(n) = args.next().and_then(|n| n.parse().ok())
+            else {
+                return Err("--agents needs a number".to_string());
+            };
+
+            n_agents = n;
+        } else {
+            return Err(format!("unknown argument: {}", arg));
         }
-    });
+    }
 
-    best_score
+    Ok(n_agents)
 }
 
 fn main() -> std::process::ExitCode {
+    let n_agents = match parse_args() {
+        Err(e) => {
+            eprintln!("{}", e);
+            return std::process::ExitCode::FAILURE;
+        },
+        Ok(n_agents) => n_agents,
+    };
+
     let valves = match read_valves(&mut std::io::stdin().lines()) {
         Err(e) => {
             eprintln!("{}", e);
@@ -367,14 +309,155 @@ fn main() -> std::process::ExitCode {
 
     let distances = calculate_distances(&valves);
 
-    let mut valves_with_flow = get_valves_with_flow(&valves);
+    let valves_with_flow = get_valves_with_flow(&valves);
 
     println!("part 1: {}", part1(&valves,
                                  &distances,
-                                 &mut valves_with_flow));
+                                 &valves_with_flow));
     println!("part 2: {}", part2(&valves,
                                  &distances,
-                                 &mut valves_with_flow));
+                                 &valves_with_flow,
+                                 n_agents));
 
     std::process::ExitCode::SUCCESS
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static EXAMPLE: [&str; 10] = [
+        "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB",
+        "Valve BB has flow rate=13; tunnels lead to valves CC, AA",
+        "Valve CC has flow rate=2; tunnels lead to valves DD, BB",
+        "Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE",
+        "Valve EE has flow rate=3; tunnels lead to valves FF, DD",
+        "Valve FF has flow rate=0; tunnels lead to valves EE, GG",
+        "Valve GG has flow rate=0; tunnels lead to valves FF, HH",
+        "Valve HH has flow rate=22; tunnel leads to valve GG",
+        "Valve II has flow rate=0; tunnels lead to valves AA, JJ",
+        "Valve JJ has flow rate=21; tunnel leads to valve II",
+    ];
+
+    fn example_valves() -> (Vec<Valve>, DistanceTable, Vec<u8>) {
+        let valves = read_valves(
+            &mut EXAMPLE.iter().map(|line| Ok(line.to_string()))
+        ).unwrap();
+        let distances = calculate_distances(&valves);
+        let valves_with_flow = get_valves_with_flow(&valves);
+
+        (valves, distances, valves_with_flow)
+    }
+
+    // Tries every order of opening the valves for a single agent
+    fn brute_force(valves: &[Valve],
+                   distances: &DistanceTable,
+                   valves_with_flow: &[u8],
+                   pos: u8,
+                   time_left: usize,
+                   mask: usize) -> usize
+    {
+        let mut best = 0;
+
+        for (i, &valve_num) in valves_with_flow.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                continue;
+            }
+
+            let Some(distance) = distances.get(pos as usize,
+                                               valve_num as usize)
+            else { continue; };
+            let cost = distance as usize + 1;
+
+            if cost >= time_left {
+                continue;
+            }
+
+            let time_left = time_left - cost;
+            let pressure =
+                time_left * valves[valve_num as usize].flow_rate as usize;
+
+            best = best.max(pressure + brute_force(valves,
+                                                   distances,
+                                                   valves_with_flow,
+                                                   valve_num,
+                                                   time_left,
+                                                   mask | (1 << i)));
+        }
+
+        best
+    }
+
+    #[test]
+    fn example() {
+        let (valves, distances, valves_with_flow) = example_valves();
+
+        assert_eq!(part1(&valves, &distances, &valves_with_flow), 1651);
+        assert_eq!(part2(&valves, &distances, &valves_with_flow, 2), 1707);
+    }
+
+    #[test]
+    fn one_agent() {
+        let (valves, distances, valves_with_flow) = example_valves();
+
+        // With only one agent part 2 is the same as part 1 with the
+        // time it took to teach the elephant taken away
+        for total_time in [TOTAL_TIME, TOTAL_TIME - 4] {
+            let best = best_for_each_mask(&valves,
+                                          &distances,
+                                          &valves_with_flow,
+                                          total_time);
+
+            assert_eq!(best_for_agents(&best, 1),
+                       brute_force(&valves,
+                                   &distances,
+                                   &valves_with_flow,
+                                   0,
+                                   total_time,
+                                   0));
+        }
+
+        assert_eq!(part2(&valves, &distances, &valves_with_flow, 1),
+                   brute_force(&valves,
+                               &distances,
+                               &valves_with_flow,
+                               0,
+                               TOTAL_TIME - 4,
+                               0));
+
+        // Every entry in the table is the best score for opening valves
+        // from its mask, which is the same as searching with the rest
+        // of the valves already open
+        let best = best_for_each_mask(&valves,
+                                      &distances,
+                                      &valves_with_flow,
+                                      TOTAL_TIME);
+
+        for (mask, &score) in best.iter().enumerate() {
+            let already_open = !mask & (best.len() - 1);
+
+            assert_eq!(score, brute_force(&valves,
+                                          &distances,
+                                          &valves_with_flow,
+                                          0,
+                                          TOTAL_TIME,
+                                          already_open));
+        }
+    }
+
+    #[test]
+    fn agent_counts() {
+        let (valves, distances, valves_with_flow) = example_valves();
+
+        assert_eq!(part2(&valves, &distances, &valves_with_flow, 0), 0);
+
+        let scores = (1..=valves_with_flow.len() + 1).map(|n_agents| {
+            part2(&valves, &distances, &valves_with_flow, n_agents)
+        }).collect::<Vec<_>>();
+
+        // More agents never does worse, and once there is an agent
+        // for every valve adding more doesn’t help
+        assert!(scores.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(scores[scores.len() - 2], scores[scores.len() - 1]);
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cycle {
+    // Number of steps before the state first enters the cycle
+    pub start: usize,
+    // Number of steps to get back to the same state
+    pub length: usize,
+}
+
+impl Cycle {
+    // Returns the smallest number of steps that gives the same state
+    // as n steps.
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            (n - self.start) % self.length + self.start
+        }
+    }
+}
+
+// Uses Brent’s algorithm to find a cycle in a sequence of states that
+// might end. The step function advances the state in place and
+// returns false if there is no next state, in which case there is no
+// cycle. Only two copies of the state are kept at any one time.
+pub fn find_terminating<S, F>(initial: &S, mut step: F) -> Option<Cycle>
+    where S: Clone + Eq,
+          F: FnMut(&mut S) -> bool
+{
+    // Find the cycle length by moving the hare ahead in powers of two
+    // and teleporting the tortoise to it each time
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+
+    if !step(&mut hare) {
+        return None;
+    }
+
+    while tortoise != hare {
+        if power == length {
+            tortoise.clone_from(&hare);
+            power *= 2;
+            length = 0;
+        }
+
+        if !step(&mut hare) {
+            return None;
+        }
+
+        length += 1;
+    }
+
+    // Put the hare `length` steps ahead and then move both until
+    // they meet at the start of the cycle
+    tortoise.clone_from(initial);
+    hare.clone_from(initial);
+
+    for _ in 0..length {
+        step(&mut hare);
+    }
+
+    let mut start = 0;
+
+    while tortoise != hare {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
+    }
+
+    Some(Cycle { start, length })
+}
+
+// Finds a cycle in an infinite sequence of states
+pub fn find<S, F>(initial: &S, mut step: F) -> Cycle
+    where S: Clone + Eq,
+          F: FnMut(&mut S)
+{
+    find_terminating(initial, |state| {
+        step(state);
+        true
+    }).unwrap()
+}
+
+// Finds a cycle by remembering a key for every state that is visited.
+// This is useful when the state is too big to compare or copy
+// cheaply, for example if it contains a growing history, but a
+// smaller key can identify when the future steps will repeat. The
+// state is left at the first repeated step, which is
+// cycle.start + cycle.length.
+pub fn find_by_key<S, K, F, G>(state: &mut S, mut step: F, mut key: G) -> Cycle
+    where K: Hash + Eq,
+          F: FnMut(&mut S),
+          G: FnMut(&S) -> K
+{
+    let mut seen = HashMap::new();
+
+    for step_num in 0.. {
+        if let Some(start) = seen.insert(key(state), step_num) {
+            return Cycle { start, length: step_num - start };
+        }
+
+        step(state);
+    }
+
+    unreachable!("infinite loop shouldn’t terminate");
+}
+
+// Advances the state by n steps, skipping over whole cycles once the
+// key repeats. The counter is some value accumulated by the state,
+// such as a height or a score, that increases by the same amount each
+// time around the cycle. Returns a state equivalent to the one after
+// n steps along with the extrapolated value of the counter.
+pub fn fast_forward<S, K, F, G, C>(
+    mut state: S,
+    n: usize,
+    mut step: F,
+    mut key: G,
+    mut counter: C,
+) -> (S, i64)
+    where K: Hash + Eq,
+          F: FnMut(&mut S),
+          G: FnMut(&S) -> K,
+          C: FnMut(&S) -> i64,
+{
+    let mut seen = HashMap::new();
+    let mut counters = Vec::new();
+
+    for step_num in 0..n {
+        let count = counter(&state);
+
+        if let Some(start) = seen.insert(key(&state), step_num) {
+            let length = step_num - start;
+            let per_cycle = count - counters[start];
+            let extra_steps = (n - step_num) % length;
+            let n_cycles = (n - step_num) / length;
+
+            for _ in 0..extra_steps {
+                step(&mut state);
+            }
+
+            let count = counter(&state);
+
+            return (state, count + n_cycles as i64 * per_cycle);
+        }
+
+        counters.push(count);
+        step(&mut state);
+    }
+
+    let count = counter(&state);
+
+    (state, count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn brent() {
+        // x → x² + 1 mod 255 starting at 3 gives 3, 10, 101, 2, 5,
+        // 26, 167, 95, 101, … so the cycle starts at step 2 and has a
+        // length of 6
+        let cycle = find(&3u32, |x| *x = (*x * *x + 1) % 255);
+        assert_eq!(cycle, Cycle { start: 2, length: 6 });
+
+        assert_eq!(cycle.reduce(0), 0);
+        assert_eq!(cycle.reduce(7), 7);
+        assert_eq!(cycle.reduce(8), 2);
+        assert_eq!(cycle.reduce(15), 3);
+
+        // A sequence that loops back to the start
+        let cycle = find(&0u32, |x| *x = (*x + 1) % 7);
+        assert_eq!(cycle, Cycle { start: 0, length: 7 });
+
+        // A sequence that stays the same
+        let cycle = find(&4u32, |_| ());
+        assert_eq!(cycle, Cycle { start: 0, length: 1 });
+
+        // A sequence that ends before repeating
+        assert_eq!(
+            find_terminating(&0u32, |x| {
+                *x += 1;
+                *x < 1000
+            }),
+            None,
+        );
+
+        assert_eq!(
+            find_terminating(&0u32, |x| {
+                *x = (*x + 3) % 10;
+                true
+            }),
+            Some(Cycle { start: 0, length: 10 }),
+        );
+    }
+
+    #[test]
+    fn by_key() {
+        // The state records the history so it never repeats but the
+        // last value does
+        let mut history = vec![3u32];
+        let cycle = find_by_key(
+            &mut history,
+            |h| h.push((h.last().unwrap().pow(2) + 1) % 255),
+            |h| *h.last().unwrap(),
+        );
+
+        assert_eq!(cycle, Cycle { start: 2, length: 6 });
+        assert_eq!(history.len(), 9);
+    }
+
+    #[test]
+    fn fast_forward_counter() {
+        // The state is a position on a loop of 5 with a counter that
+        // increases by the position each step. The first 3 steps lead
+        // in to the loop.
+        let step = |state: &mut (u32, i64)| {
+            state.0 = if state.0 < 8 { state.0 + 1 } else { 4 };
+            state.1 += state.0 as i64;
+        };
+        let key = |&(pos, _): &(u32, i64)| pos;
+        let counter = |&(_, total): &(u32, i64)| total;
+
+        let mut expected = (1, 0);
+
+        for n in 0..100 {
+            let (state, total) = fast_forward((1, 0), n, step, key, counter);
+
+            // The state itself only has the counter for the steps
+            // that were actually simulated
+            assert_eq!((state.0, total), expected);
+
+            step(&mut expected);
+        }
+
+        let (state, total) = fast_forward(
+            (1, 0),
+            1_000_000_000_003,
+            step,
+            key,
+            counter,
+        );
+        // 2 + 3 + 4 for the steps into the loop and then 5 + 6 + 7 +
+        // 8 + 4 for each time around the loop
+        assert_eq!(state.0, 4);
+        assert_eq!(total, 2 + 3 + 4 + 200_000_000_000 * 30);
+    }
+}
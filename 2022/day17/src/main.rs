@@ -1,3 +1,7 @@
+// The cycle module has more helpers than this day needs
+#[allow(dead_code)]
+mod cycle;
+
 use std::io::BufRead;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -130,7 +134,9 @@ fn settle_shape(grid: &mut Vec<u8>,
     }
 }
 
-fn add_shape(grid: &mut Vec<u8>, jet: &mut Jet, shape: u16) {
+// Drops the shape onto the tower and returns the row that it came to
+// rest on
+fn add_shape(grid: &mut Vec<u8>, jet: &mut Jet, shape: u16) -> usize {
     let mut y_pos = grid.len() + 3;
     let mut x_pos = 2;
 
@@ -152,7 +158,7 @@ fn add_shape(grid: &mut Vec<u8>, jet: &mut Jet, shape: u16) {
             y_pos -= 1;
         } else {
             settle_shape(grid, shape, x_pos, y_pos);
-            break;
+            break y_pos;
         }
     }
 }
@@ -190,82 +196,80 @@ fn read_jet<R: BufRead>(reader: &mut R) ->
     Ok(Jet::new(directions))
 }
 
-fn is_height_loop(height_history: &[usize]) -> Option<usize> {
-    const MIN_CYCLE_SIZE: usize = 5;
-    const MIN_CYCLES: usize = 100;
+// Number of rows at the top of the tower that are used to decide
+// whether the tower is in the same state as a previous one. If no
+// rock ever looks at the rows below this then they can’t affect the
+// future heights and two towers with the same key will grow the same
+// way. The tower keeps track of whether that held for every rock that
+// was dropped so that a cycle based on a key that was too short isn’t
+// trusted.
+const TOP_ROWS: usize = 64;
+
+struct Tower {
+    grid: Vec<u8>,
+    jet: Jet,
+    shape_num: usize,
+    // False if any rock fell far enough to touch the rows that aren’t
+    // in the key
+    key_is_exact: bool,
+}
 
-    if height_history.len() < MIN_CYCLE_SIZE * MIN_CYCLES {
-        return None;
+impl Tower {
+    fn new(jet: Jet) -> Tower {
+        Tower {
+            grid: Vec::new(),
+            jet,
+            shape_num: 0,
+            key_is_exact: true,
+        }
     }
 
-    let max_cycle_length = height_history.len() / MIN_CYCLES;
+    fn drop_shape(&mut self) {
+        let top = self.grid.len();
 
-    'next_cycle: for cycle_length in SHAPES.len()..=max_cycle_length {
-        for cycle_num in 0..MIN_CYCLES - 1 {
-            let start_point = height_history.len() -
-                cycle_length * MIN_CYCLES +
-                cycle_num * cycle_length;
+        let y_pos = add_shape(
+            &mut self.grid,
+            &mut self.jet,
+            SHAPES[self.shape_num % SHAPES.len()],
+        );
 
-            let mid_point = start_point + cycle_length;
-
-            for i in 0..cycle_length {
-                let bottom_height = height_history[start_point + i];
-                let top_height = height_history[mid_point + i];
-
-                if bottom_height != top_height {
-                    continue 'next_cycle;
-                }
-            }
+        // The last move that the rock tried was into the row below
+        // where it came to rest
+        if top > TOP_ROWS && y_pos <= top - TOP_ROWS {
+            self.key_is_exact = false;
         }
 
-        return Some(cycle_length);
+        self.shape_num += 1;
     }
 
-    None
-}
-
-fn jet_to_height_loop(mut jet: Jet) -> (Box<[usize]>, usize) {
-    let mut grid = Vec::<u8>::new();
-    let mut height_history = Vec::<usize>::new();
-
-    for shape_num in 0.. {
-        let old_height = grid.len();
-
-        add_shape(&mut grid, &mut jet, SHAPES[shape_num % SHAPES.len()]);
-
-        height_history.push(grid.len() - old_height);
+    fn key(&self) -> (usize, usize, Vec<u8>) {
+        let top_start = self.grid.len().saturating_sub(TOP_ROWS);
 
-        if let Some(cycle_length) = is_height_loop(&height_history) {
-            height_history.truncate(height_history.len() - cycle_length);
-            return (height_history.into_boxed_slice(), cycle_length);
-        }
+        (
+            self.shape_num % SHAPES.len(),
+            self.jet.jet_pos,
+            self.grid[top_start..].to_vec(),
+        )
     }
-
-    panic!("infinite loop shouldn’t terminate");
 }
 
-fn get_height_at(&(ref height_history, cycle_size): &(Box<[usize]>, usize),
-                 y: usize) -> usize
-{
-    if y <= height_history.len() {
-        return height_history[0..y].iter().sum();
+fn height_after(jet: &Jet, n_shapes: usize) -> Result<i64, String> {
+    let (tower, height) = cycle::fast_forward(
+        Tower::new(jet.clone()),
+        n_shapes,
+        Tower::drop_shape,
+        Tower::key,
+        |tower| tower.grid.len() as i64,
+    );
+
+    if tower.key_is_exact {
+        Ok(height)
+    } else {
+        Err(format!(
+            "a rock fell more than {} rows so the tower might not repeat",
+            TOP_ROWS,
+        ))
     }
-
-    let cycle_start = height_history.len() - cycle_size;
-
-    let base_height = height_history[0..cycle_start].iter().sum::<usize>();
-
-    let cycle_height = height_history[cycle_start..].iter().sum::<usize>();
-    let cycle_offset = y - cycle_start;
-    let n_cycles = cycle_offset / cycle_size;
-
-    let inside_cycle_pos = cycle_offset % cycle_size;
-
-    let inside_cycle_height =
-        height_history[cycle_start..cycle_start + inside_cycle_pos]
-        .iter().sum::<usize>();
-
-    base_height + cycle_height * n_cycles + inside_cycle_height
 }
 
 fn main() -> std::process::ExitCode {
@@ -277,10 +281,15 @@ fn main() -> std::process::ExitCode {
         Ok(d) => d,
     };
 
-    let loop_data = jet_to_height_loop(jet);
-
-    println!("part 1: {}", get_height_at(&loop_data, 2022));
-    println!("part 2: {}", get_height_at(&loop_data, 1000000000000));
+    for (part, n_shapes) in [2022, 1000000000000].into_iter().enumerate() {
+        match height_after(&jet, n_shapes) {
+            Ok(height) => println!("part {}: {}", part + 1, height),
+            Err(e) => {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
+            },
+        }
+    }
 
     std::process::ExitCode::SUCCESS
 }
@@ -1,7 +1,12 @@
+mod search;
+mod parallel;
+
 use std::io::Read;
+use search::{SearchState, Stats};
 
 const N_MATERIALS: usize = 4;
 const N_ROBOTS: usize = N_MATERIALS;
+const GEODE: usize = N_MATERIALS - 1;
 
 #[derive(Debug, Clone, Copy)]
 struct Costs {
@@ -13,13 +18,6 @@ struct Blueprint {
     costs: [Costs; N_ROBOTS],
 }
 
-#[derive(Debug, Clone)]
-struct StackEntry {
-    n_robots: [usize; N_ROBOTS],
-    n_materials: [usize; N_MATERIALS],
-    robot_created: Option<u8>,
-}
-
 fn read_blueprints<R>(input: &mut R) -> Result<Vec<Blueprint>, String>
     where R: Read
 {
@@ -69,158 +67,158 @@ fn read_blueprints<R>(input: &mut R) -> Result<Vec<Blueprint>, String>
     Ok(blueprints)
 }
 
-fn try_make_robot(blueprint: &Blueprint,
-                  n_materials: &mut [usize],
-                  robot_type: u8) -> bool {
-    let costs = blueprint.costs[robot_type as usize];
-
-    for (material_num, &count) in costs.material.iter().enumerate() {
-        if (count as usize) > n_materials[material_num] {
-            return false;
-        }
-    }
-
-    for (material_num, &count) in costs.material.iter().enumerate() {
-        n_materials[material_num] -= count as usize;
-    }
-
-    true
+#[derive(Debug, Clone)]
+struct Factory<'a> {
+    blueprint: &'a Blueprint,
+    minutes_remaining: usize,
+    n_robots: [usize; N_ROBOTS],
+    n_materials: [usize; N_MATERIALS],
 }
 
-fn robot_is_pointless(blueprint: &Blueprint,
-                      n_robots: &[usize],
-                      robot_type: u8) -> bool {
-    let robot_index = robot_type as usize;
+impl<'a> Factory<'a> {
+    fn new(blueprint: &'a Blueprint, n_minutes: usize) -> Factory<'a> {
+        let mut n_robots = [0; N_ROBOTS];
+
+        // Start with an ore robot
+        n_robots[0] = 1;
 
-    // It’s never pointless to build the geode robot
-    if robot_index >= N_ROBOTS - 1 {
-        return false;
+        Factory {
+            blueprint,
+            minutes_remaining: n_minutes,
+            n_robots,
+            n_materials: [0; N_MATERIALS],
+        }
     }
 
-    // It is pointless to make this robot if we already have enough
-    // robots to build whatever robot needs its resources at every
-    // turn
-    for costs in blueprint.costs.iter() {
-        if costs.material[robot_index] as usize > n_robots[robot_index] {
+    fn robot_is_pointless(&self, robot_type: usize) -> bool {
+        // It’s never pointless to build the geode robot
+        if robot_type >= GEODE {
             return false;
         }
+
+        // It is pointless to make this robot if we already have enough
+        // robots to build whatever robot needs its resources at every
+        // turn
+        self.blueprint.costs.iter().all(|costs| {
+            costs.material[robot_type] as usize <= self.n_robots[robot_type]
+        })
     }
 
-    true
-}
+    // Returns how many minutes we need to wait until we have enough
+    // materials to build the robot, or None if we aren’t collecting
+    // one of the materials
+    fn minutes_to_afford(&self, robot_type: usize) -> Option<usize> {
+        let costs = &self.blueprint.costs[robot_type];
+        let mut minutes = 0;
 
-fn apply_next_robot(blueprint: &Blueprint,
-                    stack: &mut Vec<StackEntry>,
-                    start_robot: u8) {
-    let mut top = stack.last().unwrap().clone();
+        for (material, &cost) in costs.material.iter().enumerate() {
+            let cost = cost as usize;
+            let have = self.n_materials[material];
 
-    'find_robot_type: {
-        for robot_type in start_robot..(N_ROBOTS as u8) {
-            if robot_is_pointless(blueprint,
-                                  &top.n_robots,
-                                  robot_type) {
+            if cost <= have {
                 continue;
             }
 
-            if try_make_robot(blueprint,
-                              &mut top.n_materials,
-                              robot_type) {
-                top.robot_created = Some(robot_type);
-                break 'find_robot_type;
-            }
-        }
+            let rate = self.n_robots[material];
 
-        top.robot_created = None;
-    }
+            if rate == 0 {
+                return None;
+            }
 
-    for material in 0..N_MATERIALS {
-        top.n_materials[material] += top.n_robots[material];
-    }
+            minutes = minutes.max((cost - have).div_ceil(rate));
+        }
 
-    if let Some(r) = top.robot_created {
-        top.n_robots[r as usize] += 1;
+        Some(minutes)
     }
-
-    stack.push(top);
 }
 
-fn backtrack(blueprint: &Blueprint,
-             stack: &mut Vec<StackEntry>) -> bool {
-    loop {
-        let top = match stack.pop() {
-            Some(t) => t,
-            None => return false,
-        };
+impl<'a> SearchState for Factory<'a> {
+    type Key = ();
 
-        let robot_created = match top.robot_created {
-            Some(r) => r,
-            None => continue,
-        };
+    // Instead of deciding what to do every minute, each child skips
+    // ahead to when the next robot is built. The geode robot is added
+    // last so that it gets tried first.
+    fn children(&self, children: &mut Vec<Self>) {
+        for robot_type in 0..N_ROBOTS {
+            if self.robot_is_pointless(robot_type) {
+                continue;
+            }
 
-        if stack.is_empty() {
-            return false;
-        }
+            let Some(wait) = self.minutes_to_afford(robot_type)
+            else { continue; };
 
-        apply_next_robot(blueprint, stack, robot_created + 1);
-        return true;
-    }
-}
-
-fn calc_max_geodes(state: &StackEntry, minutes_remaining: usize) -> usize {
-    state.n_materials[N_MATERIALS - 1] +
-        (0..minutes_remaining).map(|minute| {
-            // At each minute, assume we produced a geode robot the last minute
-            // and add all of the geodes produced by the robots
-            state.n_robots[N_ROBOTS - 1] + minute
-        }).sum::<usize>()
-}
+            // Building the robot takes a minute and it needs at least
+            // one more minute to collect anything
+            let minutes = wait + 1;
 
-fn try_blueprint(blueprint: &Blueprint,
-                 n_minutes: usize) -> usize {
-    let mut stack = vec![StackEntry {
-        n_robots: [0; N_ROBOTS],
-        n_materials: [0; N_MATERIALS],
-        robot_created: None,
-    }; 1];
-    let mut best_score = 0;
+            if minutes >= self.minutes_remaining {
+                continue;
+            }
 
-    // Start with an ore robot
-    stack[0].n_robots[0] = 1;
+            let costs = &self.blueprint.costs[robot_type];
+            let mut child = self.clone();
 
-    loop {
-        if stack.len() >= n_minutes + 1 {
-            let n_geodes = stack.last().unwrap().n_materials[N_MATERIALS - 1];
+            for material in 0..N_MATERIALS {
+                let cost = costs.material[material] as usize;
 
-            if n_geodes > best_score {
-                best_score = n_geodes;
+                child.n_materials[material] =
+                    child.n_materials[material] +
+                    child.n_robots[material] * minutes -
+                    cost;
             }
 
-            if !backtrack(blueprint, &mut stack) {
-                break;
-            } else {
-                continue;
-            }
-        }
+            child.n_robots[robot_type] += 1;
+            child.minutes_remaining -= minutes;
 
-        // Don’t continue if the maximum number of geodes we could
-        // build from here on is worse than the best solution
-        let max_geodes = calc_max_geodes(stack.last().unwrap(),
-                                         n_minutes + 1 - stack.len());
-        if max_geodes <= best_score {
-            if !backtrack(blueprint, &mut stack) {
-                break;
-            } else {
-                continue;
-            }
+            children.push(child);
         }
+    }
+
+    // The geodes we’d have if we stopped building robots now
+    fn score(&self) -> Option<usize> {
+        Some(self.n_materials[GEODE] +
+             self.n_robots[GEODE] * self.minutes_remaining)
+    }
+
+    // Assume we can build a geode robot every minute from now on
+    fn upper_bound(&self) -> usize {
+        let minutes = self.minutes_remaining;
 
-        apply_next_robot(blueprint, &mut stack, 0);
+        self.n_materials[GEODE] +
+            self.n_robots[GEODE] * minutes +
+            minutes * minutes.saturating_sub(1) / 2
     }
+}
+
+fn try_blueprint(blueprint: &Blueprint, n_minutes: usize) -> (usize, Stats) {
+    let result = search::branch_and_bound(Factory::new(blueprint, n_minutes));
 
-    best_score
+    (result.best.map_or(0, |(score, _)| score), result.stats)
+}
+
+fn try_blueprints(
+    blueprints: &[Blueprint],
+    n_minutes: usize,
+    n_threads: usize,
+) -> Vec<(usize, Stats)> {
+    parallel::map(blueprints, n_threads, |blueprint| {
+        try_blueprint(blueprint, n_minutes)
+    })
+}
+
+fn format_stats(stats: &Stats) -> String {
+    format!("expanded {}, pruned {}", stats.expanded, stats.pruned_by_bound)
 }
 
 fn main() -> std::process::ExitCode {
+    let n_threads = match parallel::parse_threads_arg() {
+        Err(e) => {
+            eprintln!("{}", e);
+            return std::process::ExitCode::FAILURE;
+        },
+        Ok(n_threads) => n_threads,
+    };
+
     let blueprints = match read_blueprints(&mut std::io::stdin().lock()) {
         Err(e) => {
             eprintln!("{}", e);
@@ -229,18 +227,18 @@ fn main() -> std::process::ExitCode {
         Ok(b) => b,
     };
 
-    let part1 = blueprints
+    let part1 = try_blueprints(&blueprints, 24, n_threads)
         .iter()
         .enumerate()
-        .map(|(num, bp)| {
-            let best_score = try_blueprint(bp, 24);
+        .map(|(num, (best_score, stats))| {
             let result = (num + 1) * best_score;
-            println!("{}/{}: {} * {} = {}",
+            println!("{}/{}: {} * {} = {} ({})",
                      num + 1,
                      blueprints.len(),
                      num + 1,
                      best_score,
-                     result);
+                     result,
+                     format_stats(stats));
             result
         })
         .sum::<usize>();
@@ -249,15 +247,15 @@ fn main() -> std::process::ExitCode {
 
     let max_blueprints = std::cmp::min(3, blueprints.len());
 
-    let part2 = blueprints[0..max_blueprints]
+    let part2 = try_blueprints(&blueprints[..max_blueprints], 32, n_threads)
         .iter()
         .enumerate()
-        .map(|(num, bp)| {
-            let best_score = try_blueprint(bp, 32);
-            println!("{}/{}: {}",
+        .map(|(num, (best_score, stats))| {
+            println!("{}/{}: {} ({})",
                      num + 1,
                      max_blueprints,
-                     best_score);
+                     best_score,
+                     format_stats(stats));
             best_score
         })
         .product::<usize>();
@@ -266,3 +264,36 @@ fn main() -> std::process::ExitCode {
 
     std::process::ExitCode::SUCCESS
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static EXAMPLE: &str =
+        "Blueprint 1: \
+         Each ore robot costs 4 ore. \
+         Each clay robot costs 2 ore. \
+         Each obsidian robot costs 3 ore and 14 clay. \
+         Each geode robot costs 2 ore and 7 obsidian.\n\
+         Blueprint 2: \
+         Each ore robot costs 2 ore. \
+         Each clay robot costs 3 ore. \
+         Each obsidian robot costs 3 ore and 8 clay. \
+         Each geode robot costs 3 ore and 12 obsidian.\n";
+
+    #[test]
+    fn example() {
+        let blueprints = read_blueprints(&mut EXAMPLE.as_bytes()).unwrap();
+
+        assert_eq!(blueprints.len(), 2);
+
+        for (n_minutes, expected) in [(24, [9, 12]), (32, [56, 62])] {
+            for (blueprint, expected) in blueprints.iter().zip(expected) {
+                let (best_score, stats) = try_blueprint(blueprint, n_minutes);
+
+                assert_eq!(best_score, expected);
+                assert!(stats.pruned_by_bound > 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+// A state in a search for the highest score. The search can skip a
+// state and everything reachable from it if its upper bound isn’t
+// better than the best score found so far.
+pub trait SearchState: Sized {
+    // Key used to skip states that have already been seen. Use () if
+    // states can’t be reached more than once.
+    type Key: Hash + Eq;
+
+    // Adds the states reachable in one step from this one. The
+    // children that are added last are searched first.
+    fn children(&self, children: &mut Vec<Self>);
+
+    // The score if the search finished at this state, or None if it
+    // isn’t a valid solution
+    fn score(&self) -> Option<usize>;
+
+    // The best score that could possibly be reached from this state,
+    // including the state itself
+    fn upper_bound(&self) -> usize;
+
+    fn key(&self) -> Option<Self::Key> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub expanded: usize,
+    pub pruned_by_bound: usize,
+    pub duplicates: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult<S> {
+    // The best score and the state that got it
+    pub best: Option<(usize, S)>,
+    pub stats: Stats,
+}
+
+// Depth-first search for the state with the highest score
+pub fn branch_and_bound<S>(start: S) -> SearchResult<S>
+    where S: SearchState + Clone
+{
+    let mut stats = Stats::default();
+    let mut best: Option<(usize, S)> = None;
+    let mut seen = HashSet::<S::Key>::new();
+    let mut stack = vec![start];
+
+    while let Some(state) = stack.pop() {
+        if best.as_ref()
+            .is_some_and(|&(score, _)| state.upper_bound() <= score)
+        {
+            stats.pruned_by_bound += 1;
+            continue;
+        }
+
+        if let Some(key) = state.key() {
+            if !seen.insert(key) {
+                stats.duplicates += 1;
+                continue;
+            }
+        }
+
+        stats.expanded += 1;
+
+        if let Some(score) = state.score() {
+            if best.as_ref().is_none_or(|&(best_score, _)| score > best_score) {
+                best = Some((score, state.clone()));
+            }
+        }
+
+        state.children(&mut stack);
+    }
+
+    SearchResult { best, stats }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Picks items to put in a knapsack to get the highest total value
+    // without going over the maximum weight
+    #[derive(Debug, Clone)]
+    struct Knapsack<'a> {
+        items: &'a [(usize, usize)],
+        max_weight: usize,
+        n_items: usize,
+        weight: usize,
+        value: usize,
+        picked: Vec<usize>,
+    }
+
+    impl<'a> SearchState for Knapsack<'a> {
+        type Key = (usize, usize, usize);
+
+        fn children(&self, children: &mut Vec<Self>) {
+            let Some(&(weight, value)) = self.items.get(self.n_items)
+            else { return; };
+
+            let mut skipped = self.clone();
+            skipped.n_items += 1;
+            children.push(skipped);
+
+            if self.weight + weight <= self.max_weight {
+                let mut picked = self.clone();
+                picked.n_items += 1;
+                picked.weight += weight;
+                picked.value += value;
+                picked.picked.push(self.n_items);
+                children.push(picked);
+            }
+        }
+
+        fn score(&self) -> Option<usize> {
+            Some(self.value)
+        }
+
+        fn upper_bound(&self) -> usize {
+            self.value + self.items[self.n_items..].iter()
+                .map(|&(_, value)| value)
+                .sum::<usize>()
+        }
+
+        fn key(&self) -> Option<Self::Key> {
+            Some((self.n_items, self.weight, self.value))
+        }
+    }
+
+    #[test]
+    fn knapsack() {
+        let items = [(12, 4), (2, 2), (1, 1), (1, 2), (4, 10), (7, 3)];
+
+        for max_weight in 0..30 {
+            let result = branch_and_bound(Knapsack {
+                items: &items,
+                max_weight,
+                n_items: 0,
+                weight: 0,
+                value: 0,
+                picked: Vec::new(),
+            });
+
+            // Try every combination
+            let best = (0..1 << items.len()).filter_map(|mask: usize| {
+                let picked = items.iter()
+                    .enumerate()
+                    .filter(|&(i, _)| mask & (1 << i) != 0);
+                let weight = picked.clone()
+                    .map(|(_, item)| item.0)
+                    .sum::<usize>();
+
+                (weight <= max_weight)
+                    .then(|| picked.map(|(_, item)| item.1).sum::<usize>())
+            }).max();
+
+            let (score, state) = result.best.unwrap();
+
+            assert_eq!(Some(score), best);
+            assert_eq!(
+                state.picked.iter().map(|&i| items[i].1).sum::<usize>(),
+                score,
+            );
+            assert!(result.stats.expanded < 1 << (items.len() + 1));
+        }
+
+        // Everything fits so the first branch finds the best score
+        // and the bound prunes the rest
+        let result = branch_and_bound(Knapsack {
+            items: &items,
+            max_weight: 100,
+            n_items: 0,
+            weight: 0,
+            value: 0,
+            picked: Vec::new(),
+        });
+
+        assert_eq!(result.best.unwrap().0, 22);
+        assert_eq!(
+            result.stats,
+            Stats {
+                expanded: items.len() + 1,
+                pruned_by_bound: items.len(),
+                duplicates: 0,
+            },
+        );
+    }
+}
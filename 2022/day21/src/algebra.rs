@@ -0,0 +1,306 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Overflow,
+    DivideByZero,
+    // Multiplying two expressions that both contain the unknown
+    NotLinear,
+    // Dividing by an expression that contains the unknown
+    UnknownDivisor,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Overflow => write!(f, "arithmetic overflow"),
+            Error::DivideByZero => write!(f, "division by zero"),
+            Error::NotLinear => {
+                write!(f, "expression is not linear in the unknown")
+            },
+            Error::UnknownDivisor => {
+                write!(f, "division by an expression containing the unknown")
+            },
+        }
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a.abs()
+}
+
+// An exact fraction that is always kept in its lowest terms with a
+// positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { numerator: 0, denominator: 1 };
+    pub const ONE: Rational = Rational { numerator: 1, denominator: 1 };
+
+    pub fn new(numerator: i128, denominator: i128) -> Result<Rational, Error> {
+        if denominator == 0 {
+            return Err(Error::DivideByZero);
+        }
+
+        let divisor = gcd(numerator, denominator);
+        let sign = denominator.signum();
+
+        Ok(Rational {
+            numerator: numerator / divisor * sign,
+            denominator: denominator / divisor * sign,
+        })
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    // Returns the value as an integer if it doesn’t have a fractional
+    // part
+    pub fn to_integer(self) -> Option<i64> {
+        if self.denominator == 1 {
+            self.numerator.try_into().ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn add(self, other: Rational) -> Result<Rational, Error> {
+        let numerator = self.numerator.checked_mul(other.denominator)
+            .zip(other.numerator.checked_mul(self.denominator))
+            .and_then(|(a, b)| a.checked_add(b))
+            .ok_or(Error::Overflow)?;
+        let denominator = self.denominator.checked_mul(other.denominator)
+            .ok_or(Error::Overflow)?;
+
+        Rational::new(numerator, denominator)
+    }
+
+    pub fn neg(self) -> Rational {
+        Rational {
+            numerator: -self.numerator,
+            denominator: self.denominator,
+        }
+    }
+
+    pub fn sub(self, other: Rational) -> Result<Rational, Error> {
+        self.add(other.neg())
+    }
+
+    pub fn mul(self, other: Rational) -> Result<Rational, Error> {
+        // Cross-cancel first to make overflow less likely
+        let a = gcd(self.numerator, other.denominator).max(1);
+        let b = gcd(other.numerator, self.denominator).max(1);
+
+        let numerator = (self.numerator / a)
+            .checked_mul(other.numerator / b)
+            .ok_or(Error::Overflow)?;
+        let denominator = (self.denominator / b)
+            .checked_mul(other.denominator / a)
+            .ok_or(Error::Overflow)?;
+
+        Rational::new(numerator, denominator)
+    }
+
+    pub fn div(self, other: Rational) -> Result<Rational, Error> {
+        if other.is_zero() {
+            return Err(Error::DivideByZero);
+        }
+
+        self.mul(Rational {
+            numerator: other.denominator * other.numerator.signum(),
+            denominator: other.numerator.abs(),
+        })
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(value: i64) -> Rational {
+        Rational { numerator: value as i128, denominator: 1 }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+// An expression of the form a*x + b where x is the unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Linear {
+    pub a: Rational,
+    pub b: Rational,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solution {
+    Unique(Rational),
+    // The equation holds for every value of the unknown
+    Any,
+    // The equation can’t hold for any value of the unknown
+    None,
+}
+
+impl Linear {
+    pub fn constant(value: Rational) -> Linear {
+        Linear { a: Rational::ZERO, b: value }
+    }
+
+    pub fn unknown() -> Linear {
+        Linear { a: Rational::ONE, b: Rational::ZERO }
+    }
+
+    // Returns the value if the expression doesn’t depend on the
+    // unknown
+    pub fn as_constant(&self) -> Option<Rational> {
+        self.a.is_zero().then_some(self.b)
+    }
+
+    pub fn add(&self, other: &Linear) -> Result<Linear, Error> {
+        Ok(Linear {
+            a: self.a.add(other.a)?,
+            b: self.b.add(other.b)?,
+        })
+    }
+
+    pub fn sub(&self, other: &Linear) -> Result<Linear, Error> {
+        Ok(Linear {
+            a: self.a.sub(other.a)?,
+            b: self.b.sub(other.b)?,
+        })
+    }
+
+    fn scale(&self, factor: Rational) -> Result<Linear, Error> {
+        Ok(Linear {
+            a: self.a.mul(factor)?,
+            b: self.b.mul(factor)?,
+        })
+    }
+
+    pub fn mul(&self, other: &Linear) -> Result<Linear, Error> {
+        if let Some(factor) = other.as_constant() {
+            self.scale(factor)
+        } else if let Some(factor) = self.as_constant() {
+            other.scale(factor)
+        } else {
+            Err(Error::NotLinear)
+        }
+    }
+
+    pub fn div(&self, other: &Linear) -> Result<Linear, Error> {
+        let Some(divisor) = other.as_constant()
+        else { return Err(Error::UnknownDivisor); };
+
+        Ok(Linear {
+            a: self.a.div(divisor)?,
+            b: self.b.div(divisor)?,
+        })
+    }
+
+    // Solves self = other for the unknown
+    pub fn solve(&self, other: &Linear) -> Result<Solution, Error> {
+        // (a₁ - a₂)x = b₂ - b₁
+        let a = self.a.sub(other.a)?;
+        let b = other.b.sub(self.b)?;
+
+        if a.is_zero() {
+            if b.is_zero() {
+                Ok(Solution::Any)
+            } else {
+                Ok(Solution::None)
+            }
+        } else {
+            Ok(Solution::Unique(b.div(a)?))
+        }
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.a.is_zero() {
+            write!(f, "{}", self.b)
+        } else if self.b.is_zero() {
+            write!(f, "{}x", self.a)
+        } else {
+            write!(f, "{}x + {}", self.a, self.b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn r(numerator: i128, denominator: i128) -> Rational {
+        Rational::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn rational() {
+        assert_eq!(r(4, 6), r(2, 3));
+        assert_eq!(r(4, -6), r(-2, 3));
+        assert_eq!(r(-4, -6).to_string(), "2/3");
+        assert_eq!(r(0, -6), Rational::ZERO);
+        assert_eq!(r(12, 4).to_string(), "3");
+        assert_eq!(Rational::new(1, 0), Err(Error::DivideByZero));
+
+        assert_eq!(r(1, 2).add(r(1, 3)), Ok(r(5, 6)));
+        assert_eq!(r(1, 2).sub(r(1, 3)), Ok(r(1, 6)));
+        assert_eq!(r(2, 3).mul(r(9, 4)), Ok(r(3, 2)));
+        assert_eq!(r(2, 3).div(r(-4, 9)), Ok(r(-3, 2)));
+        assert_eq!(r(2, 3).div(Rational::ZERO), Err(Error::DivideByZero));
+
+        assert_eq!(r(10, 5).to_integer(), Some(2));
+        assert_eq!(r(10, 4).to_integer(), None);
+
+        let big = Rational::from(i64::MAX);
+        assert_eq!(big.mul(big).and_then(|b| b.mul(big)), Err(Error::Overflow));
+    }
+
+    #[test]
+    fn linear() {
+        let x = Linear::unknown();
+        let c = |v| Linear::constant(Rational::from(v));
+
+        // (x + 3) * 2 / 4 = 5
+        let left = x.add(&c(3)).unwrap()
+            .mul(&c(2)).unwrap()
+            .div(&c(4)).unwrap();
+        assert_eq!(left.to_string(), "1/2x + 3/2");
+        assert_eq!(left.solve(&c(5)), Ok(Solution::Unique(r(7, 1))));
+
+        // The unknown on both sides: 3x - 4 = x + 5
+        let left = c(3).mul(&x).unwrap().sub(&c(4)).unwrap();
+        let right = x.add(&c(5)).unwrap();
+        assert_eq!(left.solve(&right), Ok(Solution::Unique(r(9, 2))));
+
+        // x + x = 2x for every x
+        let left = x.add(&x).unwrap();
+        let right = x.mul(&c(2)).unwrap();
+        assert_eq!(left.solve(&right), Ok(Solution::Any));
+
+        // x + 1 = x never holds
+        assert_eq!(x.add(&c(1)).unwrap().solve(&x), Ok(Solution::None));
+
+        // (x - x) * x is fine because the left side is constant
+        let zero = x.sub(&x).unwrap();
+        assert_eq!(zero.mul(&x), Ok(c(0)));
+
+        assert_eq!(x.mul(&x.add(&c(1)).unwrap()), Err(Error::NotLinear));
+        assert_eq!(c(1).div(&x), Err(Error::UnknownDivisor));
+        assert_eq!(x.div(&c(0)), Err(Error::DivideByZero));
+    }
+}
@@ -1,5 +1,8 @@
+mod algebra;
+
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use algebra::{Linear, Rational, Solution};
 
 #[derive(Debug, Clone)]
 enum NamedExpression {
@@ -16,35 +19,23 @@ enum Operator {
 }
 
 impl Operator {
-    fn apply(self, a: i64, b: i64) -> i64 {
+    fn apply(self, a: &Linear, b: &Linear) -> Result<Linear, algebra::Error> {
         match self {
-            Operator::Plus => a + b,
-            Operator::Minus => a - b,
-            Operator::Divide => a / b,
-            Operator::Multiply => a * b,
+            Operator::Plus => a.add(b),
+            Operator::Minus => a.sub(b),
+            Operator::Divide => a.div(b),
+            Operator::Multiply => a.mul(b),
         }
     }
-}
-
-#[derive(Debug)]
-enum Expression {
-    Constant(i64),
-    Operation {
-        a: Box<Expression>,
-        op: Operator,
-        b: Box<Expression>,
-    },
-    Marker,
-}
-
-enum BuildStackEntry {
-    NeedLeft(Operator, u32),
-    NeedRight(Box<Expression>, Operator),
-}
 
-enum FoldStackEntry<'a> {
-    NeedLeft(Operator, &'a Expression),
-    NeedRight(Expression, Operator),
+    fn symbol(self) -> char {
+        match self {
+            Operator::Plus => '+',
+            Operator::Minus => '-',
+            Operator::Divide => '/',
+            Operator::Multiply => '*',
+        }
+    }
 }
 
 fn monkey_name_to_int(name: &str) -> u32 {
@@ -124,211 +115,247 @@ fn read_monkies<I>(lines: &mut I) ->
     Ok(monkies)
 }
 
-fn build_expression(monkies: &HashMap<u32, NamedExpression>,
-                    monkey: u32,
-                    marker: Option<u32>) ->
-    Result<Expression, String>
-{
-    let mut stack = Vec::<BuildStackEntry>::new();
-    let mut next_monkey = monkey;
-
-    loop {
-        let mut expression = match marker {
-            Some(m) if m == next_monkey => Expression::Marker,
-            _ => {
-                let named_expression = match monkies.get(&next_monkey) {
-                    None => {
-                        return Err(format!("missing monkey {}",
-                                           int_to_monkey_name(next_monkey)));
-                    },
-                    Some(e) => e,
-                };
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Constant(i64),
+    Operation { a: usize, op: Operator, b: usize },
+}
 
-                match named_expression {
-                    NamedExpression::Constant(c) =>
-                        Expression::Constant(*c),
-                    &NamedExpression::Operation { a: left, op, b: right } => {
-                        stack.push(BuildStackEntry::NeedLeft(op, right));
-                        next_monkey = left;
-                        continue;
-                    },
-                }
-            },
+// The monkeys with their names replaced by indices into a table so
+// that a monkey referenced by several others is only stored once
+struct MonkeyGraph {
+    names: Vec<u32>,
+    nodes: Vec<Node>,
+    ids: HashMap<u32, usize>,
+}
+
+impl MonkeyGraph {
+    fn new(monkies: &HashMap<u32, NamedExpression>) ->
+        Result<MonkeyGraph, String>
+    {
+        let mut names = monkies.keys().copied().collect::<Vec<u32>>();
+        names.sort_unstable();
+
+        let ids = names.iter()
+            .enumerate()
+            .map(|(id, &name)| (name, id))
+            .collect::<HashMap<u32, usize>>();
+
+        let lookup = |monkey: u32, name: u32| {
+            ids.get(&name).copied().ok_or_else(|| {
+                format!("monkey {} refers to undefined monkey {}",
+                        int_to_monkey_name(monkey),
+                        int_to_monkey_name(name))
+            })
         };
 
-        loop {
-            match stack.pop() {
-                None => return Ok(expression),
-                Some(BuildStackEntry::NeedLeft(op, right)) => {
-                    let left = Box::new(expression);
-                    stack.push(BuildStackEntry::NeedRight(left, op));
-                    next_monkey = right;
-                    break;
-                },
-                Some(BuildStackEntry::NeedRight(left, op)) => {
-                    expression =
-                        Expression::Operation {
-                            a: left,
-                            op,
-                            b: Box::new(expression),
-                        };
+        let nodes = names.iter().map(|&name| {
+            Ok(match monkies[&name] {
+                NamedExpression::Constant(c) => Node::Constant(c),
+                NamedExpression::Operation { a, op, b } => Node::Operation {
+                    a: lookup(name, a)?,
+                    op,
+                    b: lookup(name, b)?,
                 },
-            };
-        }
+            })
+        }).collect::<Result<Vec<Node>, String>>()?;
+
+        Ok(MonkeyGraph { names, nodes, ids })
     }
-}
 
-fn fold_constants(expression: &Expression) -> Expression {
-    let mut stack = Vec::<FoldStackEntry>::new();
-    let mut next_expression = expression;
-
-    loop {
-        let mut expression = match next_expression {
-            Expression::Constant(c) =>
-                Expression::Constant(*c),
-            Expression::Marker =>
-                Expression::Marker,
-            &Expression::Operation { a: ref left, op, b: ref right } => {
-                stack.push(FoldStackEntry::NeedLeft(op, right));
-                next_expression = left;
-                continue;
-            },
-        };
+    fn id(&self, name: &str) -> Result<usize, String> {
+        self.ids.get(&monkey_name_to_int(name)).copied().ok_or_else(|| {
+            format!("missing monkey {}", name)
+        })
+    }
+
+    fn name(&self, id: usize) -> String {
+        int_to_monkey_name(self.names[id])
+    }
+
+    fn write_dot<W: std::io::Write>(&self, out: &mut W) ->
+        std::io::Result<()>
+    {
+        writeln!(out, "digraph monkeys {{")?;
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            let name = self.name(id);
 
-        loop {
-            match stack.pop() {
-                None => return expression,
-                Some(FoldStackEntry::NeedLeft(op, right)) => {
-                    let entry =
-                        FoldStackEntry::NeedRight(expression, op);
-                    stack.push(entry);
-                    next_expression = right;
-                    break;
+            match *node {
+                Node::Constant(c) => {
+                    writeln!(out, "  {} [label=\"{}\\n{}\"];", name, name, c)?;
                 },
-                Some(FoldStackEntry::NeedRight(left, op)) => {
-                    if let Expression::Constant(a) = left {
-                        if let Expression::Constant(b) = expression {
-                            let r = op.apply(a, b);
-                            expression = Expression::Constant(r);
-                            continue;
-                        }
-                    }
-
-                    expression =
-                        Expression::Operation {
-                            a: Box::new(left),
-                            op,
-                            b: Box::new(expression),
-                        };
+                Node::Operation { a, op, b } => {
+                    writeln!(
+                        out,
+                        "  {} [label=\"{}\\n{}\"];",
+                        name,
+                        name,
+                        op.symbol(),
+                    )?;
+                    writeln!(out, "  {} -> {};", name, self.name(a))?;
+                    writeln!(out, "  {} -> {};", name, self.name(b))?;
                 },
-            };
+            }
         }
+
+        writeln!(out, "}}")
     }
 }
 
-fn equate_expression(mut expression: &Expression, mut value: i64) ->
-    Result<i64, String>
-{
-    loop {
-        match expression {
-            Expression::Constant(_) => {
-                break Err("tried to equate value with constant \
-                           expression".to_string());
-            },
-            Expression::Marker => {
-                break Ok(value);
-            },
-            &Expression::Operation { ref a, op, ref b } => {
-                let is_right;
-                let constant;
-
-                if let &Expression::Constant(c) = a.as_ref() {
-                    expression = &b;
-                    constant = c;
-                    is_right = false;
-                } else if let &Expression::Constant(c) = b.as_ref() {
-                    expression = &a;
-                    constant = c;
-                    is_right = true;
-                } else {
-                    return Err("neither side of folded expression is a \
-                                constant".to_string());
-                };
+// Calculates the values of the monkeys, remembering each result so
+// that shared monkeys are only evaluated once
+struct Evaluator<'a> {
+    graph: &'a MonkeyGraph,
+    unknown: Option<usize>,
+    values: Vec<Option<Linear>>,
+    in_progress: Vec<bool>,
+}
 
-                value = match op {
-                    Operator::Plus => value - constant,
-                    Operator::Minus => {
-                        if is_right {
-                            value + constant
-                        } else {
-                            constant - value
-                        }
-                    },
-                    Operator::Divide => {
-                        if is_right {
-                            value * constant
-                        } else {
-                            constant / value
+impl<'a> Evaluator<'a> {
+    // If unknown is given then that monkey’s value is replaced with
+    // the unknown instead of what it yells
+    fn new(graph: &'a MonkeyGraph, unknown: Option<usize>) -> Evaluator<'a> {
+        Evaluator {
+            graph,
+            unknown,
+            values: vec![None; graph.nodes.len()],
+            in_progress: vec![false; graph.nodes.len()],
+        }
+    }
+
+    fn cycle_error(&self, stack: &[(usize, bool)], start: usize) -> String {
+        // The expanded nodes on the stack are the path from the first
+        // monkey to the current one
+        let path = stack.iter()
+            .filter_map(|&(id, expanded)| expanded.then_some(id))
+            .skip_while(|&id| id != start)
+            .chain(std::iter::once(start))
+            .map(|id| self.graph.name(id))
+            .collect::<Vec<String>>();
+
+        format!("reference cycle: {}", path.join(" → "))
+    }
+
+    fn value(&mut self, id: usize) -> Result<Linear, String> {
+        let mut stack = vec![(id, false)];
+
+        while let Some(&mut (id, ref mut expanded)) = stack.last_mut() {
+            if self.values[id].is_some() {
+                stack.pop();
+                continue;
+            }
+
+            let value = if self.unknown == Some(id) {
+                Linear::unknown()
+            } else {
+                match self.graph.nodes[id] {
+                    Node::Constant(c) => Linear::constant(Rational::from(c)),
+                    Node::Operation { a, op, b } => {
+                        if !*expanded {
+                            *expanded = true;
+                            self.in_progress[id] = true;
+
+                            for child in [b, a] {
+                                if self.in_progress[child] {
+                                    return Err(self.cycle_error(&stack, child));
+                                }
+
+                                if self.values[child].is_none() {
+                                    stack.push((child, false));
+                                }
+                            }
+
+                            continue;
                         }
+
+                        self.in_progress[id] = false;
+
+                        op.apply(
+                            self.values[a].as_ref().unwrap(),
+                            self.values[b].as_ref().unwrap(),
+                        ).map_err(|e| {
+                            format!("{}: {}", self.graph.name(id), e)
+                        })?
                     },
-                    Operator::Multiply => value / constant,
                 }
-            },
+            };
+
+            self.values[id] = Some(value);
+            stack.pop();
         }
+
+        Ok(self.values[id].unwrap())
     }
 }
 
-fn part1(monkies: &HashMap<u32, NamedExpression>) -> Result<i64, String> {
-    let expression = build_expression(&monkies,
-                                      monkey_name_to_int("root"),
-                                      None)?;
+fn part1(graph: &MonkeyGraph) -> Result<i64, String> {
+    let root = graph.id("root")?;
 
-    match fold_constants(&expression) {
-        Expression::Constant(c) => Ok(c),
-        _ => panic!("constant folding didn’t fold to a constant"),
-    }
+    let value = Evaluator::new(graph, None)
+        .value(root)?
+        .as_constant()
+        .expect("expression without an unknown depends on the unknown");
+
+    value.to_integer().ok_or_else(|| {
+        format!("root value {} is not an integer", value)
+    })
 }
 
-fn part2(monkies: &HashMap<u32, NamedExpression>) -> Result<i64, String> {
-    let expression = build_expression(&monkies,
-                                      monkey_name_to_int("root"),
-                                      Some(monkey_name_to_int("humn")))?;
-
-    let (a, b) = match fold_constants(&expression) {
-        Expression::Constant(_) =>
-            return Err("root is a constant".to_string()),
-        Expression::Marker =>
-            return Err("root is a marker".to_string()),
-        Expression::Operation { a, b, .. } =>
-            (a, b),
-    };
+fn part2(graph: &MonkeyGraph) -> Result<i64, String> {
+    let root = graph.id("root")?;
+    let humn = graph.id("humn")?;
 
-    let (expression, constant) = if let Expression::Constant(c) = *a {
-        (b, c)
-    } else if let Expression::Constant(c) = *b {
-        (a, c)
-    } else {
-        return Err("neither side of folded root expression is a \
-                    constant".to_string());
-    };
+    let Node::Operation { a, b, .. } = graph.nodes[root]
+    else { return Err("root is a constant".to_string()); };
+
+    if root == humn {
+        return Err("root is humn".to_string());
+    }
+
+    let mut evaluator = Evaluator::new(graph, Some(humn));
+    let a = evaluator.value(a)?;
+    let b = evaluator.value(b)?;
 
-    equate_expression(&expression, constant)
+    match a.solve(&b).map_err(|e| e.to_string())? {
+        Solution::Unique(value) => value.to_integer().ok_or_else(|| {
+            format!("solution humn = {} is not an integer", value)
+        }),
+        Solution::Any => {
+            Err(format!("{} = {} holds for every value", a, b))
+        },
+        Solution::None => {
+            Err(format!("{} = {} has no solution", a, b))
+        },
+    }
 }
 
 fn main() -> std::process::ExitCode {
-    let monkies = match read_monkies(&mut std::io::stdin().lines()) {
+    let graph = match read_monkies(&mut std::io::stdin().lines())
+        .and_then(|monkies| MonkeyGraph::new(&monkies))
+    {
         Err(e) => {
             eprintln!("{}", e);
             return std::process::ExitCode::FAILURE;
         },
-        Ok(m) => m,
+        Ok(g) => g,
     };
 
+    if std::env::args().nth(1).is_some_and(|arg| arg == "--dot") {
+        return match graph.write_dot(&mut std::io::stdout().lock()) {
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::ExitCode::FAILURE
+            },
+            Ok(()) => std::process::ExitCode::SUCCESS,
+        };
+    }
+
     let mut ret = std::process::ExitCode::SUCCESS;
 
     print!("part 1: ");
 
-    match part1(&monkies) {
+    match part1(&graph) {
         Err(e) => {
             println!("{}", e);
             ret = std::process::ExitCode::FAILURE;
@@ -338,7 +365,7 @@ fn main() -> std::process::ExitCode {
 
     print!("part 2: ");
 
-    match part2(&monkies) {
+    match part2(&graph) {
         Err(e) => {
             println!("{}", e);
             ret = std::process::ExitCode::FAILURE;
@@ -348,3 +375,117 @@ fn main() -> std::process::ExitCode {
 
     ret
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn graph_from(lines: &[&str]) -> Result<MonkeyGraph, String> {
+        let monkies = read_monkies(
+            &mut lines.iter().map(|line| Ok(line.to_string()))
+        )?;
+        MonkeyGraph::new(&monkies)
+    }
+
+    #[test]
+    fn cycle() {
+        let graph = graph_from(&[
+            "root: aaaa + dddd",
+            "aaaa: bbbb * cccc",
+            "bbbb: cccc - dddd",
+            "cccc: dddd / aaaa",
+            "dddd: 5",
+        ]).unwrap();
+
+        assert_eq!(
+            part1(&graph).unwrap_err(),
+            "reference cycle: aaaa → bbbb → cccc → aaaa",
+        );
+
+        // A monkey that refers to itself
+        let graph = graph_from(&["root: root + humn", "humn: 1"]).unwrap();
+        assert_eq!(part1(&graph).unwrap_err(), "reference cycle: root → root");
+    }
+
+    #[test]
+    fn undefined() {
+        assert_eq!(
+            graph_from(&["root: aaaa + humn", "humn: 3"]).err().unwrap(),
+            "monkey root refers to undefined monkey aaaa",
+        );
+
+        assert_eq!(
+            graph_from(&["root: humn + humn"]).err().unwrap(),
+            "monkey root refers to undefined monkey humn",
+        );
+
+        let graph = graph_from(&["humn: 3"]).unwrap();
+        assert_eq!(part1(&graph).unwrap_err(), "missing monkey root");
+    }
+
+    #[test]
+    fn shared() {
+        // Each monkey refers to the one before it twice so the value
+        // doubles each time. Expanding this into a tree would need 2⁴⁰
+        // nodes.
+        let name = |i: usize| {
+            let letters = [i / 676, i / 26 % 26, i % 26]
+                .map(|l| (b'a' + l as u8) as char);
+            format!("m{}{}{}", letters[0], letters[1], letters[2])
+        };
+
+        let mut lines = vec![
+            format!("root: {} + humn", name(40)),
+            "humn: 0".to_string(),
+            format!("{}: 1", name(0)),
+        ];
+
+        for i in 1..=40 {
+            let previous = name(i - 1);
+            lines.push(format!("{}: {} + {}", name(i), previous, previous));
+        }
+
+        let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+        let graph = graph_from(&lines).unwrap();
+
+        assert_eq!(part1(&graph), Ok(1 << 40));
+        assert_eq!(part2(&graph), Ok(1 << 40));
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+
+        // Every monkey is only drawn once
+        assert_eq!(dot.matches(&format!("  {} [", name(20))).count(), 1);
+        assert_eq!(dot.matches(&format!("-> {};", name(20))).count(), 2);
+    }
+
+    #[test]
+    fn dot() {
+        let graph = graph_from(&[
+            "root: pppw + sjmn",
+            "pppw: cczh / lfqf",
+            "cczh: 4",
+            "lfqf: 4",
+            "sjmn: 2",
+        ]).unwrap();
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+
+        assert_eq!(
+            String::from_utf8(dot).unwrap(),
+            "digraph monkeys {\n\
+             \x20 cczh [label=\"cczh\\n4\"];\n\
+             \x20 lfqf [label=\"lfqf\\n4\"];\n\
+             \x20 pppw [label=\"pppw\\n/\"];\n\
+             \x20 pppw -> cczh;\n\
+             \x20 pppw -> lfqf;\n\
+             \x20 root [label=\"root\\n+\"];\n\
+             \x20 root -> pppw;\n\
+             \x20 root -> sjmn;\n\
+             \x20 sjmn [label=\"sjmn\\n2\"];\n\
+             }\n",
+        );
+    }
+}
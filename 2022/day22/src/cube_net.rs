@@ -0,0 +1,476 @@
+use super::util::Grid;
+use std::collections::VecDeque;
+
+pub const N_DIRECTIONS: usize = 4;
+
+// Offsets for the directions right, down, left and up, which is also
+// the order used for the facing in the password
+pub static OFFSETS: [(i32, i32); N_DIRECTIONS] = [
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+];
+
+pub const N_FACES: usize = 6;
+
+type Vec3 = [i32; 3];
+
+fn neg(v: Vec3) -> Vec3 {
+    v.map(|c| -c)
+}
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    add(a, neg(b))
+}
+
+fn dot(a: Vec3, b: Vec3) -> i32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn scale(v: Vec3, s: i32) -> Vec3 {
+    v.map(|c| c * s)
+}
+
+// Where a face ended up after folding the net into a cube. The
+// vectors are unit vectors in 3D space pointing out of the face and
+// along the directions that go right and down on the net.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Orientation {
+    normal: Vec3,
+    right: Vec3,
+    down: Vec3,
+}
+
+impl Orientation {
+    // The 3D vector for moving in a direction on the face
+    fn direction_vector(&self, direction: usize) -> Vec3 {
+        match direction {
+            0 => self.right,
+            1 => self.down,
+            2 => neg(self.right),
+            3 => neg(self.down),
+            _ => panic!("impossible direction"),
+        }
+    }
+
+    fn vector_direction(&self, v: Vec3) -> usize {
+        (0..N_DIRECTIONS).find(|&dir| self.direction_vector(dir) == v)
+            .expect("vector isn’t parallel to the face")
+    }
+
+    // Rolls the cube so that the face in the direction becomes the
+    // front face
+    fn roll(&self, direction: usize) -> Orientation {
+        let normal = self.direction_vector(direction);
+
+        match direction {
+            0 | 2 => Orientation {
+                normal,
+                right: if direction == 0 {
+                    neg(self.normal)
+                } else {
+                    self.normal
+                },
+                down: self.down,
+            },
+            _ => Orientation {
+                normal,
+                right: self.right,
+                down: if direction == 1 {
+                    neg(self.normal)
+                } else {
+                    self.normal
+                },
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaceLink {
+    pub next_face: usize,
+    // The direction of travel after crossing into the next face
+    pub direction: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Face {
+    // Position of the top-left corner in the grid
+    pub pos: (i32, i32),
+    pub links: [FaceLink; N_DIRECTIONS],
+    orientation: Orientation,
+}
+
+// A cube net that has been folded to work out which edges are joined
+// together
+#[derive(Debug, Clone)]
+pub struct CubeNet {
+    face_size: i32,
+    faces: Vec<Face>,
+    // Index of the face at each position of the net, measured in
+    // faces
+    net: Vec<Option<usize>>,
+    net_width: i32,
+}
+
+impl CubeNet {
+    // Folds a net given as a list of positions in units of faces
+    pub fn new(cells: &[(i32, i32)], face_size: i32) -> Result<CubeNet, String> {
+        if cells.len() != N_FACES {
+            return Err(format!(
+                "cube net needs {} faces but there are {}",
+                N_FACES,
+                cells.len(),
+            ));
+        }
+
+        if cells.iter().any(|&(x, y)| x < 0 || y < 0) {
+            return Err("negative position in cube net".to_string());
+        }
+
+        let net_width = cells.iter().map(|&(x, _)| x).max().unwrap() + 1;
+        let net_height = cells.iter().map(|&(_, y)| y).max().unwrap() + 1;
+        let mut net = vec![None; (net_width * net_height) as usize];
+
+        for (face_num, &(x, y)) in cells.iter().enumerate() {
+            net[(y * net_width + x) as usize] = Some(face_num);
+        }
+
+        let face_at = |(x, y): (i32, i32)| {
+            if x < 0 || y < 0 || x >= net_width || y >= net_height {
+                None
+            } else {
+                net[(y * net_width + x) as usize]
+            }
+        };
+
+        // Walk across the net rolling the cube to find the orientation
+        // of each face
+        let mut orientations = [None; N_FACES];
+        let mut queue = VecDeque::from([0]);
+
+        orientations[0] = Some(Orientation {
+            normal: [0, 0, -1],
+            right: [1, 0, 0],
+            down: [0, 1, 0],
+        });
+
+        while let Some(face_num) = queue.pop_front() {
+            let orientation = orientations[face_num].unwrap();
+            let (x, y) = cells[face_num];
+
+            for (direction, offset) in OFFSETS.iter().enumerate() {
+                let Some(next) = face_at((x + offset.0, y + offset.1))
+                else { continue; };
+
+                if orientations[next].is_none() {
+                    orientations[next] = Some(orientation.roll(direction));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let Some(orientations) = orientations.into_iter()
+            .collect::<Option<Vec<Orientation>>>()
+        else { return Err("cube net isn’t connected".to_string()); };
+
+        for (i, a) in orientations.iter().enumerate() {
+            if orientations[i + 1..].iter().any(|b| a.normal == b.normal) {
+                return Err("cube net folds with overlapping faces".to_string());
+            }
+        }
+
+        let faces = orientations.iter().enumerate().map(|(face_num, o)| {
+            let links = std::array::from_fn(|direction| {
+                // The neighbouring face is the one facing the
+                // direction we are moving in and we will end up
+                // moving away from this face
+                let v = o.direction_vector(direction);
+                let next_face = orientations.iter()
+                    .position(|other| other.normal == v)
+                    .unwrap();
+
+                FaceLink {
+                    next_face,
+                    direction: orientations[next_face]
+                        .vector_direction(neg(o.normal)),
+                }
+            });
+
+            Face {
+                pos: (
+                    cells[face_num].0 * face_size,
+                    cells[face_num].1 * face_size,
+                ),
+                links,
+                orientation: *o,
+            }
+        }).collect();
+
+        Ok(CubeNet {
+            face_size,
+            faces,
+            net,
+            net_width,
+        })
+    }
+
+    // Finds the faces in a grid where spaces are outside of the net.
+    // The face size is worked out from the area.
+    pub fn from_grid(grid: &Grid) -> Result<CubeNet, String> {
+        let area = grid.values.iter().filter(|&&b| b != b' ').count();
+        let face_size = (1..).find(|n| n * n * N_FACES >= area).unwrap();
+
+        if face_size * face_size * N_FACES != area {
+            return Err(format!("area {} can’t make a cube", area));
+        }
+
+        let mut cells = Vec::new();
+
+        for y in (0..grid.height).step_by(face_size) {
+            for x in (0..grid.width).step_by(face_size) {
+                if grid.get((x as i32, y as i32)).is_some_and(|b| b != b' ') {
+                    cells.push(((x / face_size) as i32, (y / face_size) as i32));
+                }
+            }
+        }
+
+        CubeNet::new(&cells, face_size as i32)
+    }
+
+    pub fn face_at(&self, pos: (i32, i32)) -> Option<usize> {
+        if pos.0 < 0 || pos.1 < 0 {
+            return None;
+        }
+
+        let x = pos.0 / self.face_size;
+        let y = pos.1 / self.face_size;
+
+        if x >= self.net_width {
+            return None;
+        }
+
+        self.net.get((y * self.net_width + x) as usize).copied().flatten()
+    }
+
+    // Returns the next position and direction after moving one step
+    // from a position on the net, wrapping around the edges of the
+    // cube if necessary
+    fn step(&self, pos: (i32, i32), direction: usize) -> ((i32, i32), usize) {
+        let offset = OFFSETS[direction];
+        let next_pos = (pos.0 + offset.0, pos.1 + offset.1);
+        let face_num = self.face_at(pos).expect("position not on the net");
+
+        if self.face_at(next_pos) == Some(face_num) {
+            return (next_pos, direction);
+        }
+
+        // Work out the position of the centre of the square in 3D
+        // space using doubled coordinates so that they stay integers.
+        // The cube goes from -face_size to face_size on each axis.
+        let face = &self.faces[face_num];
+        let o = &face.orientation;
+        let n = self.face_size;
+        let x = pos.0 - face.pos.0;
+        let y = pos.1 - face.pos.1;
+        let centre = add(
+            scale(o.normal, n),
+            add(scale(o.right, 2 * x + 1 - n), scale(o.down, 2 * y + 1 - n)),
+        );
+
+        // Moving over the edge moves the square half a step out in the
+        // direction of movement and half a step back from the normal
+        let link = face.links[direction];
+        let next_face = &self.faces[link.next_face];
+        let centre = sub(add(centre, o.direction_vector(direction)), o.normal);
+        let no = &next_face.orientation;
+        let x = (dot(centre, no.right) + n - 1) / 2;
+        let y = (dot(centre, no.down) + n - 1) / 2;
+
+        ((next_face.pos.0 + x, next_face.pos.1 + y), link.direction)
+    }
+}
+
+// Walks over the surface of a folded cube using the positions and
+// directions of the net
+#[derive(Debug, Clone, Copy)]
+pub struct Mover<'a> {
+    net: &'a CubeNet,
+    pub pos: (i32, i32),
+    pub direction: usize,
+}
+
+impl<'a> Mover<'a> {
+    pub fn new(net: &'a CubeNet, pos: (i32, i32), direction: usize) -> Mover<'a> {
+        Mover { net, pos, direction }
+    }
+
+    // Returns the position and direction after moving forward without
+    // actually moving
+    pub fn peek(&self) -> ((i32, i32), usize) {
+        self.net.step(self.pos, self.direction)
+    }
+
+    pub fn forward(&mut self) {
+        (self.pos, self.direction) = self.peek();
+    }
+
+    pub fn turn_left(&mut self) {
+        self.direction = (self.direction + N_DIRECTIONS - 1) % N_DIRECTIONS;
+    }
+
+    pub fn turn_right(&mut self) {
+        self.direction = (self.direction + 1) % N_DIRECTIONS;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // All 11 distinct cube nets
+    static NETS: [&str; 11] = [
+        "#...\n####\n#...",
+        "#...\n####\n.#..",
+        "#...\n####\n..#.",
+        "#...\n####\n...#",
+        ".#..\n####\n.#..",
+        ".#..\n####\n..#.",
+        "##..\n.###\n.#..",
+        "##..\n.###\n..#.",
+        "##..\n.###\n...#",
+        "##..\n.##.\n..##",
+        "###...\n..###.",
+    ];
+
+    fn net_cells(net: &str) -> Vec<(i32, i32)> {
+        net.lines().enumerate().flat_map(|(y, line)| {
+            line.char_indices()
+                .filter(|&(_, ch)| ch == '#')
+                .map(move |(x, _)| (x as i32, y as i32))
+        }).collect()
+    }
+
+    // Returns the cells transformed with each of the 8 rotations and
+    // reflections of the square, moved so that the minimum
+    // coordinates are zero
+    fn symmetries(cells: &[(i32, i32)]) -> Vec<Vec<(i32, i32)>> {
+        (0..8).map(|sym| {
+            let transformed = cells.iter().map(|&(x, y)| {
+                let (x, y) = if sym & 1 != 0 { (y, x) } else { (x, y) };
+                let x = if sym & 2 != 0 { -x } else { x };
+                let y = if sym & 4 != 0 { -y } else { y };
+                (x, y)
+            }).collect::<Vec<_>>();
+            let min_x = transformed.iter().map(|p| p.0).min().unwrap();
+            let min_y = transformed.iter().map(|p| p.1).min().unwrap();
+
+            let mut transformed = transformed.into_iter()
+                .map(|(x, y)| (x - min_x, y - min_y))
+                .collect::<Vec<_>>();
+
+            // Make sure that a different face is first too
+            transformed.sort_unstable_by_key(|&(x, y)| (y, x));
+            transformed.rotate_left(sym % N_FACES);
+
+            transformed
+        }).collect()
+    }
+
+    fn check_net(net: &CubeNet) {
+        let n = net.face_size;
+
+        for (face_num, face) in net.faces.iter().enumerate() {
+            let mut neighbours = face.links.map(|link| link.next_face);
+            neighbours.sort_unstable();
+
+            assert!(!neighbours.contains(&face_num));
+            assert!(neighbours.windows(2).all(|w| w[0] != w[1]));
+
+            for y in 0..n {
+                for x in 0..n {
+                    let pos = (face.pos.0 + x, face.pos.1 + y);
+
+                    for direction in 0..N_DIRECTIONS {
+                        // Going round the cube should get back to
+                        // the start
+                        let mut mover = Mover::new(net, pos, direction);
+
+                        for _ in 0..n * 4 {
+                            mover.forward();
+                            assert!(net.face_at(mover.pos).is_some());
+                        }
+
+                        assert_eq!((mover.pos, mover.direction), (pos, direction));
+
+                        // Stepping and stepping back should also get
+                        // back to the start
+                        let mut mover = Mover::new(net, pos, direction);
+                        mover.forward();
+                        mover.turn_left();
+                        mover.turn_left();
+                        mover.forward();
+                        mover.turn_right();
+                        mover.turn_right();
+
+                        assert_eq!((mover.pos, mover.direction), (pos, direction));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all_nets() {
+        for net in NETS.iter() {
+            for cells in symmetries(&net_cells(net)) {
+                for face_size in 1..=4 {
+                    check_net(&CubeNet::new(&cells, face_size).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_nets() {
+        assert_eq!(
+            CubeNet::new(&net_cells("##\n##\n##"), 2).unwrap_err(),
+            "cube net folds with overlapping faces",
+        );
+        assert_eq!(
+            CubeNet::new(&net_cells("####\n#..#"), 2).unwrap_err(),
+            "cube net folds with overlapping faces",
+        );
+        assert_eq!(
+            CubeNet::new(&net_cells("###\n...\n###"), 2).unwrap_err(),
+            "cube net isn’t connected",
+        );
+        assert_eq!(
+            CubeNet::new(&net_cells("####\n.#.."), 2).unwrap_err(),
+            "cube net needs 6 faces but there are 5",
+        );
+    }
+
+    #[test]
+    fn example_edges() {
+        let net = CubeNet::new(&net_cells("..#.\n###.\n..##"), 4).unwrap();
+
+        // The examples from the puzzle description
+        let mut mover = Mover::new(&net, (11, 5), 0);
+        mover.forward();
+        assert_eq!((mover.pos, mover.direction), ((14, 8), 1));
+
+        let mut mover = Mover::new(&net, (10, 11), 1);
+        mover.forward();
+        assert_eq!((mover.pos, mover.direction), ((1, 7), 3));
+
+        let mut mover = Mover::new(&net, (6, 4), 3);
+        mover.forward();
+        assert_eq!((mover.pos, mover.direction), ((8, 2), 0));
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::cell::OnceCell;
+
+pub type EntryId = usize;
+
+#[derive(Debug, Clone)]
+pub enum EntryData {
+    File { size: usize },
+    Directory { children: BTreeMap<String, EntryId> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub parent: Option<EntryId>,
+    pub data: EntryData,
+}
+
+// A directory tree stored in a vector. Entries are never removed so a
+// parent always has a lower ID than its children.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    entries: Vec<Entry>,
+    // The recursive size of every entry. This is calculated the first
+    // time a size is needed and thrown away when the tree changes.
+    sizes: OnceCell<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Directory,
+}
+
+// Conditions for FileSystem::find, similar to the arguments of the
+// find command
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query {
+    entry_type: Option<EntryType>,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+}
+
+impl Entry {
+    pub fn entry_type(&self) -> EntryType {
+        match self.data {
+            EntryData::File { .. } => EntryType::File,
+            EntryData::Directory { .. } => EntryType::Directory,
+        }
+    }
+}
+
+impl FileSystem {
+    pub const ROOT: EntryId = 0;
+
+    pub fn new() -> FileSystem {
+        FileSystem {
+            entries: vec![Entry {
+                name: String::new(),
+                parent: None,
+                data: EntryData::Directory { children: BTreeMap::new() },
+            }],
+            sizes: OnceCell::new(),
+        }
+    }
+
+    pub fn entry(&self, id: EntryId) -> &Entry {
+        &self.entries[id]
+    }
+
+    pub fn child(&self, dir: EntryId, name: &str) -> Option<EntryId> {
+        match self.entries[dir].data {
+            EntryData::Directory { ref children } => children.get(name).copied(),
+            EntryData::File { .. } => None,
+        }
+    }
+
+    fn add_entry(
+        &mut self,
+        dir: EntryId,
+        name: &str,
+        data: EntryData,
+    ) -> Result<EntryId, String> {
+        if let Some(old) = self.child(dir, name) {
+            // Listing the same directory again is fine as long as it
+            // agrees with what we saw the first time
+            return match (&self.entries[old].data, &data) {
+                (EntryData::Directory { .. }, EntryData::Directory { .. }) => {
+                    Ok(old)
+                },
+                (
+                    &EntryData::File { size: old_size },
+                    &EntryData::File { size },
+                ) => {
+                    if old_size == size {
+                        Ok(old)
+                    } else {
+                        Err(format!(
+                            "“{}” listed with size {} but was {} before",
+                            self.path(old),
+                            size,
+                            old_size,
+                        ))
+                    }
+                },
+                _ => Err(format!(
+                    "“{}” listed as both a file and a directory",
+                    self.path(old),
+                )),
+            };
+        }
+
+        let id = self.entries.len();
+
+        let EntryData::Directory { ref mut children } = self.entries[dir].data
+        else { return Err(format!("“{}” is not a directory", self.path(dir))); };
+
+        children.insert(name.to_string(), id);
+
+        self.entries.push(Entry {
+            name: name.to_string(),
+            parent: Some(dir),
+            data,
+        });
+
+        self.sizes.take();
+
+        Ok(id)
+    }
+
+    pub fn add_file(
+        &mut self,
+        dir: EntryId,
+        name: &str,
+        size: usize,
+    ) -> Result<EntryId, String> {
+        self.add_entry(dir, name, EntryData::File { size })
+    }
+
+    pub fn add_directory(
+        &mut self,
+        dir: EntryId,
+        name: &str,
+    ) -> Result<EntryId, String> {
+        self.add_entry(
+            dir,
+            name,
+            EntryData::Directory { children: BTreeMap::new() },
+        )
+    }
+
+    // Finds an entry from an absolute path like “/a/b.txt”
+    pub fn lookup(&self, path: &str) -> Option<EntryId> {
+        let path = path.strip_prefix('/')?;
+        let mut id = FileSystem::ROOT;
+
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            id = self.child(id, part)?;
+        }
+
+        Some(id)
+    }
+
+    pub fn path(&self, mut id: EntryId) -> String {
+        let mut parts = Vec::new();
+
+        while let Some(parent) = self.entries[id].parent {
+            parts.push(self.entries[id].name.as_str());
+            id = parent;
+        }
+
+        if parts.is_empty() {
+            return "/".to_string();
+        }
+
+        parts.iter().rev().map(|part| format!("/{}", part)).collect()
+    }
+
+    fn calculate_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.entries.len()];
+
+        // Children always come after their parents so going backwards
+        // means every entry is complete before it is added to its
+        // parent
+        for (id, entry) in self.entries.iter().enumerate().rev() {
+            if let EntryData::File { size } = entry.data {
+                sizes[id] += size;
+            }
+
+            if let Some(parent) = entry.parent {
+                sizes[parent] += sizes[id];
+            }
+        }
+
+        sizes
+    }
+
+    // Returns the size of a file or the total size of everything
+    // inside a directory
+    pub fn size(&self, id: EntryId) -> usize {
+        self.sizes.get_or_init(|| self.calculate_sizes())[id]
+    }
+
+    pub fn find(&self, query: Query) -> impl Iterator<Item = EntryId> + '_ {
+        (0..self.entries.len()).filter(move |&id| query.matches(self, id))
+    }
+
+    // Writes the tree in a similar format to the tree command
+    pub fn write_tree<W: std::io::Write>(&self, out: &mut W) ->
+        std::io::Result<()>
+    {
+        writeln!(out, "/ (dir, size={})", self.size(FileSystem::ROOT))?;
+
+        // Each stack entry is the remaining children of a directory
+        let mut stack = vec![self.children(FileSystem::ROOT)];
+
+        while let Some(children) = stack.last_mut() {
+            let Some(id) = children.next()
+            else {
+                stack.pop();
+                continue;
+            };
+
+            let is_last = children.len() == 0;
+
+            for parent_children in &stack[0..stack.len() - 1] {
+                if parent_children.len() == 0 {
+                    write!(out, "    ")?;
+                } else {
+                    write!(out, "│   ")?;
+                }
+            }
+
+            let entry = &self.entries[id];
+
+            write!(
+                out,
+                "{} {} ",
+                if is_last { "└──" } else { "├──" },
+                entry.name,
+            )?;
+
+            match entry.data {
+                EntryData::File { size } => writeln!(out, "(size={})", size)?,
+                EntryData::Directory { .. } => {
+                    writeln!(out, "(dir, size={})", self.size(id))?;
+                    stack.push(self.children(id));
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn children(&self, dir: EntryId) -> std::vec::IntoIter<EntryId> {
+        match self.entries[dir].data {
+            EntryData::Directory { ref children } => {
+                children.values().copied().collect::<Vec<_>>().into_iter()
+            },
+            EntryData::File { .. } => Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Default for FileSystem {
+    fn default() -> FileSystem {
+        FileSystem::new()
+    }
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn entry_type(self, entry_type: EntryType) -> Query {
+        Query { entry_type: Some(entry_type), ..self }
+    }
+
+    // Like find -size +N
+    pub fn min_size(self, size: usize) -> Query {
+        Query { min_size: Some(size), ..self }
+    }
+
+    // Like find -size -N
+    pub fn max_size(self, size: usize) -> Query {
+        Query { max_size: Some(size), ..self }
+    }
+
+    fn matches(&self, fs: &FileSystem, id: EntryId) -> bool {
+        if self.entry_type.is_some_and(|t| t != fs.entry(id).entry_type()) {
+            return false;
+        }
+
+        if self.min_size.is_none() && self.max_size.is_none() {
+            return true;
+        }
+
+        let size = fs.size(id);
+
+        self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example() -> FileSystem {
+        let mut fs = FileSystem::new();
+        let root = FileSystem::ROOT;
+
+        let a = fs.add_directory(root, "a").unwrap();
+        fs.add_file(root, "b.txt", 14848514).unwrap();
+        fs.add_file(root, "c.dat", 8504156).unwrap();
+        let d = fs.add_directory(root, "d").unwrap();
+        let e = fs.add_directory(a, "e").unwrap();
+        fs.add_file(a, "f", 29116).unwrap();
+        fs.add_file(a, "g", 2557).unwrap();
+        fs.add_file(a, "h.lst", 62596).unwrap();
+        fs.add_file(e, "i", 584).unwrap();
+        fs.add_file(d, "j", 4060174).unwrap();
+        fs.add_file(d, "d.log", 8033020).unwrap();
+        fs.add_file(d, "d.ext", 5626152).unwrap();
+        fs.add_file(d, "k", 7214296).unwrap();
+
+        fs
+    }
+
+    #[test]
+    fn lookup() {
+        let fs = example();
+
+        assert_eq!(fs.lookup("/"), Some(FileSystem::ROOT));
+        assert_eq!(fs.path(FileSystem::ROOT), "/");
+
+        for path in ["/a", "/a/e", "/a/e/i", "/d/k", "/b.txt"] {
+            let id = fs.lookup(path).unwrap();
+            assert_eq!(fs.path(id), path);
+        }
+
+        assert_eq!(fs.lookup("/a/e/"), fs.lookup("/a/e"));
+        assert_eq!(fs.lookup("/a/z"), None);
+        assert_eq!(fs.lookup("/b.txt/z"), None);
+        assert_eq!(fs.lookup("a"), None);
+    }
+
+    #[test]
+    fn sizes() {
+        let mut fs = example();
+
+        assert_eq!(fs.size(fs.lookup("/a/e").unwrap()), 584);
+        assert_eq!(fs.size(fs.lookup("/a").unwrap()), 94853);
+        assert_eq!(fs.size(fs.lookup("/d").unwrap()), 24933642);
+        assert_eq!(fs.size(FileSystem::ROOT), 48381165);
+
+        let small = fs.find(
+            Query::new()
+                .entry_type(EntryType::Directory)
+                .max_size(100_000)
+        ).map(|id| fs.path(id)).collect::<Vec<_>>();
+        assert_eq!(small, ["/a", "/a/e"]);
+
+        let big_files = fs.find(
+            Query::new().entry_type(EntryType::File).min_size(8_000_000)
+        ).map(|id| fs.path(id)).collect::<Vec<_>>();
+        assert_eq!(big_files, ["/b.txt", "/c.dat", "/d/d.log"]);
+
+        assert_eq!(
+            fs.find(Query::new().entry_type(EntryType::Directory)).count(),
+            4,
+        );
+
+        // Adding a file should update the cached sizes
+        let e = fs.lookup("/a/e").unwrap();
+        fs.add_file(e, "new", 16).unwrap();
+        assert_eq!(fs.size(e), 600);
+        assert_eq!(fs.size(FileSystem::ROOT), 48381181);
+    }
+
+    #[test]
+    fn inconsistent() {
+        let mut fs = example();
+        let d = fs.lookup("/d").unwrap();
+
+        // Listing the same thing again is fine
+        let k = fs.lookup("/d/k").unwrap();
+        assert_eq!(fs.add_file(d, "k", 7214296), Ok(k));
+        assert_eq!(fs.add_directory(FileSystem::ROOT, "d"), Ok(d));
+
+        assert_eq!(
+            fs.add_file(d, "k", 12),
+            Err("“/d/k” listed with size 12 but was 7214296 before"
+                .to_string()),
+        );
+        assert_eq!(
+            fs.add_directory(d, "k"),
+            Err("“/d/k” listed as both a file and a directory".to_string()),
+        );
+        assert_eq!(
+            fs.add_file(fs.lookup("/d/k").unwrap(), "z", 1),
+            Err("“/d/k” is not a directory".to_string()),
+        );
+    }
+
+    #[test]
+    fn tree() {
+        let mut out = Vec::<u8>::new();
+
+        example().write_tree(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/ (dir, size=48381165)\n\
+             ├── a (dir, size=94853)\n\
+             │   ├── e (dir, size=584)\n\
+             │   │   └── i (size=584)\n\
+             │   ├── f (size=29116)\n\
+             │   ├── g (size=2557)\n\
+             │   └── h.lst (size=62596)\n\
+             ├── b.txt (size=14848514)\n\
+             ├── c.dat (size=8504156)\n\
+             └── d (dir, size=24933642)\n    \
+             ├── d.ext (size=5626152)\n    \
+             ├── d.log (size=8033020)\n    \
+             ├── j (size=4060174)\n    \
+             └── k (size=7214296)\n",
+        );
+    }
+}
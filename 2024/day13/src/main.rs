@@ -1,3 +1,5 @@
+mod parallel;
+
 use std::process::ExitCode;
 use std::sync::LazyLock;
 use std::str::FromStr;
@@ -82,9 +84,13 @@ fn read_claw_machines() -> Result<Vec<ClawMachine>, String> {
     Ok(machines)
 }
 
-fn best_cost(machines: &[ClawMachine]) -> u64 {
-    machines.iter().filter_map(|machine| {
-        machine.best_strategy().map(|(a, b)| {
+fn best_cost(machines: &[ClawMachine], n_threads: usize) -> u64 {
+    let strategies = parallel::map(machines, n_threads, |machine| {
+        machine.best_strategy()
+    });
+
+    machines.iter().zip(strategies).filter_map(|(machine, strategy)| {
+        strategy.map(|(a, b)| {
             println!("{},{} for {:?}", a, b, machine);
             a * 3 + b
         })
@@ -92,6 +98,14 @@ fn best_cost(machines: &[ClawMachine]) -> u64 {
 }
 
 fn main() -> ExitCode {
+    let n_threads = match parallel::parse_threads_arg() {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+    };
+
     let machines = match read_claw_machines() {
         Ok(m) => m,
         Err(e) => {
@@ -100,7 +114,7 @@ fn main() -> ExitCode {
         },
     };
 
-    println!("Part 1: {}", best_cost(&machines));
+    println!("Part 1: {}", best_cost(&machines, n_threads));
 
     let machines = machines.into_iter().map(|mut machine| {
         machine.prize.0 += 10000000000000;
@@ -108,7 +122,7 @@ fn main() -> ExitCode {
         machine
     }).collect::<Vec<_>>();
 
-    println!("Part 2: {}", best_cost(&machines));
+    println!("Part 2: {}", best_cost(&machines, n_threads));
 
     ExitCode::SUCCESS
 }
@@ -1,66 +1,12 @@
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum Button {
-    D0,
-    D1,
-    D2,
-    D3,
-    D4,
-    D5,
-    D6,
-    D7,
-    D8,
-    D9,
-    A,
-    Up,
-    Down,
-    Left,
-    Right,
-    Space,
-}
-
-impl Button {
-    fn as_char(&self) -> char {
-        match self {
-            Button::D0 => '0',
-            Button::D1 => '1',
-            Button::D2 => '2',
-            Button::D3 => '3',
-            Button::D4 => '4',
-            Button::D5 => '5',
-            Button::D6 => '6',
-            Button::D7 => '7',
-            Button::D8 => '8',
-            Button::D9 => '9',
-            Button::A => 'A',
-            Button::Up => '^',
-            Button::Down => 'v',
-            Button::Left => '<',
-            Button::Right => '>',
-            Button::Space => ' ',
-        }
-    }
-}
-
-const DPAD_WIDTH: usize = 3;
-const DPAD_HEIGHT: usize = 2;
-const KEYPAD_WIDTH: usize = 3;
-const KEYPAD_HEIGHT: usize = 4;
+mod keypad;
 
-static DPAD_BUTTONS: [Button; DPAD_WIDTH * DPAD_HEIGHT] = [
-    Button::Space, Button::Up,    Button::A,
-    Button::Left,  Button::Down,  Button::Right,
-];
-
-static KEYPAD_BUTTONS: [Button; KEYPAD_WIDTH * KEYPAD_HEIGHT] = [
-    Button::D7,    Button::D8,    Button::D9,
-    Button::D4,    Button::D5,    Button::D6,
-    Button::D1,    Button::D2,    Button::D3,
-    Button::Space, Button::D0,    Button::A,
-];
+use keypad::Keypad;
 
 struct Game {
-    dpad_positions: [(u8, u8); 2],
-    keypad_position: (u8, u8),
+    dpad: Keypad,
+    keypad: Keypad,
+    dpad_positions: [(usize, usize); 2],
+    keypad_position: (usize, usize),
     should_quit: bool,
     history: String,
     code: String,
@@ -68,9 +14,16 @@ struct Game {
 
 impl Game {
     fn new() -> Game {
+        let dpad = Keypad::from_layout(keypad::DIRECTIONAL_LAYOUT).unwrap();
+        let keypad = Keypad::from_layout(keypad::NUMERIC_LAYOUT).unwrap();
+        let dpad_a = dpad.position(b'A').unwrap();
+        let keypad_a = keypad.position(b'A').unwrap();
+
         Game {
-            dpad_positions: [(2, 0), (2, 0)],
-            keypad_position: (2, 3),
+            dpad,
+            keypad,
+            dpad_positions: [dpad_a, dpad_a],
+            keypad_position: keypad_a,
             should_quit: false,
             history: String::new(),
             code: String::new(),
@@ -79,10 +32,10 @@ impl Game {
 
     fn handle_key_code(&mut self, key: i32) {
         match key {
-            ncurses::KEY_UP => self.move_cursor(Button::Up),
-            ncurses::KEY_DOWN => self.move_cursor(Button::Down),
-            ncurses::KEY_LEFT => self.move_cursor(Button::Left),
-            ncurses::KEY_RIGHT => self.move_cursor(Button::Right),
+            ncurses::KEY_UP => self.move_cursor(b'^'),
+            ncurses::KEY_DOWN => self.move_cursor(b'v'),
+            ncurses::KEY_LEFT => self.move_cursor(b'<'),
+            ncurses::KEY_RIGHT => self.move_cursor(b'>'),
             _ => (),
         }
     }
@@ -92,10 +45,10 @@ impl Game {
             match ch {
                 '\u{0003}' => self.should_quit = true, // Ctrl+C
                 '\n' | 'A' => self.activate(),
-                '^' => self.move_cursor(Button::Up),
-                'v' => self.move_cursor(Button::Down),
-                '<' => self.move_cursor(Button::Left),
-                '>' => self.move_cursor(Button::Right),
+                '^' => self.move_cursor(b'^'),
+                'v' => self.move_cursor(b'v'),
+                '<' => self.move_cursor(b'<'),
+                '>' => self.move_cursor(b'>'),
                 _ => (),
             }
         }
@@ -108,34 +61,26 @@ impl Game {
         }
     }
 
-    fn draw_dpad(&self, start_x: i32, start_y: i32, cursor_pos: (u8, u8)) {
-        self.draw_buttons(
-            DPAD_WIDTH,
-            &DPAD_BUTTONS,
-            start_x, start_y,
-            cursor_pos,
-        );
-    }
-
     fn draw_buttons(
         &self,
-        width: usize,
-        buttons: &[Button],
+        keypad: &Keypad,
         start_x: i32,
         start_y: i32,
-        cursor_pos: (u8, u8),
+        cursor_pos: (usize, usize),
     ) {
-        for y in 0..buttons.len() / width {
+        for y in 0..keypad.height() {
             ncurses::mv(start_y + y as i32, start_x);
 
-            for x in 0..width {
-                if x == cursor_pos.0 as usize && y == cursor_pos.1 as usize {
+            for x in 0..keypad.width() {
+                let ch = keypad.button_at((x, y)).map_or(' ', |b| b as char);
+
+                if (x, y) == cursor_pos {
                     ncurses::addch('(' as u32);
-                    addch_utf8(buttons[y * width + x].as_char());
+                    addch_utf8(ch);
                     ncurses::addch(')' as u32);
                 } else {
                     ncurses::addch(' ' as u32);
-                    addch_utf8(buttons[y * width + x].as_char());
+                    addch_utf8(ch);
                     ncurses::addch(' ' as u32);
                 }
             }
@@ -145,15 +90,9 @@ impl Game {
     fn redraw(&self) {
         ncurses::clear();
 
-        self.draw_dpad(0, 0, self.dpad_positions[0]);
-        self.draw_dpad(0, 4, self.dpad_positions[1]);
-
-        self.draw_buttons(
-            KEYPAD_WIDTH,
-            &KEYPAD_BUTTONS,
-            0, 8,
-            self.keypad_position,
-        );
+        self.draw_buttons(&self.dpad, 0, 0, self.dpad_positions[0]);
+        self.draw_buttons(&self.dpad, 0, 4, self.dpad_positions[1]);
+        self.draw_buttons(&self.keypad, 0, 8, self.keypad_position);
 
         let _ = ncurses::mvaddstr(14, 2, "History: ");
         let _ = ncurses::addstr(&self.history);
@@ -163,31 +102,21 @@ impl Game {
         ncurses::refresh();
     }
 
-    fn move_cursor(&mut self, button: Button) {
-        move_position(
-            button,
-            &mut self.dpad_positions[0],
-            DPAD_WIDTH,
-            DPAD_HEIGHT,
-        );
+    fn move_cursor(&mut self, direction: u8) {
+        move_position(&self.dpad, direction, &mut self.dpad_positions[0]);
 
-        self.history.push(button.as_char());
+        self.history.push(direction as char);
 
         self.redraw();
     }
 
     fn activate(&mut self) {
-        let pos = self.dpad_positions[0];
-        let button = DPAD_BUTTONS[pos.1 as usize * DPAD_WIDTH + pos.0 as usize];
+        let button = self.dpad.button_at(self.dpad_positions[0]).unwrap();
 
-        if button == Button::A {
+        if button == b'A' {
             self.activate_second_dpad();
         } else {
-            move_position(
-                button,
-                &mut self.dpad_positions[1],
-                DPAD_WIDTH, DPAD_HEIGHT,
-            );
+            move_position(&self.dpad, button, &mut self.dpad_positions[1]);
         }
 
         self.history.push('A');
@@ -196,43 +125,27 @@ impl Game {
     }
 
     fn activate_second_dpad(&mut self) {
-        let pos = self.dpad_positions[1];
-        let button = DPAD_BUTTONS[pos.1 as usize * DPAD_WIDTH + pos.0 as usize];
+        let button = self.dpad.button_at(self.dpad_positions[1]).unwrap();
 
-        if button == Button::A {
+        if button == b'A' {
             self.activate_keypad();
         } else {
-            move_position(
-                button,
-                &mut self.keypad_position,
-                KEYPAD_WIDTH, KEYPAD_HEIGHT,
-            );
+            move_position(&self.keypad, button, &mut self.keypad_position);
         }
     }
 
     fn activate_keypad(&mut self) {
-        let pos = self.keypad_position;
-        let button = KEYPAD_BUTTONS[
-            pos.1 as usize * KEYPAD_WIDTH +
-                pos.0 as usize
-        ];
+        let button = self.keypad.button_at(self.keypad_position).unwrap();
 
-        self.code.push(button.as_char());
+        self.code.push(button as char);
     }
 }
 
-fn move_position(
-    button: Button,
-    pos: &mut (u8, u8),
-    width: usize,
-    height: usize,
-) {
-    match button {
-        Button::Left => pos.0 = pos.0.saturating_sub(1),
-        Button::Right => pos.0 = (pos.0 + 1).min(width as u8 - 1),
-        Button::Up => pos.1 = pos.1.saturating_sub(1),
-        Button::Down => pos.1 = (pos.1 + 1).min(height as u8 - 1),
-        _=> (),
+// Moves the arm unless that would make it point at a gap or leave the
+// keypad
+fn move_position(keypad: &Keypad, direction: u8, pos: &mut (usize, usize)) {
+    if let Some(next_pos) = keypad.step(*pos, direction) {
+        *pos = next_pos;
     }
 }
 
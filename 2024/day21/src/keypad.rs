@@ -0,0 +1,137 @@
+// A button that isn’t there. The robot arms panic if they point at it.
+pub const GAP: u8 = b'#';
+
+pub static NUMERIC_LAYOUT: &str = "789\n\
+                                   456\n\
+                                   123\n\
+                                   #0A\n";
+
+pub static DIRECTIONAL_LAYOUT: &str = "#^A\n\
+                                       <v>\n";
+
+// The buttons on a directional keypad and the direction that each one
+// moves the arm pointing at the keypad above
+pub static DIRECTIONS: [(u8, i32, i32); 4] = [
+    (b'^', 0, -1),
+    (b'v', 0, 1),
+    (b'<', -1, 0),
+    (b'>', 1, 0),
+];
+
+#[derive(Debug, Clone)]
+pub struct Keypad {
+    width: usize,
+    height: usize,
+    buttons: Vec<u8>,
+}
+
+impl Keypad {
+    // Builds a keypad from a text grid with one character for each
+    // button and GAP for the spaces without a button
+    pub fn from_layout(layout: &str) -> Result<Keypad, String> {
+        let lines = layout.lines().collect::<Vec<_>>();
+        let width = lines.first().map_or(0, |line| line.len());
+
+        if width == 0 {
+            return Err("empty keypad layout".to_string());
+        }
+
+        let mut buttons = Vec::with_capacity(width * lines.len());
+
+        for (line_num, line) in lines.iter().enumerate() {
+            if line.len() != width {
+                return Err(format!(
+                    "line {}: keypad layout lines have different lengths",
+                    line_num + 1,
+                ));
+            }
+
+            for &button in line.as_bytes() {
+                if button != GAP && buttons.contains(&button) {
+                    return Err(format!(
+                        "button “{}” is on the keypad twice",
+                        button as char,
+                    ));
+                }
+
+                buttons.push(button);
+            }
+        }
+
+        if !buttons.contains(&b'A') {
+            return Err("keypad has no A button".to_string());
+        }
+
+        Ok(Keypad { width, height: lines.len(), buttons })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Returns None if the position is off the keypad or on a gap
+    pub fn button_at(&self, (x, y): (usize, usize)) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let button = self.buttons[y * self.width + x];
+
+        (button != GAP).then_some(button)
+    }
+
+    pub fn position(&self, button: u8) -> Option<(usize, usize)> {
+        if button == GAP {
+            return None;
+        }
+
+        self.buttons.iter()
+            .position(|&b| b == button)
+            .map(|index| (index % self.width, index / self.width))
+    }
+
+    // Moves the position in the direction for a directional button.
+    // Returns None if that would leave the keypad or point at a gap.
+    pub fn step(
+        &self,
+        (x, y): (usize, usize),
+        direction: u8,
+    ) -> Option<(usize, usize)> {
+        let &(_, dx, dy) = DIRECTIONS.iter().find(|d| d.0 == direction)?;
+        let pos = (
+            x.checked_add_signed(dx as isize)?,
+            y.checked_add_signed(dy as isize)?,
+        );
+
+        self.button_at(pos).map(|_| pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let keypad = Keypad::from_layout(NUMERIC_LAYOUT).unwrap();
+
+        assert_eq!(keypad.width(), 3);
+        assert_eq!(keypad.height(), 4);
+        assert_eq!(keypad.position(b'A'), Some((2, 3)));
+        assert_eq!(keypad.position(b'7'), Some((0, 0)));
+        assert_eq!(keypad.position(GAP), None);
+        assert_eq!(keypad.button_at((0, 3)), None);
+        assert_eq!(keypad.button_at((3, 0)), None);
+        assert_eq!(keypad.step((1, 3), b'<'), None);
+        assert_eq!(keypad.step((1, 3), b'^'), Some((1, 2)));
+
+        assert!(Keypad::from_layout("").is_err());
+        assert!(Keypad::from_layout("12\n3A\n4").is_err());
+        assert!(Keypad::from_layout("1A\n1#").is_err());
+        assert!(Keypad::from_layout("12\n34").is_err());
+    }
+}
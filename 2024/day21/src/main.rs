@@ -1,264 +1,114 @@
-use std::process::ExitCode;
-use std::fmt;
-
-static DIGIT_POSITIONS: [(u8, u8); 10] = [
-    (1, 3), // 0
-
-    (0, 2), // 1
-    (1, 2), // 2
-    (2, 2), // 3
-
-    (0, 1), // 4
-    (1, 1), // 5
-    (2, 1), // 6
-
-    (7, 0), // 7
-    (8, 0), // 8
-    (9, 0), // 9
-];
-
-const KEYPAD_A: (u8, u8) = (2, 3);
-const KEYPAD_BAD: (u8, u8) = (0, 3);
-
-const DPAD_A: (u8, u8) = (2, 0);
-const DPAD_BAD: (u8, u8) = (0, 0);
-
-#[derive(Copy, Clone, Eq, PartialEq)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
-impl Direction {
-    fn as_char(&self) -> char {
-        match self {
-            Direction::Up => '^',
-            Direction::Down => 'v',
-            Direction::Left => '<',
-            Direction::Right => '>',
-        }
-    }
+mod keypad;
+mod robots;
 
-    fn as_position(&self) -> (u8, u8) {
-        match self {
-            Direction::Up => (1, 0),
-            Direction::Down => (1, 1),
-            Direction::Left => (0, 1),
-            Direction::Right => (2, 1),
-        }
-    }
-}
-
-impl fmt::Display for Direction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.as_char())
-    }
-}
-
-struct Movements {
-    n_vertical: u8,
-    vertical_direction: Direction,
-    n_horizontal: u8,
-    horizontal_direction: Direction,
-
-    option: u16,
-    n_options: u16,
+use std::process::ExitCode;
+use keypad::Keypad;
+use robots::Chain;
 
-    bad_option: u16,
+struct Args {
+    door_layout: String,
+    remote_layout: String,
 }
 
-impl Movements {
-    fn new(source: (u8, u8), dest: (u8, u8), bad: (u8, u8)) -> Movements {
-        let n_horizontal = source.0.abs_diff(dest.0);
-        let n_vertical = source.1.abs_diff(dest.1);
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut door_layout = keypad::NUMERIC_LAYOUT.to_string();
+    let mut remote_layout = keypad::DIRECTIONAL_LAYOUT.to_string();
 
-        let horizontal_direction = if dest.0 < source.0 {
-            Direction::Left
-        } else {
-            Direction::Right
+    while let Some(arg) = args.next() {
+        let layout = match arg.as_str() {
+            "--door" => &mut door_layout,
+            "--remote" => &mut remote_layout,
+            _ => return Err(format!("unknown argument: {}", arg)),
         };
 
-        let vertical_direction = if dest.1 < source.1 {
-            Direction::Up
-        } else {
-            Direction::Down
+        let Some(filename) = args.next()
+        else {
+            return Err(format!("{} needs a keypad layout file", arg));
         };
 
-        let bad_option = if (source.0 == bad.0 || dest.0 == bad.0) &&
-            (source.1 == bad.1 || dest.1 == bad.1)
-        {
-            // We’re assuming the bad button is on a corner of the
-            // grid and that it’s not the destination or the source,
-            // so in that case if it’s an option then it’s either on
-            // the same column or row as the start button and the bad
-            // path would be to move all the way along that axis
-            // first.
-            if source.0 == bad.0 {
-                // Don’t do all the Y movements first
-                (1 << n_vertical) - 1
-            } else {
-                // Don’t do all the X movements first
-                ((1 << n_vertical) - 1) << n_horizontal
-            }
-        } else {
-            u16::MAX
-        };
-
-        Movements {
-            n_vertical,
-            n_horizontal,
-            horizontal_direction,
-            vertical_direction,
-
-            option: 0,
-            n_options: 1u16 << (n_vertical + n_horizontal),
-
-            bad_option,
-        }
+        *layout = std::fs::read_to_string(&filename)
+            .map_err(|e| format!("{}: {}", filename, e))?;
     }
+
+    Ok(Args { door_layout, remote_layout })
 }
 
-impl Iterator for Movements {
-    type Item = Path;
+fn read_codes() -> Result<Vec<String>, String> {
+    let mut codes = Vec::new();
 
-    fn next(&mut self) -> Option<Path> {
-        while self.option < self.n_options {
-            let option = self.option;
-            self.option += 1;
+    for result in std::io::stdin().lines() {
+        let line = result.map_err(|e| e.to_string())?;
+        let code = line.trim();
 
-            if option != self.bad_option &&
-                option.count_ones() == self.n_vertical as u32
-            {
-                return Some(Path::new(
-                    self.n_vertical + self.n_horizontal,
-                    self.horizontal_direction,
-                    self.vertical_direction,
-                    option
-                ));
-            }
+        if !code.is_empty() {
+            codes.push(code.to_string());
         }
-
-        None
     }
-}
 
-#[derive(Clone)]
-struct Path {
-    n_entries: u8,
-    horizontal_direction: Direction,
-    vertical_direction: Direction,
-    pattern: u16,
+    Ok(codes)
+}
 
-    pos: u8,
+fn numeric_part(code: &str) -> u64 {
+    code.chars()
+        .filter_map(|ch| ch.to_digit(10))
+        .fold(0, |value, digit| value * 10 + digit as u64)
 }
 
-impl Path {
-    fn new(
-        n_entries: u8,
-        horizontal_direction: Direction,
-        vertical_direction: Direction,
-        pattern: u16,
-    ) -> Path {
-        Path {
-            n_entries,
-            horizontal_direction,
-            vertical_direction,
-            pattern,
-            pos: 0,
-        }
-    }
+fn complexity(chain: &Chain, code: &str) -> Result<u64, String> {
+    chain.code_cost(code)
+        .map(|cost| cost * numeric_part(code))
+        .ok_or_else(|| format!("code “{}” can’t be typed", code))
 }
 
-impl Iterator for Path {
-    type Item = Direction;
+fn run(args: &Args, codes: &[String]) -> Result<(), String> {
+    let door = Keypad::from_layout(&args.door_layout)?;
+    let remote = Keypad::from_layout(&args.remote_layout)?;
 
-    fn next(&mut self) -> Option<Direction> {
-        (self.pos < self.n_entries).then(|| {
-            let dir = if self.pattern & (1 << self.pos) == 0 {
-                self.horizontal_direction
-            } else {
-                self.vertical_direction
-            };
+    let chain = Chain::new(&door, &remote, 2)?;
+    let mut part1 = 0;
 
-            self.pos += 1;
+    for code in codes.iter() {
+        part1 += complexity(&chain, code)?;
 
-            dir
-        })
+        println!("{}: {}", code, chain.code_sequence(code).unwrap());
     }
-}
 
-impl fmt::Display for Path {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for dir in self.clone() {
-            dir.fmt(f)?;
-        }
+    println!("Part 1: {}", part1);
 
-        Ok(())
-    }
-}
+    let chain = Chain::new(&door, &remote, 25)?;
+    let mut part2 = 0;
 
-fn main() -> ExitCode {
-    for path in Movements::new((1, 2), (5, 4), (1, 3)) {
-        println!("{}", path);
+    for code in codes.iter() {
+        part2 += complexity(&chain, code)?;
     }
 
-    ExitCode::SUCCESS
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_path() {
-        assert_eq!(
-            &Path::new(
-                5,
-                Direction::Left,
-                Direction::Up,
-                0x3,
-            ).to_string(),
-            "^^<<<",
-        );
+    println!("Part 2: {}", part2);
 
-        assert_eq!(
-            &Path::new(
-                6,
-                Direction::Right,
-                Direction::Down,
-                0xa,
-            ).to_string(),
-            ">v>v>>",
-        );
+    Ok(())
+}
 
-        assert!(
-            &Path::new(
-                0,
-                Direction::Right,
-                Direction::Down,
-                0,
-            ).next().is_none()
-        );
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let codes = match read_codes() {
+        Ok(codes) => codes,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    if let Err(e) = run(&args, &codes) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
     }
 
-    #[test]
-    fn bad() {
-        let mut movements = Movements::new(
-            (2, 3), (0, 2), (0, 3)
-        ).map(|path| path.to_string());
-
-        assert_eq!(&movements.next().unwrap(), "^<<");
-        assert_eq!(&movements.next().unwrap(), "<^<");
-        assert!(movements.next().is_none());
-
-        let mut movements = Movements::new(
-            (2, 3), (0, 2), (2, 2)
-        ).map(|path| path.to_string());
-
-        assert_eq!(&movements.next().unwrap(), "<^<");
-        assert_eq!(&movements.next().unwrap(), "<<^");
-        assert!(movements.next().is_none());
-    }
+    ExitCode::SUCCESS
 }
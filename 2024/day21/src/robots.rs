@@ -0,0 +1,349 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::keypad::{Keypad, GAP, DIRECTIONS};
+
+// The cheapest way to press a button on one keypad, starting from
+// another button
+#[derive(Debug, Clone)]
+struct Press {
+    // The number of buttons the human has to press
+    cost: u64,
+    // The buttons pressed on the keypad above, ending with A
+    upper: Vec<u8>,
+}
+
+// A keypad on a door operated by a chain of robots. Each robot is
+// controlled by a directional keypad that is pressed by the next
+// robot, and the human presses the last directional keypad.
+pub struct Chain {
+    keypads: Vec<Keypad>,
+    // For each keypad except the human’s one, the cheapest press for
+    // every pair of buttons, indexed by the positions of the buttons
+    // in the keypad
+    presses: Vec<Vec<Option<Press>>>,
+}
+
+impl Chain {
+    pub fn new(
+        door: &Keypad,
+        remote: &Keypad,
+        n_robots: usize,
+    ) -> Result<Chain, String> {
+        for &(direction, _, _) in DIRECTIONS.iter() {
+            if remote.position(direction).is_none() {
+                return Err(format!(
+                    "the robot keypad has no “{}” button",
+                    direction as char,
+                ));
+            }
+        }
+
+        let mut keypads = vec![door.clone()];
+
+        keypads.resize(n_robots + 2, remote.clone());
+
+        // The human can press any button directly so the table is
+        // built from the top down to the door
+        let mut presses = Vec::<Vec<Option<Press>>>::new();
+
+        for level in (0..keypads.len() - 1).rev() {
+            let upper_presses = presses.last();
+            let table = cheapest_presses(
+                &keypads[level],
+                &keypads[level + 1],
+                upper_presses,
+            );
+
+            presses.push(table);
+        }
+
+        presses.reverse();
+
+        Ok(Chain { keypads, presses })
+    }
+
+    fn press(&self, level: usize, from: u8, to: u8) -> Option<&Press> {
+        let keypad = &self.keypads[level];
+        let n_buttons = n_positions(keypad);
+        let from = button_index(keypad, from)?;
+        let to = button_index(keypad, to)?;
+
+        self.presses[level][from * n_buttons + to].as_ref()
+    }
+
+    // The number of buttons the human has to press to type the code
+    // on the door, or None if it can’t be typed
+    pub fn code_cost(&self, code: &str) -> Option<u64> {
+        let mut from = b'A';
+        let mut cost = 0;
+
+        for &button in code.as_bytes() {
+            cost += self.press(0, from, button)?.cost;
+            from = button;
+        }
+
+        Some(cost)
+    }
+
+    // The buttons the human has to press to type the code on the door
+    pub fn code_sequence(&self, code: &str) -> Option<String> {
+        let top_level = self.keypads.len() - 1;
+        let mut sequence = String::new();
+        // Presses to expand, as the level of the keypad and the
+        // buttons to move between
+        let mut stack = Vec::new();
+
+        push_presses(&mut stack, 0, code.as_bytes());
+
+        while let Some((level, from, to)) = stack.pop() {
+            if level == top_level {
+                sequence.push(to as char);
+            } else {
+                let press = self.press(level, from, to)?;
+                push_presses(&mut stack, level + 1, &press.upper);
+            }
+        }
+
+        Some(sequence)
+    }
+}
+
+// Adds each button in the list to the stack in reverse order so that
+// they will be popped in order, starting from A
+fn push_presses(
+    stack: &mut Vec<(usize, u8, u8)>,
+    level: usize,
+    buttons: &[u8],
+) {
+    for (i, &to) in buttons.iter().enumerate().rev() {
+        let from = if i == 0 { b'A' } else { buttons[i - 1] };
+
+        stack.push((level, from, to));
+    }
+}
+
+// Finds the cheapest way to get from each button on the keypad to
+// each other button and press it. The state of the search is the
+// position of the arm on this keypad and the button the arm above is
+// pointing at. If there are no upper presses then the keypad above is
+// the human’s one so every button costs 1.
+fn cheapest_presses(
+    keypad: &Keypad,
+    upper_keypad: &Keypad,
+    upper_presses: Option<&Vec<Option<Press>>>,
+) -> Vec<Option<Press>> {
+    let n_buttons = n_positions(keypad);
+    let n_upper_buttons = n_positions(upper_keypad);
+    let upper_index = |button: u8| {
+        button_index(upper_keypad, button).unwrap()
+    };
+    let upper_cost = |from: u8, to: u8| -> Option<u64> {
+        match upper_presses {
+            None => Some(1),
+            Some(presses) => {
+                let index = upper_index(from) * n_upper_buttons
+                    + upper_index(to);
+                presses[index].as_ref().map(|press| press.cost)
+            },
+        }
+    };
+    let mut table = vec![None; n_buttons * n_buttons];
+    let n_states = n_buttons * n_upper_buttons;
+    let mut costs = vec![u64::MAX; n_states];
+    let mut parents = vec![usize::MAX; n_states];
+    let mut queue = BinaryHeap::new();
+
+    for from in 0..n_buttons {
+        if button_at_index(keypad, from) == GAP {
+            continue;
+        }
+
+        costs.fill(u64::MAX);
+
+        let start = from * n_upper_buttons + upper_index(b'A');
+
+        costs[start] = 0;
+        queue.push(Reverse((0, start)));
+
+        while let Some(Reverse((cost, state))) = queue.pop() {
+            if cost > costs[state] {
+                continue;
+            }
+
+            let pos = state / n_upper_buttons;
+            let upper =
+                button_at_index(upper_keypad, state % n_upper_buttons);
+
+            let pos = (pos % keypad.width(), pos / keypad.width());
+
+            for &(direction, _, _) in DIRECTIONS.iter() {
+                let Some((x, y)) = keypad.step(pos, direction)
+                else { continue; };
+
+                let Some(step_cost) = upper_cost(upper, direction)
+                else { continue; };
+
+                let next = (y * keypad.width() + x) * n_upper_buttons
+                    + upper_index(direction);
+                let next_cost = cost + step_cost;
+
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    parents[next] = state;
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        // Pressing A on the keypad above presses the button
+        for to in 0..n_buttons {
+            let best = (0..n_upper_buttons).filter_map(|upper| {
+                let state = to * n_upper_buttons + upper;
+                let button = button_at_index(upper_keypad, upper);
+
+                if costs[state] == u64::MAX {
+                    return None;
+                }
+
+                upper_cost(button, b'A')
+                    .map(|cost| (costs[state] + cost, state))
+            }).min();
+
+            let Some((cost, mut state)) = best
+            else { continue; };
+
+            let mut upper = vec![b'A'];
+
+            while state != start {
+                upper.push(button_at_index(
+                    upper_keypad,
+                    state % n_upper_buttons,
+                ));
+                state = parents[state];
+            }
+
+            upper.reverse();
+
+            table[from * n_buttons + to] = Some(Press { cost, upper });
+        }
+    }
+
+    table
+}
+
+// The number of buttons on the keypad including the gaps
+fn n_positions(keypad: &Keypad) -> usize {
+    keypad.width() * keypad.height()
+}
+
+// The index of a button in a table with an entry for every position
+// on the keypad
+fn button_index(keypad: &Keypad, button: u8) -> Option<usize> {
+    keypad.position(button).map(|(x, y)| y * keypad.width() + x)
+}
+
+fn button_at_index(keypad: &Keypad, index: usize) -> u8 {
+    let pos = (index % keypad.width(), index / keypad.width());
+
+    keypad.button_at(pos).unwrap_or(GAP)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keypad::{NUMERIC_LAYOUT, DIRECTIONAL_LAYOUT};
+
+    // Types a sequence of human presses through the chain of keypads
+    // and returns what ends up being typed on the door
+    fn simulate(keypads: &[&Keypad], sequence: &str) -> String {
+        // The position of the arm pointing at each keypad except the
+        // human’s one
+        let mut positions = keypads[..keypads.len() - 1].iter()
+            .map(|keypad| keypad.position(b'A').unwrap())
+            .collect::<Vec<_>>();
+        let mut typed = String::new();
+
+        for &button in sequence.as_bytes() {
+            let mut level = positions.len() - 1;
+            let mut button = button;
+
+            loop {
+                if button != b'A' {
+                    positions[level] = keypads[level]
+                        .step(positions[level], button)
+                        .expect("arm pointed at a gap");
+                    break;
+                }
+
+                button = keypads[level].button_at(positions[level]).unwrap();
+
+                if level == 0 {
+                    typed.push(button as char);
+                    break;
+                }
+
+                level -= 1;
+            }
+        }
+
+        typed
+    }
+
+    #[test]
+    fn example() {
+        let door = Keypad::from_layout(NUMERIC_LAYOUT).unwrap();
+        let remote = Keypad::from_layout(DIRECTIONAL_LAYOUT).unwrap();
+
+        let chain = Chain::new(&door, &remote, 0).unwrap();
+        assert_eq!(chain.code_cost("029A"), Some(12));
+
+        let chain = Chain::new(&door, &remote, 2).unwrap();
+
+        for (code, length) in [
+            ("029A", 68),
+            ("980A", 60),
+            ("179A", 68),
+            ("456A", 64),
+            ("379A", 64),
+        ] {
+            let sequence = chain.code_sequence(code).unwrap();
+
+            assert_eq!(chain.code_cost(code), Some(length));
+            assert_eq!(sequence.len() as u64, length);
+            assert_eq!(
+                simulate(&[&door, &remote, &remote, &remote], &sequence),
+                code,
+            );
+        }
+
+        assert_eq!(chain.code_cost("029B"), None);
+
+        let chain = Chain::new(&door, &remote, 25).unwrap();
+        assert_eq!(chain.code_cost("029A"), Some(82050061710));
+    }
+
+    #[test]
+    fn custom_layout() {
+        // The gaps mean that there is only one shortest way between
+        // some of the buttons
+        let door = Keypad::from_layout("12#\n#3A\n").unwrap();
+        let remote = Keypad::from_layout(DIRECTIONAL_LAYOUT).unwrap();
+
+        for n_robots in 0..4 {
+            let chain = Chain::new(&door, &remote, n_robots).unwrap();
+            let sequence = chain.code_sequence("13A21").unwrap();
+            let keypads = std::iter::once(&door)
+                .chain(std::iter::repeat_n(&remote, n_robots + 1))
+                .collect::<Vec<_>>();
+
+            assert_eq!(chain.code_cost("13A21"), Some(sequence.len() as u64));
+            assert_eq!(simulate(&keypads, &sequence), "13A21");
+        }
+
+        let chain = Chain::new(&door, &remote, 0).unwrap();
+        assert_eq!(chain.code_sequence("1").unwrap(), "<^<A");
+
+        // A keypad can’t control a robot without arrows
+        assert!(Chain::new(&door, &door, 1).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+mod parallel;
+
 use std::process::ExitCode;
 use std::collections::HashMap;
 
@@ -51,11 +53,14 @@ fn patterns_for_number(mut number: u64) -> HashMap<[i8; 4], u8> {
     patterns
 }
 
-fn part2(numbers: &[u64]) {
+fn part2(numbers: &[u64], n_threads: usize) {
+    let buyers = parallel::map(numbers, n_threads, |&number| {
+        patterns_for_number(number)
+    });
     let mut pattern_sales = HashMap::new();
 
-    for &number in numbers.iter() {
-        for (pattern, sales) in patterns_for_number(number) {
+    for patterns in buyers {
+        for (pattern, sales) in patterns {
             *pattern_sales.entry(pattern).or_insert(0u32) += sales as u32;
         }
     }
@@ -72,6 +77,14 @@ fn read_numbers() -> Result<Vec<u64>, String> {
 }
 
 fn main() -> ExitCode {
+    let n_threads = match parallel::parse_threads_arg() {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+    };
+
     let numbers = match read_numbers() {
         Ok(numbers) => numbers,
         Err(e) => {
@@ -81,7 +94,7 @@ fn main() -> ExitCode {
     };
 
     part1(&numbers);
-    part2(&numbers);
+    part2(&numbers, n_threads);
 
     ExitCode::SUCCESS
 }
@@ -1,7 +1,10 @@
+mod bitset;
+
 use std::process::ExitCode;
 use std::str::FromStr;
 use std::collections::HashMap;
 use std::fmt;
+use bitset::{BitSet, Elem};
 
 struct Rule {
     before: u8,
@@ -40,7 +43,10 @@ impl fmt::Display for ValidationError {
     }
 }
 
-type RuleBits = HashMap<u8, u128>;
+// Enough bits for every possible u8 page number
+type PageSet = BitSet<[Elem; 4]>;
+
+type RuleBits = HashMap<u8, PageSet>;
 
 impl FromStr for Rule {
     type Err = String;
@@ -51,16 +57,10 @@ impl FromStr for Rule {
             return Err("missing ‘|’".to_string());
         };
 
-        let rule = Rule {
+        Ok(Rule {
             before: before.parse::<u8>().map_err(|e| e.to_string())?,
             after: after.parse::<u8>().map_err(|e| e.to_string())?,
-        };
-
-        if rule.before >= 128 || rule.after >= 128 {
-            return Err("page number too high".to_string());
-        }
-
-        Ok(rule)
+        })
     }
 }
 
@@ -128,30 +128,24 @@ fn rules_to_bitset<'a, I: IntoIterator<Item = &'a Rule>>(rules: I) -> RuleBits {
     let mut rule_bits = HashMap::new();
 
     for rule in rules {
-        let bit = 1u128 << rule.after;
-
         rule_bits.entry(rule.before)
-            .and_modify(|mask| *mask |= bit)
-            .or_insert(bit);
+            .or_insert_with(PageSet::default)
+            .set(rule.after as usize);
     }
 
     rule_bits
 }
 
-fn all_befores(rule_bits: &RuleBits, top_rule: u8) -> u128 {
-    let mut mask = 0u128;
+fn all_befores(rule_bits: &RuleBits, top_rule: u8) -> PageSet {
+    let mut mask = PageSet::default();
     let mut stack = vec![top_rule];
 
     while let Some(rule_num) = stack.pop() {
         if let Some(&(mut bits)) = rule_bits.get(&rule_num) {
-            bits &= !mask;
-            mask |= bits;
+            bits -= &mask;
+            mask |= &bits;
 
-            while bits != 0 {
-                let next_bit = bits.trailing_zeros();
-                stack.push(next_bit as u8);
-                bits &= !(1u128 << next_bit);
-            }
+            stack.extend(bits.bits().map(|page| page as u8));
         }
     }
 
@@ -167,16 +161,16 @@ fn fill_befores(rule_bits: &RuleBits) -> RuleBits {
 
 fn rule_bits_for_update(rules: &[Rule], update: &[u8]) -> RuleBits {
     // Get a bitmask of all pages in the update
-    let page_mask = update.iter().fold(0u128, {
-        |mask, rule| mask | 1u128 << rule
-    });
+    let page_mask = update.iter()
+        .map(|&page| page as usize)
+        .collect::<PageSet>();
 
     let rule_bits = rules_to_bitset(
         rules.iter()
         // Filter out rules that don’t concern the pages in the update
             .filter(|rule| {
-                page_mask & (1u128 << rule.before) != 0 &&
-                    page_mask & (1u128 << rule.after) != 0
+                page_mask.contains(rule.before as usize) &&
+                    page_mask.contains(rule.after as usize)
             })
     );
 
@@ -187,8 +181,8 @@ fn validate_update(
     rule_bits: &RuleBits,
     update: &[u8],
 ) -> Result<(), ValidationError> {
-    for (&rule, &bits) in rule_bits.iter() {
-        if bits & (1u128 << rule) != 0 {
+    for (&rule, bits) in rule_bits.iter() {
+        if bits.contains(rule as usize) {
             return Err(ValidationError::Cycle(rule));
         }
     }
@@ -201,7 +195,7 @@ fn validate_update(
     for &page in update.iter().rev().skip(1) {
         rule_bits
             .get(&page)
-            .and_then(|bits| (bits & (1u128 << next) != 0).then_some(()))
+            .and_then(|bits| bits.contains(next as usize).then_some(()))
             .ok_or_else(|| {
                 ValidationError::NoRule(page, next)
             })?;
@@ -236,7 +230,7 @@ fn main() -> ExitCode {
                     u32::MAX -
                         rule_bits
                         .get(page)
-                        .map(|bits| bits.count_ones())
+                        .map(|bits| bits.len() as u32)
                         .unwrap_or(0)
                 });
 
@@ -1,5 +1,8 @@
 use std::process::ExitCode;
 
+// The cycle module has more helpers than this day needs
+#[allow(dead_code)]
+mod cycle;
 mod util;
 mod walker;
 
@@ -39,39 +42,46 @@ fn part1(mut grid: Grid, mut pos: (i32, i32)) -> usize {
     }
 }
 
-fn is_loop(grid: &mut Grid, mut pos: (i32, i32)) -> bool {
-    let mut direction = QuadDirection::Up;
+#[derive(Clone, PartialEq, Eq)]
+struct Guard {
+    pos: (i32, i32),
+    direction: QuadDirection,
+}
 
-    *grid.get_mut(pos).unwrap() = b'0' + (1u8 << direction as u8);
+impl Guard {
+    fn new(pos: (i32, i32)) -> Guard {
+        Guard {
+            pos,
+            direction: QuadDirection::Up,
+        }
+    }
 
-    loop {
-        let next_pos = direction.move_pos(pos);
+    // Moves or turns the guard. Returns false if the guard left the
+    // grid.
+    fn step(&mut self, grid: &Grid, obstacle: (i32, i32)) -> bool {
+        let next_pos = self.direction.move_pos(self.pos);
 
         match grid.get(next_pos) {
-            Some(b'#') => direction = direction.turn_right(),
-            Some(grid_byte) => {
-                pos = next_pos;
-
-                if grid_byte == b'.' {
-                    *grid.get_mut(pos).unwrap() =
-                        b'0' + (1u8 << direction as u8);
-                } else {
-                    // Have we already been here in the same direction?
-                    let found_loop = grid_byte & (1u8 << direction as u8) != 0;
-                    *grid.get_mut(pos).unwrap() |= 1u8 << direction as u8;
-                    if found_loop {
-                        break true;
-                    }
-                }
+            Some(b'#') => self.direction = self.direction.turn_right(),
+            Some(_) if next_pos == obstacle => {
+                self.direction = self.direction.turn_right();
             },
-            None => break false,
+            Some(_) => self.pos = next_pos,
+            None => return false,
         }
+
+        true
     }
 }
 
+fn is_loop(grid: &Grid, pos: (i32, i32), obstacle: (i32, i32)) -> bool {
+    cycle::find_terminating(&Guard::new(pos), |guard| {
+        guard.step(grid, obstacle)
+    }).is_some()
+}
+
 fn part2(grid: &Grid, pos: (i32, i32)) -> usize {
     let mut count = 0;
-    let mut grid_copy = grid.clone();
 
     for y in 0..grid.height {
         for x in 0..grid.width {
@@ -79,10 +89,7 @@ fn part2(grid: &Grid, pos: (i32, i32)) -> usize {
                 continue;
             }
 
-            grid.values.clone_into(&mut grid_copy.values);
-            grid_copy.values[y * grid.width + x] = b'#';
-
-            if is_loop(&mut grid_copy, pos) {
+            if is_loop(grid, pos, (x as i32, y as i32)) {
                 count += 1;
             }
         }
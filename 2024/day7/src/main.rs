@@ -1,3 +1,5 @@
+mod parallel;
+
 use std::process::ExitCode;
 use std::str::FromStr;
 
@@ -88,10 +90,18 @@ fn find_solution(equation: &Equation) -> Option<u32> {
     None
 }
 
-fn part1(equations: &[Equation]) -> u64 {
-    equations.iter().filter_map(|equation| {
-        find_solution(equation).is_some().then_some(equation.test_value)
-    }).sum::<u64>()
+// Adds up the test values of the equations that func finds a solution
+// for, checking the equations in parallel
+fn sum_solvable<F>(equations: &[Equation], n_threads: usize, func: F) -> u64
+    where F: Fn(&Equation) -> Option<u32> + Sync
+{
+    parallel::map(equations, n_threads, |equation| {
+        func(equation).is_some().then_some(equation.test_value)
+    }).into_iter().flatten().sum::<u64>()
+}
+
+fn part1(equations: &[Equation], n_threads: usize) -> u64 {
+    sum_solvable(equations, n_threads, find_solution)
 }
 
 fn elephant_operator(a: u64, b: u64) -> u64 {
@@ -129,15 +139,19 @@ fn find_elephant_solution(equation: &Equation) -> Option<u32> {
     None
 }
 
-fn part2(equations: &[Equation]) -> u64 {
-    equations.iter().filter_map(|equation| {
-        find_elephant_solution(equation)
-            .is_some()
-            .then_some(equation.test_value)
-    }).sum::<u64>()
+fn part2(equations: &[Equation], n_threads: usize) -> u64 {
+    sum_solvable(equations, n_threads, find_elephant_solution)
 }
 
 fn main() -> ExitCode {
+    let n_threads = match parallel::parse_threads_arg() {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        },
+    };
+
     let equations = match read_equations(std::io::stdin().lines()) {
         Ok(e) => e,
         Err(e) => {
@@ -146,8 +160,8 @@ fn main() -> ExitCode {
         },
     };
 
-    println!("Part 1: {}", part1(&equations));
-    println!("Part 2: {}", part2(&equations));
+    println!("Part 1: {}", part1(&equations, n_threads));
+    println!("Part 2: {}", part2(&equations, n_threads));
 
     ExitCode::SUCCESS
 }
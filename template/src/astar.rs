@@ -0,0 +1,213 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+// A state in a search for the cheapest way to reach a goal. States
+// are hashed so that each one is only searched from its cheapest
+// known cost.
+pub trait SearchState: Clone + Eq + Hash {
+    // The states reachable in one move from this one along with the
+    // cost of the move
+    fn successors(&self) -> impl Iterator<Item = (Self, u64)>;
+
+    // An estimate of the cost to get from this state to a goal. This
+    // must never be more than the real cost or the result might not
+    // be the cheapest. Returning 0 makes the search Dijkstra’s
+    // algorithm.
+    fn heuristic(&self) -> u64;
+
+    fn is_goal(&self) -> bool;
+}
+
+#[derive(Debug, Clone)]
+pub struct Path<S> {
+    pub cost: u64,
+    // Every state from the start to the goal with the total cost to
+    // reach it
+    pub steps: Vec<(S, u64)>,
+    // The number of states that were taken from the queue
+    pub n_expanded: usize,
+}
+
+struct Node<S> {
+    state: S,
+    cost: u64,
+    parent: Option<usize>,
+}
+
+// Finds the cheapest route from the start to a goal state using the
+// A* algorithm. Returns None if no goal can be reached.
+pub fn astar<S: SearchState>(start: S) -> Option<Path<S>> {
+    let mut nodes = vec![Node { state: start.clone(), cost: 0, parent: None }];
+    let mut node_nums = HashMap::from([(start.clone(), 0)]);
+    // The queue is ordered by the estimated total cost and then by the
+    // cost so far so that ties are broken towards the goal
+    let mut queue = BinaryHeap::from([
+        Reverse((start.heuristic(), Reverse(0), 0)),
+    ]);
+    let mut n_expanded = 0;
+
+    while let Some(Reverse((_, Reverse(cost), node_num))) = queue.pop() {
+        // Skip entries that were queued before a cheaper route was
+        // found to the same state
+        if cost > nodes[node_num].cost {
+            continue;
+        }
+
+        n_expanded += 1;
+
+        if nodes[node_num].state.is_goal() {
+            return Some(reconstruct_path(nodes, node_num, n_expanded));
+        }
+
+        let successors = nodes[node_num].state.successors()
+            .collect::<Vec<_>>();
+
+        for (next_state, move_cost) in successors {
+            let next_cost = cost + move_cost;
+
+            let next_num = match node_nums.get(&next_state) {
+                Some(&next_num) => {
+                    let node = &mut nodes[next_num];
+
+                    if node.cost <= next_cost {
+                        continue;
+                    }
+
+                    node.cost = next_cost;
+                    node.parent = Some(node_num);
+
+                    next_num
+                },
+                None => {
+                    let next_num = nodes.len();
+
+                    node_nums.insert(next_state.clone(), next_num);
+                    nodes.push(Node {
+                        state: next_state,
+                        cost: next_cost,
+                        parent: Some(node_num),
+                    });
+
+                    next_num
+                },
+            };
+
+            let estimate = next_cost + nodes[next_num].state.heuristic();
+
+            queue.push(Reverse((estimate, Reverse(next_cost), next_num)));
+        }
+    }
+
+    None
+}
+
+fn reconstruct_path<S: Clone>(
+    nodes: Vec<Node<S>>,
+    goal: usize,
+    n_expanded: usize,
+) -> Path<S> {
+    let mut steps = Vec::new();
+    let mut node_num = Some(goal);
+
+    while let Some(num) = node_num {
+        let node = &nodes[num];
+
+        steps.push((node.state.clone(), node.cost));
+        node_num = node.parent;
+    }
+
+    steps.reverse();
+
+    Path { cost: nodes[goal].cost, steps, n_expanded }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Walks on a grid where each cell costs its digit to enter
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Walk<'a> {
+        grid: &'a [&'a [u8]],
+        pos: (usize, usize),
+        use_heuristic: bool,
+    }
+
+    impl<'a> Walk<'a> {
+        fn goal(&self) -> (usize, usize) {
+            (self.grid[0].len() - 1, self.grid.len() - 1)
+        }
+    }
+
+    impl<'a> SearchState for Walk<'a> {
+        fn successors(&self) -> impl Iterator<Item = (Self, u64)> {
+            let (x, y) = self.pos;
+
+            [(1, 0), (0, 1), (-1, 0), (0, -1)].into_iter()
+                .filter_map(move |(dx, dy)| {
+                    let x = x.checked_add_signed(dx)?;
+                    let y = y.checked_add_signed(dy)?;
+                    let &cell = self.grid.get(y)?.get(x)?;
+
+                    (cell != b'#').then(|| {
+                        let next = Walk { pos: (x, y), ..self.clone() };
+                        (next, (cell - b'0') as u64)
+                    })
+                })
+        }
+
+        fn heuristic(&self) -> u64 {
+            if !self.use_heuristic {
+                return 0;
+            }
+
+            let (gx, gy) = self.goal();
+
+            (self.pos.0.abs_diff(gx) + self.pos.1.abs_diff(gy)) as u64
+        }
+
+        fn is_goal(&self) -> bool {
+            self.pos == self.goal()
+        }
+    }
+
+    #[test]
+    fn walk() {
+        let grid: &[&[u8]] = &[
+            b"11111",
+            b"9###1",
+            b"19111",
+            b"1#1#9",
+            b"11111",
+        ];
+
+        let results = [false, true].map(|use_heuristic| {
+            astar(Walk { grid, pos: (0, 0), use_heuristic }).unwrap()
+        });
+
+        for path in results.iter() {
+            assert_eq!(path.cost, 12);
+            assert_eq!(path.steps.len(), 13);
+            assert_eq!(path.steps[0].0.pos, (0, 0));
+            assert_eq!(path.steps[0].1, 0);
+            assert_eq!(path.steps.last().unwrap().1, 12);
+
+            for pair in path.steps.windows(2) {
+                let (a, b) = (&pair[0].0, &pair[1].0);
+                assert_eq!(a.pos.0.abs_diff(b.pos.0)
+                           + a.pos.1.abs_diff(b.pos.1), 1);
+                assert!(pair[0].1 < pair[1].1);
+            }
+        }
+
+        // The heuristic should avoid looking at some of the states
+        let [dijkstra, a_star] = &results;
+        assert!(a_star.n_expanded < dijkstra.n_expanded);
+
+        // Walled off from the goal
+        let grid: &[&[u8]] = &[b"11#", b"1#1"];
+        assert!(astar(Walk { grid, pos: (0, 0), use_heuristic: true })
+                .is_none());
+    }
+}
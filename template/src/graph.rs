@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use crate::distance_table::DistanceTable;
+
+pub const UNREACHABLE: u32 = u32::MAX;
+
+// A directed graph with weighted edges. The edges are stored in a
+// single list sorted by the node that they come from so that the
+// neighbours of a node are just a slice of the list.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    // Index of the first edge for each node, with an extra entry at
+    // the end for the total number of edges
+    offsets: Box<[usize]>,
+    // The node that each edge goes to and its weight
+    edges: Box<[(usize, u32)]>,
+}
+
+impl Graph {
+    pub fn from_edges<I>(n_nodes: usize, edges: I) -> Graph
+        where I: IntoIterator<Item = (usize, usize, u32)>
+    {
+        let mut edges = edges.into_iter().collect::<Vec<_>>();
+
+        assert!(edges.iter().all(|&(a, b, _)| a < n_nodes && b < n_nodes));
+
+        // Stable sort so that the neighbours stay in the order they
+        // were added
+        edges.sort_by_key(|&(from, _, _)| from);
+
+        let mut offsets = Vec::with_capacity(n_nodes + 1);
+        let mut edge_num = 0;
+
+        for node in 0..=n_nodes {
+            while edges.get(edge_num).is_some_and(|e| e.0 < node) {
+                edge_num += 1;
+            }
+
+            offsets.push(edge_num);
+        }
+
+        Graph {
+            offsets: offsets.into_boxed_slice(),
+            edges: edges.into_iter().map(|(_, to, weight)| (to, weight))
+                .collect(),
+        }
+    }
+
+    // Adds each edge in both directions
+    pub fn from_undirected_edges<I>(n_nodes: usize, edges: I) -> Graph
+        where I: IntoIterator<Item = (usize, usize, u32)>
+    {
+        Graph::from_edges(
+            n_nodes,
+            edges.into_iter().flat_map(|(a, b, weight)| {
+                [(a, b, weight), (b, a, weight)]
+            }),
+        )
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn n_edges(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn neighbours(&self, node: usize) -> &[(usize, u32)] {
+        &self.edges[self.offsets[node]..self.offsets[node + 1]]
+    }
+
+    // Counts the number of edges on the shortest route from the start
+    // to every node. The weights of the edges are ignored.
+    pub fn bfs_distances(&self, start: usize) -> Vec<u32> {
+        let mut distances = vec![UNREACHABLE; self.n_nodes()];
+        let mut queue = VecDeque::from([start]);
+
+        distances[start] = 0;
+
+        while let Some(node) = queue.pop_front() {
+            let distance = distances[node];
+
+            for &(next, _) in self.neighbours(node) {
+                if distances[next] == UNREACHABLE {
+                    distances[next] = distance + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    // Does a breadth-first search from every node. This ignores the
+    // weights but for a sparse graph it is a lot quicker than
+    // floyd_warshall.
+    pub fn all_pairs_bfs(&self) -> DistanceTable {
+        let distances = (0..self.n_nodes())
+            .map(|node| self.bfs_distances(node))
+            .collect::<Vec<_>>();
+
+        DistanceTable::from_fn(self.n_nodes(), |from, to| {
+            let distance = distances[from][to];
+            (distance != UNREACHABLE).then_some(distance)
+        })
+    }
+
+    // Finds the shortest weighted distance between every pair of nodes
+    pub fn floyd_warshall(&self) -> DistanceTable {
+        let n_nodes = self.n_nodes();
+        let mut distances = vec![UNREACHABLE; n_nodes * n_nodes];
+
+        for node in 0..n_nodes {
+            distances[node * n_nodes + node] = 0;
+
+            for &(next, weight) in self.neighbours(node) {
+                let distance = &mut distances[node * n_nodes + next];
+                *distance = (*distance).min(weight);
+            }
+        }
+
+        for via in 0..n_nodes {
+            for from in 0..n_nodes {
+                let first = distances[from * n_nodes + via];
+
+                if first == UNREACHABLE {
+                    continue;
+                }
+
+                for to in 0..n_nodes {
+                    let second = distances[via * n_nodes + to];
+
+                    if second == UNREACHABLE {
+                        continue;
+                    }
+
+                    let distance = &mut distances[from * n_nodes + to];
+                    *distance = (*distance).min(first + second);
+                }
+            }
+        }
+
+        DistanceTable::from_fn(n_nodes, |from, to| {
+            let distance = distances[from * n_nodes + to];
+            (distance != UNREACHABLE).then_some(distance)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn adjacency() {
+        let graph = Graph::from_edges(
+            4,
+            [(2, 0, 5), (0, 1, 1), (2, 3, 2), (0, 2, 7)],
+        );
+
+        assert_eq!(graph.n_nodes(), 4);
+        assert_eq!(graph.n_edges(), 4);
+        assert_eq!(graph.neighbours(0), [(1, 1), (2, 7)]);
+        assert_eq!(graph.neighbours(1), []);
+        assert_eq!(graph.neighbours(2), [(0, 5), (3, 2)]);
+        assert_eq!(graph.neighbours(3), []);
+
+        let graph = Graph::from_undirected_edges(3, [(0, 1, 1), (1, 2, 4)]);
+
+        assert_eq!(graph.neighbours(0), [(1, 1)]);
+        assert_eq!(graph.neighbours(1), [(0, 1), (2, 4)]);
+        assert_eq!(graph.neighbours(2), [(1, 4)]);
+    }
+
+    #[test]
+    fn all_pairs() {
+        // A ring of 6 nodes with a shortcut and a node on its own
+        let graph = Graph::from_undirected_edges(
+            7,
+            (0..6).map(|i| (i, (i + 1) % 6, 1)).chain([(0, 3, 1)]),
+        );
+
+        assert_eq!(graph.bfs_distances(1), [1, 0, 1, 2, 3, 2, UNREACHABLE]);
+
+        let bfs = graph.all_pairs_bfs();
+        let floyd_warshall = graph.floyd_warshall();
+
+        for from in 0..graph.n_nodes() {
+            for to in 0..graph.n_nodes() {
+                assert_eq!(bfs.get(from, to), floyd_warshall.get(from, to));
+            }
+        }
+
+        assert_eq!(bfs.get(2, 5), Some(3));
+        assert_eq!(bfs.get(6, 0), None);
+        assert_eq!(bfs.get(6, 6), Some(0));
+
+        // Floyd–Warshall uses the weights
+        let graph = Graph::from_edges(
+            3,
+            [(0, 1, 10), (0, 2, 3), (2, 1, 4), (1, 0, 1)],
+        );
+        let distances = graph.floyd_warshall();
+
+        assert_eq!(distances.get(0, 1), Some(7));
+        assert_eq!(distances.get(2, 0), Some(5));
+        assert_eq!(distances.get(1, 2), Some(4));
+        assert_eq!(graph.all_pairs_bfs().get(0, 1), Some(1));
+    }
+}
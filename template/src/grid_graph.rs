@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+use crate::graph::{Graph, UNREACHABLE};
+use crate::util::Grid;
+use crate::walker::{Direction, QuadDirection};
+
+// Builds a graph of the routes between points of interest on a grid.
+// Each point becomes a node numbered by its position in the list.
+// There is an edge between two points when there is a route between
+// them that doesn’t pass through any of the other points and its
+// weight is the length of the shortest such route. is_open decides
+// which grid values can be walked on.
+pub fn from_grid<F>(grid: &Grid, points: &[(i32, i32)], is_open: F) -> Graph
+    where F: Fn(u8) -> bool
+{
+    let point_nums = points.iter()
+        .enumerate()
+        .map(|(point_num, &pos)| (pos, point_num))
+        .collect::<HashMap<_, _>>();
+    let mut distances = vec![UNREACHABLE; grid.width * grid.height];
+    let mut visited = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut edges = Vec::new();
+
+    for (start_num, &start) in points.iter().enumerate() {
+        let Some(start_index) = grid_index(grid, start)
+        else { continue; };
+
+        distances[start_index] = 0;
+        visited.push(start_index);
+        queue.push_back(start);
+
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[grid_index(grid, pos).unwrap()];
+
+            // Don’t walk through other points because the route will be
+            // found by going through its node instead
+            if let Some(&point_num) = point_nums.get(&pos) {
+                if point_num != start_num {
+                    edges.push((start_num, point_num, distance));
+                    continue;
+                }
+            }
+
+            let mut direction = Some(QuadDirection::first_direction());
+
+            while let Some(d) = direction {
+                let next_pos = d.move_pos(pos);
+
+                direction = d.next_direction();
+
+                let Some(index) = grid_index(grid, next_pos)
+                else { continue; };
+
+                if distances[index] == UNREACHABLE
+                    && is_open(grid.values[index])
+                {
+                    distances[index] = distance + 1;
+                    visited.push(index);
+                    queue.push_back(next_pos);
+                }
+            }
+        }
+
+        for index in visited.drain(..) {
+            distances[index] = UNREACHABLE;
+        }
+    }
+
+    edges.sort_unstable();
+
+    Graph::from_edges(points.len(), edges)
+}
+
+fn grid_index(grid: &Grid, (x, y): (i32, i32)) -> Option<usize> {
+    if x < 0 || y < 0 || x as usize >= grid.width || y as usize >= grid.height {
+        None
+    } else {
+        Some(y as usize * grid.width + x as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let mut test_input: &[u8] = b"###########\n\
+                                      #0.1.....2#\n\
+                                      #.#######.#\n\
+                                      #4.......3#\n\
+                                      ###########\n";
+        let grid = Grid::load(&mut test_input).unwrap();
+        let points = (b'0'..=b'4').map(|digit| {
+            let index = grid.values.iter().position(|&b| b == digit).unwrap();
+            ((index % grid.width) as i32, (index / grid.width) as i32)
+        }).collect::<Vec<_>>();
+
+        let graph = from_grid(&grid, &points, |b| b != b'#');
+
+        assert_eq!(graph.neighbours(0), [(1, 2), (4, 2)]);
+        assert_eq!(graph.neighbours(1), [(0, 2), (2, 6)]);
+        assert_eq!(graph.neighbours(2), [(1, 6), (3, 2)]);
+        assert_eq!(graph.neighbours(3), [(2, 2), (4, 8)]);
+        assert_eq!(graph.neighbours(4), [(0, 2), (3, 8)]);
+
+        let distances = graph.floyd_warshall();
+
+        assert_eq!(distances.get(0, 3), Some(10));
+        assert_eq!(distances.get(4, 2), Some(10));
+    }
+}
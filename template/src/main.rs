@@ -1,5 +1,21 @@
 mod util;
 mod walker;
+#[allow(dead_code)]
+mod cycle;
+#[allow(dead_code)]
+mod graph;
+#[allow(dead_code)]
+mod distance_table;
+#[allow(dead_code)]
+mod grid_graph;
+#[allow(dead_code)]
+mod tsp;
+#[allow(dead_code)]
+mod search;
+#[allow(dead_code)]
+mod parallel;
+#[allow(dead_code)]
+mod astar;
 
 use std::io::BufRead;
 use std::process::ExitCode;
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The number of threads to use when the user doesn’t pick one
+pub fn default_n_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Parses the command line arguments for a program that only takes
+// “--threads N” and returns the number of threads to use
+pub fn parse_threads_arg() -> Result<usize, String> {
+    threads_from_args(std::env::args().skip(1))
+}
+
+fn threads_from_args<I>(args: I) -> Result<usize, String>
+    where I: IntoIterator<Item = String>
+{
+    let mut args = args.into_iter();
+    let mut n_threads = default_n_threads();
+
+    while let Some(arg) = args.next() {
+        if arg == "--threads" {
+            let Some(n) = args.next().and_then(|n| n.parse().ok())
+            else {
+                return Err("--threads needs a number".to_string());
+            };
+
+            n_threads = n;
+        } else {
+            return Err(format!("unknown argument: {}", arg));
+        }
+    }
+
+    Ok(n_threads)
+}
+
+// Calls func on every item using up to n_threads threads and returns
+// the results in the same order as the items. Each thread takes the
+// next item that hasn’t been started yet so that one slow item
+// doesn’t hold up a whole chunk of others.
+pub fn map<T, R, F>(items: &[T], n_threads: usize, func: F) -> Vec<R>
+    where T: Sync,
+          R: Send,
+          F: Fn(&T) -> R + Sync
+{
+    let n_threads = n_threads.min(items.len());
+
+    if n_threads <= 1 {
+        return items.iter().map(func).collect();
+    }
+
+    let next_item = AtomicUsize::new(0);
+    let mut results = std::iter::repeat_with(|| None)
+        .take(items.len())
+        .collect::<Vec<Option<R>>>();
+
+    std::thread::scope(|scope| {
+        let handles = (0..n_threads).map(|_| {
+            scope.spawn(|| {
+                let mut done = Vec::new();
+
+                loop {
+                    let index = next_item.fetch_add(1, Ordering::Relaxed);
+
+                    let Some(item) = items.get(index)
+                    else { break; };
+
+                    done.push((index, func(item)));
+                }
+
+                done
+            })
+        }).collect::<Vec<_>>();
+
+        for handle in handles {
+            for (index, result) in handle.join().unwrap() {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        let items = (0..100u64).collect::<Vec<_>>();
+        let expected = items.iter().map(|&i| i * i).collect::<Vec<_>>();
+
+        for n_threads in [0, 1, 2, 3, 8, 200] {
+            let results = map(&items, n_threads, |&i| {
+                // Make the early items slow so that they finish last
+                if i < 4 {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+
+                i * i
+            });
+
+            assert_eq!(results, expected);
+        }
+
+        assert_eq!(map(&[] as &[u8], 4, |&b| b), []);
+    }
+
+    #[test]
+    fn uses_threads() {
+        let items = [(); 4];
+        let main_thread = std::thread::current().id();
+
+        let thread_ids = map(&items, 4, |_| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::thread::current().id()
+        });
+
+        assert!(thread_ids.iter().all(|&id| id != main_thread));
+        assert_eq!(
+            map(&items, 1, |_| std::thread::current().id()),
+            [main_thread; 4],
+        );
+    }
+
+    #[test]
+    fn threads_arg() {
+        let parse = |args: &[&str]| {
+            threads_from_args(args.iter().map(|arg| arg.to_string()))
+        };
+
+        assert_eq!(parse(&[]), Ok(default_n_threads()));
+        assert_eq!(parse(&["--threads", "3"]), Ok(3));
+        assert_eq!(parse(&["--threads", "3", "--threads", "0"]), Ok(0));
+        assert_eq!(
+            parse(&["--threads"]),
+            Err("--threads needs a number".to_string()),
+        );
+        assert_eq!(
+            parse(&["--threads", "lots"]),
+            Err("--threads needs a number".to_string()),
+        );
+        assert_eq!(
+            parse(&["--fast"]),
+            Err("unknown argument: --fast".to_string()),
+        );
+    }
+}